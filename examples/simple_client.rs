@@ -1,4 +1,5 @@
-use na_kraken_client::{KrakenClient, Config, Error};
+use na_kraken_client::KrakenClient;
+use na_kraken_client::utils::PairLookup;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -13,17 +14,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let asset_pairs = client.public().get_asset_pairs(Some(vec!["XBTUSD", "ETHUSD"])).await?;
     for (pair_name, pair_info) in asset_pairs {
         println!("Pair: {}", pair_name);
-        println!("  Altname: {}", pair_info.altname);
-        println!("  Base: {}", pair_info.base);
-        println!("  Quote: {}", pair_info.quote);
-        println!("  Pair decimals: {}", pair_info.pair_decimals);
-        println!("  Lot decimals: {}", pair_info.lot_decimals);
+        println!("  Altname: {}", pair_info.altname.unwrap_or_default());
+        println!("  Base: {}", pair_info.base.unwrap_or_default());
+        println!("  Quote: {}", pair_info.quote.unwrap_or_default());
+        println!("  Pair decimals: {}", pair_info.pair_decimals.unwrap_or_default());
+        println!("  Lot decimals: {}", pair_info.lot_decimals.unwrap_or_default());
         println!();
     }
     
     println!("Fetching ticker information for BTC/USD...");
-    let ticker = client.public().get_ticker(vec!["XBTUSD"]).await?;
-    if let Some(btc_ticker) = ticker.get("XXBTZUSD") {
+    let (ticker, _failed) = client.public().get_ticker(vec!["XBTUSD"]).await?;
+    if let Some(btc_ticker) = ticker.get_pair("XBTUSD") {
         println!("BTC/USD Ticker:");
         println!("  Last trade: {}", btc_ticker.c[0]);
         println!("  Today's volume: {}", btc_ticker.v[0]);
@@ -35,6 +36,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Uncomment to use authenticated endpoints
     // Note: You need to set your API key and secret
     /*
+    use na_kraken_client::{Config, Error};
+    
     println!("\nSetting up authenticated client...");
     let config = Config::new()
         .with_api_key("your-api-key")
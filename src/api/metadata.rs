@@ -0,0 +1,115 @@
+//! Cache of asset and asset pair metadata
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::error::Result;
+use crate::models::market::{AssetInfo, AssetPair};
+
+/// Cached value and when it was fetched
+type Slot<T> = RwLock<Option<(Instant, Arc<T>)>>;
+
+/// Asset pair and asset metadata shared by clones of a client
+///
+/// Entries are fetched on first use and refetched once they are older than
+/// [`crate::config::Config::metadata_ttl`].
+#[derive(Debug, Clone, Default)]
+pub struct MetadataCache {
+    /// Asset pairs by canonical name
+    asset_pairs: Arc<Slot<HashMap<String, AssetPair>>>,
+    
+    /// Assets by canonical name
+    assets: Arc<Slot<HashMap<String, AssetInfo>>>,
+}
+
+impl MetadataCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Drop all cached entries, so the next lookup fetches them again
+    pub async fn clear(&self) {
+        *self.asset_pairs.write().await = None;
+        *self.assets.write().await = None;
+    }
+    
+    /// Get the asset pairs, calling `fetch` if they are missing or expired
+    pub(crate) async fn asset_pairs<F, Fut>(&self, ttl: Option<Duration>, fetch: F) -> Result<Arc<HashMap<String, AssetPair>>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<HashMap<String, AssetPair>>>,
+    {
+        get_or_fetch(&self.asset_pairs, ttl, fetch).await
+    }
+    
+    /// Get the assets, calling `fetch` if they are missing or expired
+    pub(crate) async fn assets<F, Fut>(&self, ttl: Option<Duration>, fetch: F) -> Result<Arc<HashMap<String, AssetInfo>>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<HashMap<String, AssetInfo>>>,
+    {
+        get_or_fetch(&self.assets, ttl, fetch).await
+    }
+}
+
+/// Get a cached value, refetching it once it is older than `ttl`
+///
+/// Without a `ttl` caching is disabled and every call fetches.
+async fn get_or_fetch<T, F, Fut>(slot: &Slot<T>, ttl: Option<Duration>, fetch: F) -> Result<Arc<T>>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let ttl = match ttl {
+        Some(ttl) => ttl,
+        None => return fetch().await.map(Arc::new),
+    };
+    
+    if let Some((fetched_at, ref value)) = *slot.read().await {
+        if fetched_at.elapsed() < ttl {
+            return Ok(value.clone());
+        }
+    }
+    
+    let mut slot = slot.write().await;
+    
+    // Another task may have refreshed the entry while this one waited for the lock
+    if let Some((fetched_at, ref value)) = *slot {
+        if fetched_at.elapsed() < ttl {
+            return Ok(value.clone());
+        }
+    }
+    
+    let value = Arc::new(fetch().await?);
+    *slot = Some((Instant::now(), value.clone()));
+    
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    
+    #[tokio::test]
+    async fn test_get_or_fetch() {
+        let slot: Slot<u32> = RwLock::new(None);
+        let ttl = Some(Duration::from_millis(50));
+        
+        assert_eq!(*get_or_fetch(&slot, ttl, || async { Ok(1) }).await.unwrap(), 1);
+        assert_eq!(*get_or_fetch(&slot, ttl, || async { Ok(2) }).await.unwrap(), 1);
+        
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        
+        // A failed refresh leaves the expired entry in place for the next attempt
+        assert!(get_or_fetch(&slot, ttl, || async { Err(Error::Other("down".to_string())) }).await.is_err());
+        assert_eq!(*get_or_fetch(&slot, ttl, || async { Ok(3) }).await.unwrap(), 3);
+        
+        // Without a TTL every call fetches
+        assert_eq!(*get_or_fetch(&slot, None, || async { Ok(4) }).await.unwrap(), 4);
+    }
+}
@@ -4,8 +4,9 @@ pub mod public;
 pub mod private;
 pub mod websocket;
 pub mod rate_limiter;
+pub mod metadata;
 
 // Re-export commonly used types
-pub use public::PublicApi;
-pub use private::PrivateApi;
+pub use public::{PublicApi, PublicApiTrait};
+pub use private::{PrivateApi, PrivateApiTrait};
 pub use websocket::WebSocketApi;
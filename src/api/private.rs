@@ -1,15 +1,35 @@
 //! Private API endpoints for the Kraken API
+//!
+//! # Paging
+//!
+//! History endpoints take an `ofs` offset and return [`PAGE_SIZE`](crate::models::account::PAGE_SIZE)
+//! entries per page, newest first, along with the total `count`. The `*_stream` methods
+//! request each page only once the previous one has been consumed, through the private
+//! rate limiter like any other call, so dropping the stream stops fetching. A stream ends
+//! once `count` entries have been fetched, after an empty page, or after an error.
+//!
+//! Offsets count from the newest entry, so entries added while a stream is read shift
+//! the later pages: an entry can appear on two pages, and removed entries can make
+//! others be skipped. The `get_all_*` and `*_between` methods merge pages by ID, which
+//! removes the duplicates; the streams yield pages as they are. Passing a fixed `end`
+//! keeps new entries out of the range.
 
+use futures::{stream, Stream, StreamExt};
 use std::collections::HashMap;
+use std::future::Future;
 use serde::Deserialize;
 use serde_json::Value;
 
-use crate::auth::{generate_nonce, sign_message};
+use crate::api::rate_limiter::endpoint_cost;
 use crate::client::KrakenClient;
-use crate::error::{Error, Result};
-use crate::models::account::{Balance, TradeBalance, OpenOrders, ClosedOrders, Ledger, TradeHistory};
-use crate::models::trading::{Order, OrderResponse, OrderInfo, TradeInfo};
-use crate::utils::hashmap_to_url_encoded;
+use crate::config::EndpointClass;
+use crate::error::{split_warnings, Error, Result};
+use crate::models::account::{balance_from_values, AddExportResponse, Balance, BalanceEx, ExportFormat, ExportReport, ExportStatus, RemoveExportResponse, RemoveExportType, StakeableAsset, StakingResponse, StakingTransaction, TradeBalance, OpenOrders, OpenOrdersWithTrades, OpenOrderWithTrades, OpenPositions, ClosedOrders, Ledger, Page, PagedResult, TradeHistory, Wallet, WalletTransferResponse};
+use crate::models::trading::{AmendOrderResponse, BatchOrder, BatchOrderResponseEntry, CancelResult, CancelTarget, Order, OrderAmend, OrderInfo, OrderResponse, OrderSide, OrderType, TradeInfo};
+use crate::models::websocket::WebSocketToken;
+use crate::transport::{TransportRequest, TransportResponse};
+use crate::utils::{hashmap_to_url_encoded, sanitize_params};
+use uuid::Uuid;
 
 /// Response wrapper for Kraken API responses
 #[derive(Debug, Deserialize)]
@@ -34,46 +54,125 @@ impl<'a> PrivateApi<'a> {
     }
     
     /// Make a private API request
-    async fn private_request<T: for<'de> Deserialize<'de>>(&self, endpoint: &str, mut params: HashMap<String, String>) -> Result<T> {
+    ///
+    /// Each request gets a unique ID, which is included in its log lines and in any
+    /// error it returns.
+    async fn private_request<T: for<'de> Deserialize<'de>>(&self, endpoint: &str, params: HashMap<String, String>) -> Result<T> {
+        let request_id = Uuid::new_v4().to_string();
+        let endpoint = self.client.config.endpoint_path(endpoint);
+        
+        self.send_private(&endpoint, params, &request_id).await
+            .map_err(|e| e.with_request_id(&request_id))
+    }
+    
+    /// Send a private API request and parse its result
+    async fn send_private<T: for<'de> Deserialize<'de>>(&self, endpoint: &str, params: HashMap<String, String>, request_id: &str) -> Result<T> {
+        let (nonce, response) = self.send_signed(endpoint, params, request_id).await?;
+        let response: KrakenResponse<T> = serde_json::from_slice(&response.body)?;
+        
+        let (errors, warnings) = split_warnings(response.error);
+        
+        if !warnings.is_empty() {
+            log_warn!("POST {} request={} returned warnings: {}", endpoint, request_id, warnings.join(", "));
+        }
+        
+        if !errors.is_empty() {
+            log_warn!("POST {} request={} failed: {}", endpoint, request_id, errors.join(", "));
+            return Err(private_api_error(&errors, nonce));
+        }
+        
+        response.result.ok_or_else(|| Error::Api("No result data".into()))
+    }
+    
+    /// Make a private API request whose successful response is a binary body
+    async fn private_request_bytes(&self, endpoint: &str, params: HashMap<String, String>) -> Result<Vec<u8>> {
+        let request_id = Uuid::new_v4().to_string();
+        let endpoint = self.client.config.endpoint_path(endpoint);
+        
+        self.send_private_bytes(&endpoint, params, &request_id).await
+            .map_err(|e| e.with_request_id(&request_id))
+    }
+    
+    /// Send a private API request and return its binary body
+    async fn send_private_bytes(&self, endpoint: &str, params: HashMap<String, String>, request_id: &str) -> Result<Vec<u8>> {
+        let (nonce, response) = self.send_signed(endpoint, params, request_id).await?;
+        
+        // Errors are still reported as JSON
+        let is_json = response.content_type()
+            .map(|v| v.contains("application/json"))
+            .unwrap_or(false);
+        
+        if is_json {
+            let response: KrakenResponse<Value> = serde_json::from_slice(&response.body)?;
+            
+            let (errors, warnings) = split_warnings(response.error);
+            
+            if !warnings.is_empty() {
+                log_warn!("POST {} request={} returned warnings: {}", endpoint, request_id, warnings.join(", "));
+            }
+            
+            if !errors.is_empty() {
+                log_warn!("POST {} request={} failed: {}", endpoint, request_id, errors.join(", "));
+                return Err(private_api_error(&errors, nonce));
+            }
+        }
+        
+        Ok(response.body)
+    }
+    
+    /// Sign and send a private API request, returning the nonce it was sent with
+    async fn send_signed(&self, endpoint: &str, mut params: HashMap<String, String>, request_id: &str) -> Result<(u64, TransportResponse)> {
         // Check if API key and secret are set
         let api_key = self.client.config.api_key.clone().ok_or_else(|| Error::Auth("API key not set".to_string()))?;
-        let api_secret = self.client.config.api_secret.clone().ok_or_else(|| Error::Auth("API secret not set".to_string()))?;
+        let signer = self.client.signer().ok_or_else(|| Error::Auth("API secret not set".to_string()))?;
+        
+        // Every private call counts towards the shared private API counter. Wait before
+        // generating the nonce so a delayed call doesn't send an outdated one.
+        self.client.private_rate_limiter().wait(endpoint_cost(endpoint)).await;
         
         // Generate nonce
-        let nonce = generate_nonce();
+        let nonce = self.client.next_nonce();
         params.insert("nonce".to_string(), nonce.to_string());
         
+        // Add the two-factor password if the key requires one
+        if let Some(ref otp) = self.client.config.otp {
+            params.insert("otp".to_string(), otp.clone());
+        }
+        
         // Create post data
         let post_data = hashmap_to_url_encoded(&params);
         
         // Sign the request
-        let signature = sign_message(endpoint, nonce, &post_data, &api_secret)?;
+        let signature = signer.sign(endpoint, nonce, &post_data)?;
         
         // Create the URL
         let url = format!("{}{}", self.client.config.api_url, endpoint);
         
+        log_debug!("POST {} request={} params={}", endpoint, request_id, sanitize_params(&params));
+        
+        let timeout = self.client.config.request_timeout(EndpointClass::for_endpoint(endpoint));
+        
         // Make the request
-        let response = self.client.http_client()
-            .post(&url)
-            .header("API-Key", api_key)
-            .header("API-Sign", signature)
-            .header("Content-Type", "application/x-www-form-urlencoded")
-            .body(post_data)
-            .send()
-            .await?
-            .json::<KrakenResponse<T>>()
-            .await?;
+        let request = TransportRequest::post(url, post_data)
+            .with_timeout(timeout)
+            .with_header("API-Key", api_key)
+            .with_header("API-Sign", signature)
+            .with_header("Content-Type", "application/x-www-form-urlencoded");
         
-        if !response.error.is_empty() {
-            return Err(Error::Api(response.error.join(", ")));
-        }
+        let response = self.client.send(endpoint, request).await?;
         
-        response.result.ok_or_else(|| Error::Api("No result data".to_string()))
+        Ok((nonce, response))
     }
     
     /// Get account balance
     pub async fn get_balance(&self) -> Result<Balance> {
-        self.private_request("/0/private/Balance", HashMap::new()).await
+        let balance: HashMap<String, Value> = self.private_request("/private/Balance", HashMap::new()).await?;
+        Ok(balance_from_values(balance))
+    }
+    
+    /// Get extended account balance, including amounts held by open orders
+    pub async fn get_balance_ex(&self) -> Result<BalanceEx> {
+        self.private_request("/private/BalanceEx", HashMap::new()).await
     }
     
     /// Get trade balance
@@ -84,11 +183,13 @@ impl<'a> PrivateApi<'a> {
             params.insert("asset".to_string(), asset.to_string());
         }
         
-        self.private_request("/0/private/TradeBalance", params).await
+        self.private_request("/private/TradeBalance", params).await
     }
     
     /// Get open orders
-    pub async fn get_open_orders(&self, trades: Option<bool>, userref: Option<&str>) -> Result<OpenOrders> {
+    ///
+    /// Fails if any order can't be parsed, rather than returning a partial list.
+    pub async fn get_open_orders(&self, trades: Option<bool>, userref: Option<i32>) -> Result<OpenOrders> {
         let mut params = HashMap::new();
         
         if let Some(trades) = trades {
@@ -99,24 +200,44 @@ impl<'a> PrivateApi<'a> {
             params.insert("userref".to_string(), userref.to_string());
         }
         
-        let result: HashMap<String, Value> = self.private_request("/0/private/OpenOrders", params).await?;
+        let result: HashMap<String, Value> = self.private_request("/private/OpenOrders", params).await?;
         
         // Extract the open orders
-        let open_orders = result.get("open").and_then(|v| v.as_object()).ok_or_else(|| Error::Api("Missing 'open' field".to_string()))?;
+        let open_orders = result.get("open").and_then(|v| v.as_object()).ok_or_else(|| Error::Api("Missing 'open' field".into()))?;
         
-        // Parse the open orders
-        let mut orders = HashMap::new();
-        for (order_id, order_data) in open_orders {
-            if let Ok(order) = serde_json::from_value(order_data.clone()) {
-                orders.insert(order_id.clone(), order);
-            }
-        }
+        parse_open_orders(open_orders)
+    }
+    
+    /// Get open orders together with the details of their trades
+    ///
+    /// Fetches the orders with their trade IDs, then the trades with
+    /// [`PrivateApi::query_trades`]. Fails if any of the trades is not returned.
+    pub async fn get_open_orders_with_trades(&self, userref: Option<i32>) -> Result<OpenOrdersWithTrades> {
+        let orders = self.get_open_orders(Some(true), userref).await?;
         
-        Ok(orders)
+        let trade_ids: Vec<String> = orders.values().flat_map(|order| order.trades.iter().flatten().cloned()).collect();
+        let mut trades = self.query_trades_in_chunks(&trade_ids).await?;
+        
+        orders.into_iter()
+            .map(|(txid, order)| {
+                let order_trades = order.trades.iter()
+                    .flatten()
+                    .map(|id| match trades.remove(id) {
+                        Some(trade) => Ok((id.clone(), trade)),
+                        None => Err(Error::Other(format!("Trade {} of order {} was not returned by QueryTrades", id, txid))),
+                    })
+                    .collect::<Result<_>>()?;
+                
+                Ok((txid, OpenOrderWithTrades { order, trades: order_trades }))
+            })
+            .collect()
     }
     
     /// Get closed orders
-    pub async fn get_closed_orders(&self, trades: Option<bool>, userref: Option<&str>, start: Option<u64>, end: Option<u64>, ofs: Option<u64>, closetime: Option<&str>) -> Result<ClosedOrders> {
+    ///
+    /// Kraken returns at most 50 orders per call; the page's `count` is the total number
+    /// of matching orders, so further pages can be requested with `ofs`.
+    pub async fn get_closed_orders(&self, trades: Option<bool>, userref: Option<i32>, start: Option<u64>, end: Option<u64>, ofs: Option<u64>, closetime: Option<&str>) -> Result<Page<ClosedOrders>> {
         let mut params = HashMap::new();
         
         if let Some(trades) = trades {
@@ -143,10 +264,11 @@ impl<'a> PrivateApi<'a> {
             params.insert("closetime".to_string(), closetime.to_string());
         }
         
-        let result: HashMap<String, Value> = self.private_request("/0/private/ClosedOrders", params).await?;
+        let result: HashMap<String, Value> = self.private_request("/private/ClosedOrders", params).await?;
         
         // Extract the closed orders
-        let closed_orders = result.get("closed").and_then(|v| v.as_object()).ok_or_else(|| Error::Api("Missing 'closed' field".to_string()))?;
+        let closed_orders = result.get("closed").and_then(|v| v.as_object()).ok_or_else(|| Error::Api("Missing 'closed' field".into()))?;
+        let count = result.get("count").and_then(|v| v.as_u64()).unwrap_or(0);
         
         // Parse the closed orders
         let mut orders = HashMap::new();
@@ -156,11 +278,25 @@ impl<'a> PrivateApi<'a> {
             }
         }
         
-        Ok(orders)
+        Ok(Page { entries: orders, count })
+    }
+    
+    /// Stream closed orders page by page, following `ofs`; see [paging](self#paging)
+    pub fn get_closed_orders_stream<'b>(&'b self, trades: Option<bool>, userref: Option<i32>, start: Option<u64>, end: Option<u64>, closetime: Option<&'b str>) -> impl Stream<Item = Result<Page<ClosedOrders>>> + 'b {
+        paginate(move |ofs| self.get_closed_orders(trades, userref, start, end, Some(ofs), closetime))
+    }
+    
+    /// Get every order closed between `start` and `end`, following `ofs` until every entry has been fetched
+    ///
+    /// `start` and `end` are Unix timestamps. Pages are merged and deduplicated by
+    /// transaction ID. Stops after `max_pages` requests if given, in which case the
+    /// result is marked incomplete.
+    pub async fn get_closed_orders_between(&self, start: u64, end: u64, max_pages: Option<u32>) -> Result<PagedResult<ClosedOrders>> {
+        collect_pages(max_pages, |ofs| self.get_closed_orders(None, None, Some(start), Some(end), Some(ofs), None)).await
     }
     
     /// Query orders info
-    pub async fn query_orders(&self, txid: Vec<&str>, trades: Option<bool>, userref: Option<&str>) -> Result<HashMap<String, OrderInfo>> {
+    pub async fn query_orders(&self, txid: Vec<&str>, trades: Option<bool>, userref: Option<i32>) -> Result<HashMap<String, OrderInfo>> {
         let mut params = HashMap::new();
         params.insert("txid".to_string(), txid.join(","));
         
@@ -172,11 +308,58 @@ impl<'a> PrivateApi<'a> {
             params.insert("userref".to_string(), userref.to_string());
         }
         
-        self.private_request("/0/private/QueryOrders", params).await
+        let mut orders: HashMap<String, OrderInfo> = self.private_request("/private/QueryOrders", params).await?;
+        
+        for (txid, order) in orders.iter_mut() {
+            order.txid = txid.clone();
+        }
+        
+        Ok(orders)
+    }
+    
+    /// Query orders info by user reference ID
+    ///
+    /// Kraken can only look up orders by transaction ID, so this first finds the IDs of
+    /// open and closed orders carrying the user reference, following `ofs` through every
+    /// page of closed orders, then queries those orders.
+    pub async fn query_orders_by_userref(&self, userref: i32, trades: Option<bool>) -> Result<HashMap<String, OrderInfo>> {
+        let open_orders = self.get_open_orders(None, Some(userref)).await?;
+        let closed_orders = collect_pages(None, |ofs| self.get_closed_orders(None, Some(userref), None, None, Some(ofs), None)).await?;
+        
+        if !closed_orders.complete {
+            log_warn!("Closed orders for user reference {} ended before the reported count was reached", userref);
+        }
+        
+        let txids: Vec<String> = open_orders.into_keys().chain(closed_orders.entries.into_keys()).collect();
+        
+        // QueryOrders accepts at most 50 transaction IDs per call
+        let mut orders = HashMap::new();
+        for chunk in txids.chunks(50) {
+            let chunk: Vec<&str> = chunk.iter().map(|s| s.as_str()).collect();
+            orders.extend(self.query_orders(chunk, trades, Some(userref)).await?);
+        }
+        
+        Ok(orders)
+    }
+    
+    /// Stream the trades history page by page, following `ofs`; see [paging](self#paging)
+    pub fn get_trades_history_stream<'b>(&'b self, type_: Option<&'b str>, trades: Option<bool>, start: Option<u64>, end: Option<u64>) -> impl Stream<Item = Result<Page<TradeHistory>>> + 'b {
+        paginate(move |ofs| self.get_trades_history(type_, trades, start, end, Some(ofs)))
+    }
+    
+    /// Get the complete trades history, following `ofs` until every entry has been fetched
+    ///
+    /// Pages are merged and deduplicated by trade ID. Stops after `max_pages` requests
+    /// if given, in which case the result is marked incomplete.
+    pub async fn get_all_trades_history(&self, type_: Option<&str>, trades: Option<bool>, start: Option<u64>, end: Option<u64>, max_pages: Option<u32>) -> Result<PagedResult<TradeHistory>> {
+        collect_pages(max_pages, |ofs| self.get_trades_history(type_, trades, start, end, Some(ofs))).await
     }
     
     /// Get trades history
-    pub async fn get_trades_history(&self, type_: Option<&str>, trades: Option<bool>, start: Option<u64>, end: Option<u64>, ofs: Option<u64>) -> Result<TradeHistory> {
+    ///
+    /// Kraken returns at most 50 trades per call; the page's `count` is the total number
+    /// of matching trades, so further pages can be requested with `ofs`.
+    pub async fn get_trades_history(&self, type_: Option<&str>, trades: Option<bool>, start: Option<u64>, end: Option<u64>, ofs: Option<u64>) -> Result<Page<TradeHistory>> {
         let mut params = HashMap::new();
         
         if let Some(type_) = type_ {
@@ -199,10 +382,11 @@ impl<'a> PrivateApi<'a> {
             params.insert("ofs".to_string(), ofs.to_string());
         }
         
-        let result: HashMap<String, Value> = self.private_request("/0/private/TradesHistory", params).await?;
+        let result: HashMap<String, Value> = self.private_request("/private/TradesHistory", params).await?;
         
         // Extract the trades
-        let trades = result.get("trades").and_then(|v| v.as_object()).ok_or_else(|| Error::Api("Missing 'trades' field".to_string()))?;
+        let trades = result.get("trades").and_then(|v| v.as_object()).ok_or_else(|| Error::Api("Missing 'trades' field".into()))?;
+        let count = result.get("count").and_then(|v| v.as_u64()).unwrap_or(0);
         
         // Parse the trades
         let mut trades_history = HashMap::new();
@@ -212,7 +396,7 @@ impl<'a> PrivateApi<'a> {
             }
         }
         
-        Ok(trades_history)
+        Ok(Page { entries: trades_history, count })
     }
     
     /// Query trades info
@@ -224,11 +408,97 @@ impl<'a> PrivateApi<'a> {
             params.insert("trades".to_string(), trades.to_string());
         }
         
-        self.private_request("/0/private/QueryTrades", params).await
+        self.private_request("/private/QueryTrades", params).await
+    }
+    
+    /// Query info about the trades of orders with the given user reference ID
+    pub async fn query_trades_by_userref(&self, userref: i32) -> Result<HashMap<String, TradeInfo>> {
+        let orders = self.query_orders_by_userref(userref, Some(true)).await?;
+        
+        let trade_ids: Vec<String> = orders.into_values().flat_map(|order| order.trades.unwrap_or_default()).collect();
+        
+        self.query_trades_in_chunks(&trade_ids).await
+    }
+    
+    /// Query info about any number of trades
+    async fn query_trades_in_chunks(&self, trade_ids: &[String]) -> Result<HashMap<String, TradeInfo>> {
+        // QueryTrades accepts at most 20 transaction IDs per call
+        let mut trades = HashMap::new();
+        for chunk in trade_ids.chunks(20) {
+            let chunk: Vec<&str> = chunk.iter().map(|s| s.as_str()).collect();
+            trades.extend(self.query_trades(chunk, None).await?);
+        }
+        
+        Ok(trades)
+    }
+    
+    /// Get open margin positions
+    ///
+    /// The `value` and `net` profit/loss fields are only included when `docalcs` is set.
+    pub async fn get_open_positions(&self, txid: Option<Vec<&str>>, docalcs: Option<bool>) -> Result<OpenPositions> {
+        let mut params = HashMap::new();
+        
+        if let Some(txid) = txid {
+            params.insert("txid".to_string(), txid.join(","));
+        }
+        
+        if let Some(docalcs) = docalcs {
+            params.insert("docalcs".to_string(), docalcs.to_string());
+        }
+        
+        self.private_request("/private/OpenPositions", params).await
+    }
+    
+    /// Get open margin positions with their unrealized profit/loss
+    ///
+    /// Every returned position has `value` and `net` set; if Kraken omits them for any
+    /// position, an error is returned instead.
+    pub async fn get_open_positions_with_pnl(&self, txid: Option<Vec<&str>>) -> Result<OpenPositions> {
+        let positions = self.get_open_positions(txid, Some(true)).await?;
+        
+        for (position_id, position) in &positions {
+            if position.value.is_none() || position.net.is_none() {
+                return Err(Error::Api(format!("Missing profit/loss calculations for position {}", position_id).into()));
+            }
+        }
+        
+        Ok(positions)
     }
     
     /// Get ledgers info
     pub async fn get_ledgers(&self, asset: Option<Vec<&str>>, type_: Option<&str>, start: Option<u64>, end: Option<u64>, ofs: Option<u64>) -> Result<Ledger> {
+        let (ledger_entries, _count) = self.get_ledgers_page(asset, type_, start, end, ofs).await?;
+        
+        Ok(ledger_entries)
+    }
+    
+    /// Stream ledger entries page by page, following `ofs`; see [paging](self#paging)
+    pub fn get_ledgers_stream<'b>(&'b self, asset: Option<Vec<&'b str>>, type_: Option<&'b str>, start: Option<u64>, end: Option<u64>) -> impl Stream<Item = Result<Page<Ledger>>> + 'b {
+        paginate(move |ofs| {
+            let asset = asset.clone();
+            async move {
+                let (entries, count) = self.get_ledgers_page(asset, type_, start, end, Some(ofs)).await?;
+                Ok(Page { entries, count })
+            }
+        })
+    }
+    
+    /// Get all ledger entries, following `ofs` until every entry has been fetched
+    ///
+    /// Pages are merged and deduplicated by ledger ID. Stops after `max_pages` requests
+    /// if given, in which case the result is marked incomplete.
+    pub async fn get_all_ledgers(&self, asset: Option<Vec<&str>>, type_: Option<&str>, start: Option<u64>, end: Option<u64>, max_pages: Option<u32>) -> Result<PagedResult<Ledger>> {
+        collect_pages(max_pages, |ofs| {
+            let asset = asset.clone();
+            async move {
+                let (entries, count) = self.get_ledgers_page(asset, type_, start, end, Some(ofs)).await?;
+                Ok(Page { entries, count })
+            }
+        }).await
+    }
+    
+    /// Get a page of ledger entries along with the total number of matching entries
+    async fn get_ledgers_page(&self, asset: Option<Vec<&str>>, type_: Option<&str>, start: Option<u64>, end: Option<u64>, ofs: Option<u64>) -> Result<(Ledger, u64)> {
         let mut params = HashMap::new();
         
         if let Some(asset) = asset {
@@ -251,10 +521,11 @@ impl<'a> PrivateApi<'a> {
             params.insert("ofs".to_string(), ofs.to_string());
         }
         
-        let result: HashMap<String, Value> = self.private_request("/0/private/Ledgers", params).await?;
+        let result: HashMap<String, Value> = self.private_request("/private/Ledgers", params).await?;
         
         // Extract the ledgers
-        let ledgers = result.get("ledger").and_then(|v| v.as_object()).ok_or_else(|| Error::Api("Missing 'ledger' field".to_string()))?;
+        let ledgers = result.get("ledger").and_then(|v| v.as_object()).ok_or_else(|| Error::Api("Missing 'ledger' field".into()))?;
+        let count = result.get("count").and_then(|v| v.as_u64()).unwrap_or(0);
         
         // Parse the ledgers
         let mut ledger_entries = HashMap::new();
@@ -264,11 +535,125 @@ impl<'a> PrivateApi<'a> {
             }
         }
         
-        Ok(ledger_entries)
+        Ok((ledger_entries, count))
+    }
+    
+    /// Query specific ledger entries by ID
+    ///
+    /// Kraken accepts up to 20 IDs per request.
+    pub async fn query_ledgers(&self, ids: Vec<&str>, trades: Option<bool>) -> Result<Ledger> {
+        let mut params = HashMap::new();
+        params.insert("id".to_string(), ids.join(","));
+        
+        if let Some(trades) = trades {
+            params.insert("trades".to_string(), trades.to_string());
+        }
+        
+        self.private_request("/private/QueryLedgers", params).await
+    }
+    
+    /// Request a trades or ledgers export report
+    pub async fn add_export(&self, report: ExportReport, description: &str, format: Option<ExportFormat>, starttm: Option<u64>, endtm: Option<u64>) -> Result<AddExportResponse> {
+        let mut params = HashMap::new();
+        params.insert("report".to_string(), report.to_string());
+        params.insert("description".to_string(), description.to_string());
+        
+        if let Some(format) = format {
+            params.insert("format".to_string(), format.to_string());
+        }
+        
+        if let Some(starttm) = starttm {
+            params.insert("starttm".to_string(), starttm.to_string());
+        }
+        
+        if let Some(endtm) = endtm {
+            params.insert("endtm".to_string(), endtm.to_string());
+        }
+        
+        self.private_request("/private/AddExport", params).await
+    }
+    
+    /// Get the status of requested export reports
+    pub async fn export_status(&self, report: ExportReport) -> Result<Vec<ExportStatus>> {
+        let mut params = HashMap::new();
+        params.insert("report".to_string(), report.to_string());
+        
+        self.private_request("/private/ExportStatus", params).await
+    }
+    
+    /// Retrieve a processed export report as raw ZIP archive bytes
+    pub async fn retrieve_export(&self, id: &str) -> Result<Vec<u8>> {
+        let mut params = HashMap::new();
+        params.insert("id".to_string(), id.to_string());
+        
+        self.private_request_bytes("/private/RetrieveExport", params).await
+    }
+    
+    /// Cancel a queued export report or delete a processed one
+    pub async fn remove_export(&self, id: &str, type_: RemoveExportType) -> Result<RemoveExportResponse> {
+        let mut params = HashMap::new();
+        params.insert("id".to_string(), id.to_string());
+        params.insert("type".to_string(), type_.to_string());
+        
+        self.private_request("/private/RemoveExport", params).await
+    }
+    
+    /// Transfer funds between the spot and futures wallets
+    pub async fn wallet_transfer(&self, asset: &str, from: Wallet, to: Wallet, amount: &str) -> Result<WalletTransferResponse> {
+        let mut params = HashMap::new();
+        params.insert("asset".to_string(), asset.to_string());
+        params.insert("from".to_string(), from.to_string());
+        params.insert("to".to_string(), to.to_string());
+        params.insert("amount".to_string(), amount.to_string());
+        
+        self.private_request("/private/WalletTransfer", params).await
+    }
+    
+    /// Get the assets that can be staked
+    pub async fn get_stakeable_assets(&self) -> Result<Vec<StakeableAsset>> {
+        self.private_request("/private/Staking/Assets", HashMap::new()).await
+    }
+    
+    /// Stake an asset using the given staking method
+    pub async fn stake(&self, asset: &str, amount: &str, method: &str) -> Result<StakingResponse> {
+        let mut params = HashMap::new();
+        params.insert("asset".to_string(), asset.to_string());
+        params.insert("amount".to_string(), amount.to_string());
+        params.insert("method".to_string(), method.to_string());
+        
+        self.private_request("/private/Stake", params).await
+    }
+    
+    /// Unstake a staked asset
+    pub async fn unstake(&self, asset: &str, amount: &str) -> Result<StakingResponse> {
+        let mut params = HashMap::new();
+        params.insert("asset".to_string(), asset.to_string());
+        params.insert("amount".to_string(), amount.to_string());
+        
+        self.private_request("/private/Unstake", params).await
+    }
+    
+    /// Get staking transactions that have not completed yet
+    pub async fn get_pending_staking_transactions(&self) -> Result<Vec<StakingTransaction>> {
+        self.private_request("/private/Staking/Pending", HashMap::new()).await
     }
     
     /// Add order
+    ///
+    /// Retried after retryable errors when [`crate::config::Config::with_order_retries`]
+    /// is set, with a generated client order ID if the order has none.
     pub async fn add_order(&self, order: &Order) -> Result<OrderResponse> {
+        order.validate_local()?;
+        
+        let retries = self.client.config.order_retries;
+        let generated;
+        let order = if retries > 0 && order.cl_ord_id.is_none() {
+            generated = order.clone().with_generated_cl_ord_id();
+            &generated
+        } else {
+            order
+        };
+        
         let mut params = HashMap::new();
         
         // Convert order to parameters
@@ -301,11 +686,29 @@ impl<'a> PrivateApi<'a> {
             params.insert("expiretm".to_string(), expiretm.clone());
         }
         
-        if let Some(ref userref) = order.userref {
-            params.insert("userref".to_string(), userref.clone());
+        if let Some(ref deadline) = order.deadline {
+            params.insert("deadline".to_string(), deadline.clone());
+        }
+        
+        if let Some(reduce_only) = order.reduce_only {
+            params.insert("reduce_only".to_string(), reduce_only.to_string());
+        }
+        
+        if let Some(trigger) = order.trigger {
+            params.insert("trigger".to_string(), trigger.to_string());
+        }
+        
+        if let Some(userref) = order.userref {
+            params.insert("userref".to_string(), userref.to_string());
+        }
+        
+        if let Some(ref cl_ord_id) = order.cl_ord_id {
+            params.insert("cl_ord_id".to_string(), cl_ord_id.clone());
         }
         
-        if let Some(validate) = order.validate {
+        if self.client.config.dry_run {
+            params.insert("validate".to_string(), "true".to_string());
+        } else if let Some(validate) = order.validate {
             params.insert("validate".to_string(), validate.to_string());
         }
         
@@ -321,19 +724,928 @@ impl<'a> PrivateApi<'a> {
             params.insert("close[price2]".to_string(), close_price2.clone());
         }
         
-        self.private_request("/0/private/AddOrder", params).await
+        // Validate-only orders never reach the matching engine, so they don't count
+        let validate_only = self.client.config.dry_run || order.validate == Some(true);
+        
+        if !validate_only {
+            self.client.order_rate_limiter().wait(&order.pair, 1.0).await;
+        }
+        
+        let mut delay = self.client.config.order_retry_delay;
+        let mut attempt = 0;
+        let response: OrderResponse = loop {
+            match self.private_request("/private/AddOrder", params.clone()).await {
+                Err(e) if attempt < retries && e.is_retryable() => {
+                    log_warn!("Retrying {} order for {} after error: {}", order.ordertype, order.pair, e);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => break result?,
+            }
+        };
+        
+        if self.client.config.dry_run {
+            log_warn!("Dry run: {} order for {} was validated but not submitted", order.ordertype, order.pair);
+        }
+        
+        if !validate_only {
+            for txid in &response.txid {
+                self.client.order_rate_limiter().record_order(txid, &order.pair).await;
+            }
+        }
+        
+        Ok(response)
+    }
+    
+    /// Place a market buy order
+    pub async fn market_buy(&self, pair: &str, volume: &str) -> Result<OrderResponse> {
+        self.add_order(&Order::new(pair, OrderSide::Buy, OrderType::Market, volume)).await
+    }
+    
+    /// Place a market sell order
+    pub async fn market_sell(&self, pair: &str, volume: &str) -> Result<OrderResponse> {
+        self.add_order(&Order::new(pair, OrderSide::Sell, OrderType::Market, volume)).await
+    }
+    
+    /// Place a limit buy order
+    pub async fn limit_buy(&self, pair: &str, volume: &str, price: &str) -> Result<OrderResponse> {
+        self.add_order(&Order::new(pair, OrderSide::Buy, OrderType::Limit, volume).with_price(price)).await
+    }
+    
+    /// Place a limit sell order
+    pub async fn limit_sell(&self, pair: &str, volume: &str, price: &str) -> Result<OrderResponse> {
+        self.add_order(&Order::new(pair, OrderSide::Sell, OrderType::Limit, volume).with_price(price)).await
+    }
+    
+    /// Add a batch of orders for a single pair
+    ///
+    /// Kraken accepts between 2 and 15 orders per batch. Each order can be accepted or
+    /// rejected individually, so the returned list holds one result per submitted order,
+    /// in the same order. The optional RFC3339 `deadline` applies to the whole batch.
+    pub async fn add_order_batch(&self, pair: &str, orders: Vec<BatchOrder>, deadline: Option<&str>) -> Result<Vec<Result<OrderResponse>>> {
+        if orders.len() < 2 || orders.len() > 15 {
+            return Err(Error::Other(format!("Order batch must contain between 2 and 15 orders, got {}", orders.len())));
+        }
+        
+        for (i, order) in orders.iter().enumerate() {
+            order.validate_local().map_err(|e| match e {
+                Error::Other(msg) => Error::Other(format!("Order {} in batch: {}", i, msg)),
+                e => e,
+            })?;
+        }
+        
+        let mut params = HashMap::new();
+        params.insert("pair".to_string(), pair.to_string());
+        
+        if let Some(deadline) = deadline {
+            params.insert("deadline".to_string(), deadline.to_string());
+        }
+        
+        let dry_run = self.client.config.dry_run;
+        if dry_run {
+            params.insert("validate".to_string(), "true".to_string());
+        }
+        
+        for (i, order) in orders.iter().enumerate() {
+            let prefix = format!("orders[{}]", i);
+            
+            params.insert(format!("{}[type]", prefix), order.type_.to_string());
+            params.insert(format!("{}[ordertype]", prefix), order.ordertype.to_string());
+            params.insert(format!("{}[volume]", prefix), order.volume.clone());
+            
+            if let Some(ref price) = order.price {
+                params.insert(format!("{}[price]", prefix), price.clone());
+            }
+            
+            if let Some(ref price2) = order.price2 {
+                params.insert(format!("{}[price2]", prefix), price2.clone());
+            }
+            
+            if let Some(ref leverage) = order.leverage {
+                params.insert(format!("{}[leverage]", prefix), leverage.clone());
+            }
+            
+            if let Some(ref oflags) = order.oflags {
+                params.insert(format!("{}[oflags]", prefix), oflags.clone());
+            }
+            
+            if let Some(ref starttm) = order.starttm {
+                params.insert(format!("{}[starttm]", prefix), starttm.clone());
+            }
+            
+            if let Some(ref expiretm) = order.expiretm {
+                params.insert(format!("{}[expiretm]", prefix), expiretm.clone());
+            }
+            
+            if let Some(reduce_only) = order.reduce_only {
+                params.insert(format!("{}[reduce_only]", prefix), reduce_only.to_string());
+            }
+            
+            if let Some(trigger) = order.trigger {
+                params.insert(format!("{}[trigger]", prefix), trigger.to_string());
+            }
+            
+            if let Some(userref) = order.userref {
+                params.insert(format!("{}[userref]", prefix), userref.to_string());
+            }
+            
+            if let Some(ref cl_ord_id) = order.cl_ord_id {
+                params.insert(format!("{}[cl_ord_id]", prefix), cl_ord_id.clone());
+            }
+            
+            if let Some(ref close_ordertype) = order.close_ordertype {
+                params.insert(format!("{}[close][ordertype]", prefix), close_ordertype.to_string());
+            }
+            
+            if let Some(ref close_price) = order.close_price {
+                params.insert(format!("{}[close][price]", prefix), close_price.clone());
+            }
+            
+            if let Some(ref close_price2) = order.close_price2 {
+                params.insert(format!("{}[close][price2]", prefix), close_price2.clone());
+            }
+        }
+        
+        if !dry_run {
+            self.client.order_rate_limiter().wait(pair, orders.len() as f64).await;
+        }
+        
+        let result: HashMap<String, Value> = self.private_request("/private/AddOrderBatch", params).await?;
+        
+        if dry_run {
+            log_warn!("Dry run: batch of {} orders for {} was validated but not submitted", orders.len(), pair);
+        }
+        
+        // Extract the per-order results
+        let entries = result.get("orders").and_then(|v| v.as_array()).ok_or_else(|| Error::Api("Missing 'orders' field".into()))?;
+        
+        let mut responses = Vec::new();
+        for entry in entries {
+            let entry: BatchOrderResponseEntry = serde_json::from_value(entry.clone())?;
+            
+            let response = match (entry.error, entry.descr, entry.txid) {
+                (Some(error), _, _) => Err(Error::Api(error.into())),
+                (None, Some(descr), Some(txid)) => Ok(OrderResponse { descr, txid: vec![txid] }),
+                (None, Some(descr), None) if dry_run => Ok(OrderResponse { descr, txid: Vec::new() }),
+                _ => Err(Error::Api("Malformed batch order entry".into())),
+            };
+            
+            if let Ok(ref response) = response {
+                for txid in &response.txid {
+                    self.client.order_rate_limiter().record_order(txid, pair).await;
+                }
+            }
+            
+            responses.push(response);
+        }
+        
+        Ok(responses)
     }
     
-    /// Cancel order
-    pub async fn cancel_order(&self, txid: &str) -> Result<HashMap<String, Value>> {
+    /// Amend an open order in place
+    ///
+    /// Unlike Kraken's `EditOrder`, which cancels the order and places a new one with a
+    /// new txid, amending keeps the txid and, where possible, the order's queue
+    /// priority. Fails locally if the amend changes neither quantity nor price.
+    ///
+    /// Kraken has no validate-only amend, so amends are refused in dry-run mode.
+    pub async fn amend_order(&self, txid: &str, amend: OrderAmend) -> Result<AmendOrderResponse> {
+        if amend.is_empty() {
+            return Err(Error::Other("Order amend must change the quantity, display quantity or limit price".to_string()));
+        }
+        
+        if self.client.config.dry_run {
+            return Err(Error::Other(format!("Dry run: refusing to amend order {}", txid)));
+        }
+        
         let mut params = HashMap::new();
         params.insert("txid".to_string(), txid.to_string());
         
-        self.private_request("/0/private/CancelOrder", params).await
+        if let Some(order_qty) = amend.order_qty {
+            params.insert("order_qty".to_string(), order_qty);
+        }
+        
+        if let Some(limit_price) = amend.limit_price {
+            params.insert("limit_price".to_string(), limit_price);
+        }
+        
+        if let Some(display_qty) = amend.display_qty {
+            params.insert("display_qty".to_string(), display_qty);
+        }
+        
+        if let Some(post_only) = amend.post_only {
+            params.insert("post_only".to_string(), post_only.to_string());
+        }
+        
+        self.private_request("/private/AmendOrder", params).await
+    }
+    
+    /// Cancel an order, or all orders sharing a user reference ID
+    ///
+    /// A transaction ID converts into a [`CancelTarget`], so `cancel_order("O...")` cancels a single order.
+    pub async fn cancel_order(&self, target: impl Into<CancelTarget>) -> Result<CancelResult> {
+        let target = target.into();
+        
+        let mut params = HashMap::new();
+        match target {
+            CancelTarget::ClOrdId(ref cl_ord_id) => params.insert("cl_ord_id".to_string(), cl_ord_id.clone()),
+            _ => params.insert("txid".to_string(), target.to_string()),
+        };
+        
+        // Cancelling a recently placed order adds a penalty to its pair's order counter
+        if let CancelTarget::TxId(ref txid) = target {
+            if let Some((pair, penalty)) = self.client.order_rate_limiter().take_cancel_penalty(txid).await {
+                self.client.order_rate_limiter().wait(&pair, penalty).await;
+            }
+        }
+        
+        self.private_request("/private/CancelOrder", params).await
     }
     
     /// Cancel all orders
-    pub async fn cancel_all_orders(&self) -> Result<HashMap<String, Value>> {
-        self.private_request("/0/private/CancelAll", HashMap::new()).await
+    pub async fn cancel_all_orders(&self) -> Result<CancelResult> {
+        self.private_request("/private/CancelAll", HashMap::new()).await
+    }
+    
+    /// Get a token for subscribing to private WebSocket channels
+    pub async fn get_websockets_token(&self) -> Result<WebSocketToken> {
+        self.private_request("/private/GetWebSocketsToken", HashMap::new()).await
+    }
+}
+
+/// Follow `ofs` across pages lazily, fetching each page only when it is polled for
+///
+/// Ends after the page that reaches `count`, after an empty page, or after the
+/// first error.
+fn paginate<V, F, Fut>(fetch_page: F) -> impl Stream<Item = Result<Page<HashMap<String, V>>>>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<Page<HashMap<String, V>>>>,
+{
+    stream::unfold((fetch_page, Some(0)), |(mut fetch_page, ofs)| async move {
+        let ofs = ofs?;
+        
+        match fetch_page(ofs).await {
+            Ok(page) => {
+                let next = ofs + page.entries.len() as u64;
+                let next = (!page.entries.is_empty() && next < page.count).then_some(next);
+                Some((Ok(page), (fetch_page, next)))
+            }
+            Err(e) => Some((Err(e), (fetch_page, None))),
+        }
+    })
+}
+
+/// Follow `ofs` across pages until `count` entries have been fetched
+///
+/// Stops early after `max_pages` requests so an inconsistent `count` can't cause an
+/// unbounded loop. The result is marked incomplete if it stopped before `count` was
+/// reached.
+async fn collect_pages<V, F, Fut>(max_pages: Option<u32>, fetch_page: F) -> Result<PagedResult<HashMap<String, V>>>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<Page<HashMap<String, V>>>>,
+{
+    let pages = paginate(fetch_page);
+    futures::pin_mut!(pages);
+    
+    let mut entries = HashMap::new();
+    let mut ofs = 0;
+    let mut count = 0;
+    let mut fetched = 0;
+    
+    loop {
+        if max_pages.is_some_and(|max_pages| fetched >= max_pages) {
+            return Ok(PagedResult { entries, complete: false });
+        }
+        
+        let page = match pages.next().await {
+            Some(page) => page?,
+            None => return Ok(PagedResult { entries, complete: ofs >= count }),
+        };
+        fetched += 1;
+        
+        ofs += page.entries.len() as u64;
+        count = page.count;
+        entries.extend(page.entries);
+        
+        if ofs >= count {
+            return Ok(PagedResult { entries, complete: true });
+        }
+    }
+}
+
+/// Create an error from the `error` array of a private API response
+///
+/// Nonce errors are reported as [`Error::Auth`] including the nonce that was sent,
+/// to help debug collisions between processes sharing an API key.
+fn private_api_error(errors: &[String], nonce: u64) -> Error {
+    if errors.iter().any(|e| e.starts_with("EAPI:Invalid nonce")) {
+        return Error::Auth(format!("{} (nonce sent: {})", errors.join(", "), nonce));
+    }
+    
+    Error::from_api_errors(errors)
+}
+
+/// Parse open orders, failing with the IDs of any orders that don't match the model
+fn parse_open_orders(open_orders: &serde_json::Map<String, Value>) -> Result<OpenOrders> {
+    let mut orders = HashMap::new();
+    let mut failed = Vec::new();
+    
+    for (order_id, order_data) in open_orders {
+        match serde_json::from_value(order_data.clone()) {
+            Ok(order) => {
+                orders.insert(order_id.clone(), order);
+            }
+            Err(e) => failed.push(format!("{} ({})", order_id, e)),
+        }
+    }
+    
+    if !failed.is_empty() {
+        failed.sort();
+        return Err(Error::Other(format!("Failed to parse open orders: {}", failed.join(", "))));
+    }
+    
+    Ok(orders)
+}
+
+/// Pass the private endpoint methods to `$callback`, after the tokens in its braces
+///
+/// [`PrivateApiTrait`] and the blocking client are both generated from this one list.
+macro_rules! private_methods {
+    ($callback:ident! { $($args:tt)* }) => {
+        $callback! {
+            $($args)*
+            
+            /// Get account balance
+            fn get_balance() -> Balance;
+            
+            /// Get extended account balance, including amounts held by open orders
+            fn get_balance_ex() -> BalanceEx;
+            
+            /// Get trade balance
+            fn get_trade_balance(asset: Option<&str>) -> TradeBalance;
+            
+            /// Get open orders
+            fn get_open_orders(trades: Option<bool>, userref: Option<i32>) -> OpenOrders;
+            
+            /// Get open orders together with the details of their trades
+            fn get_open_orders_with_trades(userref: Option<i32>) -> OpenOrdersWithTrades;
+            
+            /// Get closed orders
+            fn get_closed_orders(trades: Option<bool>, userref: Option<i32>, start: Option<u64>, end: Option<u64>, ofs: Option<u64>, closetime: Option<&str>) -> Page<ClosedOrders>;
+            
+            /// Get every order closed between `start` and `end`, following `ofs` until every entry has been fetched
+            fn get_closed_orders_between(start: u64, end: u64, max_pages: Option<u32>) -> PagedResult<ClosedOrders>;
+            
+            /// Query orders info
+            fn query_orders(txid: Vec<&str>, trades: Option<bool>, userref: Option<i32>) -> HashMap<String, OrderInfo>;
+            
+            /// Query orders info by user reference ID
+            fn query_orders_by_userref(userref: i32, trades: Option<bool>) -> HashMap<String, OrderInfo>;
+            
+            /// Get the complete trades history, following `ofs` until every entry has been fetched
+            fn get_all_trades_history(type_: Option<&str>, trades: Option<bool>, start: Option<u64>, end: Option<u64>, max_pages: Option<u32>) -> PagedResult<TradeHistory>;
+            
+            /// Get trades history
+            fn get_trades_history(type_: Option<&str>, trades: Option<bool>, start: Option<u64>, end: Option<u64>, ofs: Option<u64>) -> Page<TradeHistory>;
+            
+            /// Query trades info
+            fn query_trades(txid: Vec<&str>, trades: Option<bool>) -> HashMap<String, TradeInfo>;
+            
+            /// Query info about the trades of orders with the given user reference ID
+            fn query_trades_by_userref(userref: i32) -> HashMap<String, TradeInfo>;
+            
+            /// Get open margin positions
+            fn get_open_positions(txid: Option<Vec<&str>>, docalcs: Option<bool>) -> OpenPositions;
+            
+            /// Get open margin positions with their unrealized profit/loss
+            fn get_open_positions_with_pnl(txid: Option<Vec<&str>>) -> OpenPositions;
+            
+            /// Get ledgers info
+            fn get_ledgers(asset: Option<Vec<&str>>, type_: Option<&str>, start: Option<u64>, end: Option<u64>, ofs: Option<u64>) -> Ledger;
+            
+            /// Get all ledger entries, following `ofs` until every entry has been fetched
+            fn get_all_ledgers(asset: Option<Vec<&str>>, type_: Option<&str>, start: Option<u64>, end: Option<u64>, max_pages: Option<u32>) -> PagedResult<Ledger>;
+            
+            /// Query specific ledger entries by ID
+            fn query_ledgers(ids: Vec<&str>, trades: Option<bool>) -> Ledger;
+            
+            /// Request a trades or ledgers export report
+            fn add_export(report: ExportReport, description: &str, format: Option<ExportFormat>, starttm: Option<u64>, endtm: Option<u64>) -> AddExportResponse;
+            
+            /// Get the status of requested export reports
+            fn export_status(report: ExportReport) -> Vec<ExportStatus>;
+            
+            /// Retrieve a processed export report as raw ZIP archive bytes
+            fn retrieve_export(id: &str) -> Vec<u8>;
+            
+            /// Cancel a queued export report or delete a processed one
+            fn remove_export(id: &str, type_: RemoveExportType) -> RemoveExportResponse;
+            
+            /// Transfer funds between the spot and futures wallets
+            fn wallet_transfer(asset: &str, from: Wallet, to: Wallet, amount: &str) -> WalletTransferResponse;
+            
+            /// Get the assets that can be staked
+            fn get_stakeable_assets() -> Vec<StakeableAsset>;
+            
+            /// Stake an asset using the given staking method
+            fn stake(asset: &str, amount: &str, method: &str) -> StakingResponse;
+            
+            /// Unstake a staked asset
+            fn unstake(asset: &str, amount: &str) -> StakingResponse;
+            
+            /// Get staking transactions that have not completed yet
+            fn get_pending_staking_transactions() -> Vec<StakingTransaction>;
+            
+            /// Add order
+            fn add_order(order: &Order) -> OrderResponse;
+            
+            /// Place a market buy order
+            fn market_buy(pair: &str, volume: &str) -> OrderResponse;
+            
+            /// Place a market sell order
+            fn market_sell(pair: &str, volume: &str) -> OrderResponse;
+            
+            /// Place a limit buy order
+            fn limit_buy(pair: &str, volume: &str, price: &str) -> OrderResponse;
+            
+            /// Place a limit sell order
+            fn limit_sell(pair: &str, volume: &str, price: &str) -> OrderResponse;
+            
+            /// Add a batch of orders for a single pair
+            fn add_order_batch(pair: &str, orders: Vec<BatchOrder>, deadline: Option<&str>) -> Vec<Result<OrderResponse>>;
+            
+            /// Amend an open order in place
+            fn amend_order(txid: &str, amend: OrderAmend) -> AmendOrderResponse;
+            
+            /// Cancel order
+            fn cancel_order(target: CancelTarget) -> CancelResult;
+            
+            /// Cancel all orders
+            fn cancel_all_orders() -> CancelResult;
+            
+            /// Get a token for subscribing to private WebSocket channels
+            fn get_websockets_token() -> WebSocketToken;
+        }
+    };
+}
+#[cfg(feature = "blocking")]
+pub(crate) use private_methods;
+
+private_methods!(api_trait! {
+    /// Private API endpoints as a trait
+    ///
+    /// Implemented by [`PrivateApi`]. Depend on this trait to swap in a fake in tests.
+    PrivateApiTrait for PrivateApi;
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    fn open_order(trades: Value) -> Value {
+        json!({
+            "refid": null,
+            "userref": 0,
+            "status": "open",
+            "opentm": 1688666559.8974,
+            "starttm": 0,
+            "expiretm": 0,
+            "descr": {
+                "pair": "XBTUSD",
+                "type": "buy",
+                "ordertype": "limit",
+                "price": "30010.0",
+                "price2": "0",
+                "leverage": "none",
+                "order": "buy 1.25000000 XBTUSD @ limit 30010.0",
+                "close": ""
+            },
+            "vol": "1.25000000",
+            "vol_exec": "0.37500000",
+            "cost": "11253.7",
+            "fee": "0.00000",
+            "price": "30010.0",
+            "stopprice": "0.00000",
+            "limitprice": "0.00000",
+            "misc": "",
+            "oflags": "fciq",
+            "trades": trades
+        })
+    }
+    
+    #[test]
+    fn test_parse_open_orders_with_trades() {
+        let mut open = serde_json::Map::new();
+        open.insert("OQCLML-BW3P3-BUCMWZ".to_string(), open_order(json!(["TCCCTY-WE2O6-P3NB37"])));
+        
+        let orders = parse_open_orders(&open).unwrap();
+        let order = &orders["OQCLML-BW3P3-BUCMWZ"];
+        
+        assert_eq!(order.trades.as_deref(), Some(&["TCCCTY-WE2O6-P3NB37".to_string()][..]));
+    }
+    
+    #[test]
+    fn test_parse_open_orders_reports_malformed_orders() {
+        let mut open = serde_json::Map::new();
+        open.insert("OQCLML-BW3P3-BUCMWZ".to_string(), open_order(Value::Null));
+        open.insert("OB5VMB-B4U2U-DK2WRW".to_string(), json!({ "status": "open" }));
+        
+        let err = parse_open_orders(&open).unwrap_err().to_string();
+        
+        assert!(err.contains("OB5VMB-B4U2U-DK2WRW"));
+        assert!(!err.contains("OQCLML-BW3P3-BUCMWZ"));
+    }
+    
+    #[tokio::test]
+    async fn test_collect_pages() {
+        let page = |ofs: u64, count: u64| {
+            let entries: HashMap<String, u64> = (ofs..(ofs + 2).min(count)).map(|i| (i.to_string(), i)).collect();
+            async move { Ok(Page { entries, count }) }
+        };
+        
+        let result = collect_pages(None, |ofs| page(ofs, 5)).await.unwrap();
+        assert_eq!(result.entries.len(), 5);
+        assert!(result.complete);
+        
+        // A count that is never reached stops at the page limit
+        let result = collect_pages(Some(3), |ofs| page(ofs, u64::MAX)).await.unwrap();
+        assert_eq!(result.entries.len(), 6);
+        assert!(!result.complete);
+        
+        let result = collect_pages(Some(3), |ofs| page(ofs, 4)).await.unwrap();
+        assert!(result.complete);
+    }
+    
+    #[tokio::test]
+    async fn test_paginate() {
+        let requested = std::sync::Mutex::new(Vec::new());
+        let pages = paginate(|ofs| {
+            requested.lock().unwrap().push(ofs);
+            let entries: HashMap<String, u64> = (ofs..(ofs + 2).min(5)).map(|i| (i.to_string(), i)).collect();
+            async move { Ok(Page { entries, count: 5 }) }
+        });
+        futures::pin_mut!(pages);
+        
+        // Pages are only fetched as they are consumed
+        assert_eq!(pages.next().await.unwrap().unwrap().entries.len(), 2);
+        assert_eq!(*requested.lock().unwrap(), vec![0]);
+        
+        let rest: Vec<_> = pages.collect().await;
+        assert_eq!(rest.len(), 2);
+        assert_eq!(*requested.lock().unwrap(), vec![0, 2, 4]);
+        
+        // An error ends the stream
+        let pages = paginate(|_| async { Err::<Page<HashMap<String, u64>>, _>(Error::Other("down".to_string())) });
+        let results: Vec<_> = pages.collect().await;
+        assert!(matches!(results[..], [Err(_)]));
+    }
+    
+    #[test]
+    fn test_private_api_error_includes_nonce() {
+        let error = private_api_error(&["EAPI:Invalid nonce".to_string()], 1688669448123);
+        assert!(matches!(error, Error::Auth(ref msg) if msg == "EAPI:Invalid nonce (nonce sent: 1688669448123)"));
+        
+        let error = private_api_error(&["EOrder:Insufficient funds".to_string()], 1688669448123);
+        assert!(matches!(error, Error::Api(_)));
+    }
+    
+    #[tokio::test]
+    async fn test_dry_run_forces_validate() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        let transport = MockTransport::new(|request| {
+            assert!(request.url.ends_with("/0/private/AddOrder"));
+            assert!(request.body.unwrap().contains("validate=true"));
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"descr":{"order":"buy 1.25000000 XBTUSD @ limit 27500.0"}}}"#))
+        });
+        
+        let config = Config::new()
+            .with_api_key("key")
+            .with_api_secret("c2VjcmV0")
+            .with_dry_run(true);
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let order = Order::new("XBTUSD", OrderSide::Buy, OrderType::Limit, "1.25").with_price("27500.0").with_validate(false);
+        let response = client.private().add_order(&order).await.unwrap();
+        
+        assert!(response.txid.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_add_order_retries_with_cl_ord_id() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+        
+        // Fails the first order with a 503, then accepts it
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let sent = bodies.clone();
+        let transport = MockTransport::new(move |request| {
+            let mut bodies = sent.lock().unwrap();
+            bodies.push(request.body.unwrap());
+            
+            if bodies.len() == 1 {
+                return Ok(TransportResponse { status: 503, headers: Default::default(), body: b"unavailable".to_vec() });
+            }
+            
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"descr":{"order":"buy 0.01000000 XBTUSD @ market"},"txid":["OUF4EM-FRGI2-MQMWZD"]}}"#))
+        });
+        
+        let config = Config::new()
+            .with_api_key("key")
+            .with_api_secret("c2VjcmV0")
+            .with_order_retries(2)
+            .with_order_retry_delay(Duration::from_millis(1));
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let response = client.private().market_buy("XBTUSD", "0.01").await.unwrap();
+        assert_eq!(response.txid, vec!["OUF4EM-FRGI2-MQMWZD"]);
+        
+        // Both attempts carry the same generated client order ID
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2);
+        let cl_ord_id = |body: &str| body.split('&').find(|param| param.starts_with("cl_ord_id=")).map(str::to_string);
+        assert!(cl_ord_id(&bodies[0]).is_some());
+        assert_eq!(cl_ord_id(&bodies[0]), cl_ord_id(&bodies[1]));
+    }
+    
+    #[tokio::test]
+    async fn test_dry_run_refuses_amend() {
+        use crate::config::Config;
+        use crate::transport::mock::MockTransport;
+        
+        let transport = MockTransport::unreachable();
+        
+        let config = Config::new()
+            .with_api_key("key")
+            .with_api_secret("c2VjcmV0")
+            .with_dry_run(true);
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let error = client.private().amend_order("OHYO67-6LP66-HMQ437", OrderAmend::new().with_limit_price("27500.0")).await.unwrap_err();
+        assert!(matches!(error, Error::Other(ref msg) if msg.starts_with("Dry run")));
+    }
+    
+    #[tokio::test]
+    async fn test_add_order_batch_validates_entries() {
+        use crate::config::Config;
+        use crate::transport::mock::MockTransport;
+        
+        let transport = MockTransport::unreachable();
+        
+        let config = Config::new().with_api_key("key").with_api_secret("c2VjcmV0");
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let orders = vec![
+            BatchOrder::new(OrderSide::Buy, OrderType::Limit, "1.0").with_price("27500.0"),
+            BatchOrder::new(OrderSide::Sell, OrderType::Limit, "1.0"),
+        ];
+        let error = client.private().add_order_batch("XBTUSD", orders, None).await.unwrap_err();
+        assert!(matches!(error, Error::Other(ref msg) if msg == "Order 1 in batch: limit order requires a price"));
+    }
+    
+    #[tokio::test]
+    async fn test_add_order_batch_deadline() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        let transport = MockTransport::new(|request| {
+            let body = request.body.unwrap();
+            assert!(body.contains("deadline=2023-07-06T18%3A50%3A48.000Z"));
+            assert!(!body.contains("orders%5B0%5D%5Bdeadline%5D"));
+            assert!(body.contains("orders%5B1%5D%5Breduce_only%5D=true"));
+            assert!(body.contains("orders%5B1%5D%5Btrigger%5D=index"));
+            assert!(!body.contains("orders%5B0%5D%5Btrigger%5D"));
+            
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"orders":[{"descr":{"order":"buy 1.0 XBTUSD @ limit 27500.0"},"txid":"OUF4EM-FRGI2-MQMWZD"},{"descr":{"order":"sell 1.0 XBTUSD @ limit 28500.0"},"txid":"OUF4EM-FRGI2-MQMWZE"}]}}"#))
+        });
+        
+        let config = Config::new().with_api_key("key").with_api_secret("c2VjcmV0");
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let orders = vec![
+            BatchOrder::new(OrderSide::Buy, OrderType::Limit, "1.0").with_price("27500.0"),
+            BatchOrder::new(OrderSide::Sell, OrderType::Limit, "1.0").with_price("28500.0").with_reduce_only(true).with_trigger(crate::models::trading::TriggerType::Index),
+        ];
+        let responses = client.private().add_order_batch("XBTUSD", orders, Some("2023-07-06T18:50:48.000Z")).await.unwrap();
+        assert_eq!(responses.len(), 2);
+        assert!(responses.iter().all(Result::is_ok));
+    }
+    
+    #[tokio::test]
+    async fn test_custom_signer() {
+        use crate::auth::Signer;
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        #[derive(Debug)]
+        struct MockSigner;
+        
+        impl Signer for MockSigner {
+            fn sign(&self, path: &str, nonce: u64, _postdata: &str) -> Result<String> {
+                Ok(format!("{}:{}", path, nonce))
+            }
+        }
+        
+        let transport = MockTransport::new(|request| {
+            let signature = request.headers.iter().find(|(name, _)| name == "API-Sign").unwrap();
+            assert!(signature.1.starts_with("/0/private/Balance:"));
+            
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"XXBT":"1.0"}}"#))
+        });
+        
+        // No API secret is needed with a custom signer
+        let config = Config::new().with_api_key("key").with_signer(MockSigner).require_auth().unwrap();
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let balance = client.private().get_balance().await.unwrap();
+        assert_eq!(balance["XXBT"], "1.0");
+    }
+    
+    #[tokio::test]
+    async fn test_query_ledgers() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        let transport = MockTransport::new(|request| {
+            assert!(request.url.ends_with("/0/private/QueryLedgers"));
+            assert!(request.body.unwrap().contains("id=L4UESK-KG3EQ-UFO4T5%2CL4UESK-KG3EQ-UFO4T6"));
+            
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"L4UESK-KG3EQ-UFO4T5":{"refid":"TJKLXX-PGMUI-4NTLXU","time":1688464484.1787,"type":"trade","subtype":"","aclass":"currency","asset":"ZGBP","amount":"-24.5000","fee":"0.0490","balance":"459567.9171"}}}"#))
+        });
+        
+        let config = Config::new().with_api_key("key").with_api_secret("c2VjcmV0");
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let ledger = client.private().query_ledgers(vec!["L4UESK-KG3EQ-UFO4T5", "L4UESK-KG3EQ-UFO4T6"], None).await.unwrap();
+        assert_eq!(ledger["L4UESK-KG3EQ-UFO4T5"].refid, "TJKLXX-PGMUI-4NTLXU");
+    }
+    
+    #[tokio::test]
+    async fn test_get_open_orders_with_trades() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        // Lists a trade that QueryTrades does not return when `missing_trade` is set
+        let transport = |missing_trade: bool| MockTransport::new(move |request| {
+            let body = request.body.unwrap();
+            
+            if request.url.ends_with("/0/private/OpenOrders") {
+                assert!(body.contains("trades=true"));
+                
+                let other_trades = if missing_trade { json!(["TMISSI-NGTRA-DE0000"]) } else { Value::Null };
+                let result = json!({ "error": [], "result": { "open": {
+                    "OQCLML-BW3P3-BUCMWZ": open_order(json!(["TCCCTY-WE2O6-P3NB37"])),
+                    "OB5VMB-B4U2U-DK2WRW": open_order(other_trades),
+                } } });
+                return Ok(TransportResponse::json(result.to_string()));
+            }
+            
+            assert!(request.url.ends_with("/0/private/QueryTrades"));
+            assert!(body.contains("TCCCTY-WE2O6-P3NB37"));
+            
+            let result = json!({ "error": [], "result": { "TCCCTY-WE2O6-P3NB37": {
+                "ordertxid": "OQCLML-BW3P3-BUCMWZ",
+                "postxid": "TKH2SE-M7IF5-CFI7LT",
+                "pair": "XXBTZUSD",
+                "time": 1688667796.8802,
+                "type": "buy",
+                "ordertype": "limit",
+                "price": "30010.00000",
+                "cost": "11253.75000",
+                "fee": "0.00000",
+                "vol": "0.37500000",
+                "margin": "0.00000",
+                "misc": ""
+            } } });
+            Ok(TransportResponse::json(result.to_string()))
+        });
+        
+        let config = Config::new().with_api_key("key").with_api_secret("c2VjcmV0");
+        let client = KrakenClient::with_transport(config.clone(), transport(false)).unwrap();
+        
+        let orders = client.private().get_open_orders_with_trades(None).await.unwrap();
+        assert_eq!(orders["OQCLML-BW3P3-BUCMWZ"].trades["TCCCTY-WE2O6-P3NB37"].vol, "0.37500000");
+        assert!(orders["OB5VMB-B4U2U-DK2WRW"].trades.is_empty());
+        
+        let client = KrakenClient::with_transport(config, transport(true)).unwrap();
+        let error = client.private().get_open_orders_with_trades(None).await.unwrap_err();
+        assert!(matches!(error, Error::Other(ref msg) if msg.contains("TMISSI-NGTRA-DE0000")));
+    }
+    
+    #[tokio::test]
+    async fn test_query_orders_by_userref_pages_closed_orders() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        let transport = MockTransport::new(|request| {
+            let body = request.body.unwrap();
+            assert!(body.contains("userref=42"));
+            
+            if request.url.ends_with("/0/private/OpenOrders") {
+                return Ok(TransportResponse::json(r#"{"error":[],"result":{"open":{}}}"#));
+            }
+            
+            if request.url.ends_with("/0/private/ClosedOrders") {
+                let mut order = open_order(Value::Null);
+                order["status"] = json!("closed");
+                order["closetm"] = json!(1688666600.0);
+                
+                // One order per page, so the second order is only found by following ofs
+                let txid = if body.contains("ofs=1") { "OB5VMB-B4U2U-DK2WRW" } else { "OQCLML-BW3P3-BUCMWZ" };
+                let result = json!({ "error": [], "result": { "closed": { txid: order }, "count": 2 } });
+                return Ok(TransportResponse::json(result.to_string()));
+            }
+            
+            assert!(request.url.ends_with("/0/private/QueryOrders"));
+            assert!(body.contains("OQCLML-BW3P3-BUCMWZ") && body.contains("OB5VMB-B4U2U-DK2WRW"));
+            
+            let result = json!({ "error": [], "result": {
+                "OQCLML-BW3P3-BUCMWZ": open_order(Value::Null),
+                "OB5VMB-B4U2U-DK2WRW": open_order(Value::Null),
+            } });
+            Ok(TransportResponse::json(result.to_string()))
+        });
+        
+        let config = Config::new().with_api_key("key").with_api_secret("c2VjcmV0");
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let orders = client.private().query_orders_by_userref(42, None).await.unwrap();
+        assert_eq!(orders.len(), 2);
+        assert_eq!(orders["OB5VMB-B4U2U-DK2WRW"].txid, "OB5VMB-B4U2U-DK2WRW");
+    }
+    
+    #[tokio::test]
+    async fn test_cancel_order_by_userref() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        let transport = MockTransport::new(|request| {
+            assert!(request.url.ends_with("/0/private/CancelOrder"));
+            assert!(request.body.unwrap().contains("txid=-42"));
+            
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"count":3}}"#))
+        });
+        
+        let config = Config::new().with_api_key("key").with_api_secret("c2VjcmV0");
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let result = client.private().cancel_order(-42).await.unwrap();
+        assert_eq!(result.count, 3);
+    }
+    
+    #[tokio::test]
+    async fn test_cancel_order_by_cl_ord_id() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        let transport = MockTransport::new(|request| {
+            let body = request.body.unwrap();
+            assert!(body.contains("cl_ord_id=my-order-1"));
+            assert!(!body.contains("txid="));
+            
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"count":1}}"#))
+        });
+        
+        let config = Config::new().with_api_key("key").with_api_secret("c2VjcmV0");
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let target = CancelTarget::ClOrdId("my-order-1".to_string());
+        let result = client.private().cancel_order(target).await.unwrap();
+        assert_eq!(result.count, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_amend_order() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        let transport = MockTransport::new(|request| {
+            assert!(request.url.ends_with("/0/private/AmendOrder"));
+            
+            let body = request.body.unwrap();
+            assert!(body.contains("txid=OHYO67-6LP66-HMQ437"));
+            assert!(body.contains("limit_price=30010.0"));
+            assert!(body.contains("post_only=true"));
+            assert!(!body.contains("order_qty"));
+            
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"amend_id":"TJSMEH-AA67V-YUSQ6O"}}"#))
+        });
+        
+        let config = Config::new().with_api_key("key").with_api_secret("c2VjcmV0");
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        let amend = OrderAmend::new().with_limit_price("30010.0").with_post_only(true);
+        let response = client.private().amend_order("OHYO67-6LP66-HMQ437", amend).await.unwrap();
+        assert_eq!(response.amend_id, "TJSMEH-AA67V-YUSQ6O");
+        
+        let empty = client.private().amend_order("OHYO67-6LP66-HMQ437", OrderAmend::new().with_post_only(true)).await;
+        assert!(matches!(empty, Err(Error::Other(_))));
     }
 }
@@ -1,13 +1,18 @@
 //! Public API endpoints for the Kraken API
 
+use futures::future;
 use std::collections::HashMap;
 use serde::Deserialize;
 use serde_json::Value;
 
+use crate::api::rate_limiter::Tier;
 use crate::client::KrakenClient;
-use crate::error::{Error, Result};
-use crate::models::market::{AssetInfo, AssetPair, OHLC, Orderbook, OrderbookEntry, ServerTime, Ticker, Trade};
-use crate::utils::build_url;
+use crate::config::EndpointClass;
+use crate::error::{split_warnings, Error, Result};
+use crate::models::market::{AssetInfo, AssetPair, AssetPairInfo, Interval, OHLC, Orderbook, OrderbookEntry, Paginated, ServerTime, Spread, SystemStatus, Ticker, Trade, TradeCursor};
+use crate::transport::TransportRequest;
+use crate::utils::{build_url, sanitize_params, PairLookup};
+use uuid::Uuid;
 
 /// Response wrapper for Kraken API responses
 #[derive(Debug, Deserialize)]
@@ -31,22 +36,52 @@ impl<'a> PublicApi<'a> {
         Self { client }
     }
     
-    /// Get server time
-    pub async fn get_server_time(&self) -> Result<ServerTime> {
-        let url = format!("{}/0/public/Time", self.client.config.api_url);
+    /// Make a public API request
+    ///
+    /// Each request gets a unique ID, which is included in its log lines and in any
+    /// error it returns.
+    async fn public_request<T: for<'de> Deserialize<'de>>(&self, endpoint: &str, params: HashMap<String, String>) -> Result<T> {
+        let request_id = Uuid::new_v4().to_string();
+        let endpoint = self.client.config.endpoint_path(endpoint);
+        
+        self.send_public(&endpoint, params, &request_id).await
+            .map_err(|e| e.with_request_id(&request_id))
+    }
+    
+    /// Send a public API request and parse its result
+    async fn send_public<T: for<'de> Deserialize<'de>>(&self, endpoint: &str, params: HashMap<String, String>, request_id: &str) -> Result<T> {
+        let url = build_url(&self.client.config.api_url, endpoint, Some(&params));
+        
+        log_debug!("GET {} request={} params={}", endpoint, request_id, sanitize_params(&params));
+        
+        let timeout = self.client.config.request_timeout(EndpointClass::for_endpoint(endpoint));
         
-        let response = self.client.http_client()
-            .get(&url)
-            .send()
-            .await?
-            .json::<KrakenResponse<ServerTime>>()
-            .await?;
+        let request = TransportRequest::get(url).with_timeout(timeout);
+        let response = self.client.send(endpoint, request).await?;
+        let response: KrakenResponse<T> = serde_json::from_slice(&response.body)?;
         
-        if !response.error.is_empty() {
-            return Err(Error::Api(response.error.join(", ")));
+        let (errors, warnings) = split_warnings(response.error);
+        
+        if !warnings.is_empty() {
+            log_warn!("GET {} request={} returned warnings: {}", endpoint, request_id, warnings.join(", "));
+        }
+        
+        if !errors.is_empty() {
+            log_warn!("GET {} request={} failed: {}", endpoint, request_id, errors.join(", "));
+            return Err(Error::from_api_errors(&errors));
         }
         
-        response.result.ok_or_else(|| Error::Api("No result data".to_string()))
+        response.result.ok_or_else(|| Error::Api("No result data".into()))
+    }
+    
+    /// Get server time
+    pub async fn get_server_time(&self) -> Result<ServerTime> {
+        self.public_request("/public/Time", HashMap::new()).await
+    }
+    
+    /// Get system status
+    pub async fn get_system_status(&self) -> Result<SystemStatus> {
+        self.public_request("/public/SystemStatus", HashMap::new()).await
     }
     
     /// Get asset info
@@ -57,69 +92,93 @@ impl<'a> PublicApi<'a> {
             params.insert("asset".to_string(), assets.join(","));
         }
         
-        let url = build_url(&self.client.config.api_url, "/0/public/Assets", Some(&params));
-        
-        let response = self.client.http_client()
-            .get(&url)
-            .send()
-            .await?
-            .json::<KrakenResponse<HashMap<String, AssetInfo>>>()
-            .await?;
-        
-        if !response.error.is_empty() {
-            return Err(Error::Api(response.error.join(", ")));
-        }
-        
-        response.result.ok_or_else(|| Error::Api("No result data".to_string()))
+        self.public_request("/public/Assets", params).await
     }
     
     /// Get tradable asset pairs
     pub async fn get_asset_pairs(&self, pairs: Option<Vec<&str>>) -> Result<HashMap<String, AssetPair>> {
+        self.get_asset_pairs_with_info(pairs, AssetPairInfo::Info).await
+    }
+    
+    /// Get tradable asset pairs, restricted to the given level of info
+    pub async fn get_asset_pairs_with_info(&self, pairs: Option<Vec<&str>>, info: AssetPairInfo) -> Result<HashMap<String, AssetPair>> {
         let mut params = HashMap::new();
         
         if let Some(pairs) = pairs {
             params.insert("pair".to_string(), pairs.join(","));
         }
         
-        let url = build_url(&self.client.config.api_url, "/0/public/AssetPairs", Some(&params));
-        
-        let response = self.client.http_client()
-            .get(&url)
-            .send()
-            .await?
-            .json::<KrakenResponse<HashMap<String, AssetPair>>>()
-            .await?;
+        params.insert("info".to_string(), info.to_string());
         
-        if !response.error.is_empty() {
-            return Err(Error::Api(response.error.join(", ")));
-        }
-        
-        response.result.ok_or_else(|| Error::Api("No result data".to_string()))
+        self.public_request("/public/AssetPairs", params).await
     }
     
     /// Get ticker information
-    pub async fn get_ticker(&self, pairs: Vec<&str>) -> Result<HashMap<String, Ticker>> {
+    ///
+    /// Pairs whose ticker data can't be parsed are skipped rather than failing the
+    /// whole request, and their names are returned alongside the parsed tickers.
+    /// Tickers are keyed by Kraken's pair name; use [`PairLookup::get_pair`] to look
+    /// them up by the name that was requested.
+    pub async fn get_ticker(&self, pairs: Vec<&str>) -> Result<(HashMap<String, Ticker>, Vec<String>)> {
         let mut params = HashMap::new();
         params.insert("pair".to_string(), pairs.join(","));
         
-        let url = build_url(&self.client.config.api_url, "/0/public/Ticker", Some(&params));
+        let result: HashMap<String, Value> = self.public_request("/public/Ticker", params).await?;
+        
+        Ok(parse_tickers(result))
+    }
+    
+    /// Get ticker information for many pairs, `chunk_size` pairs per request
+    ///
+    /// The chunks are requested concurrently, each taking a token from the rate limiter
+    /// first, so chunks beyond the available tokens are spaced out by the refill time.
+    /// The results are merged; pairs whose ticker data can't be parsed are skipped.
+    pub async fn get_tickers_chunked(&self, pairs: Vec<&str>, chunk_size: usize) -> Result<HashMap<String, Ticker>> {
+        if chunk_size == 0 {
+            return Err(Error::Other("Chunk size must be at least 1".to_string()));
+        }
         
-        let response = self.client.http_client()
-            .get(&url)
-            .send()
-            .await?
-            .json::<KrakenResponse<HashMap<String, Ticker>>>()
-            .await?;
+        let requests = pairs.chunks(chunk_size).map(|chunk| async move {
+            self.client.rate_limiter().wait(Tier::Tier1).await;
+            self.get_ticker(chunk.to_vec()).await
+        });
         
-        if !response.error.is_empty() {
-            return Err(Error::Api(response.error.join(", ")));
+        let mut tickers = HashMap::new();
+        let mut failed = Vec::new();
+        
+        for (chunk_tickers, chunk_failed) in future::try_join_all(requests).await? {
+            tickers.extend(chunk_tickers);
+            failed.extend(chunk_failed);
+        }
+        
+        if !failed.is_empty() {
+            log_debug!("Skipped unparseable tickers: {}", failed.join(", "));
         }
         
-        response.result.ok_or_else(|| Error::Api("No result data".to_string()))
+        Ok(tickers)
+    }
+    
+    /// Get ticker information for every tradable pair
+    ///
+    /// This returns one entry per pair listed on Kraken, so the response is large;
+    /// prefer [`PublicApi::get_ticker`] when only a few pairs are needed. Pairs whose
+    /// ticker data can't be parsed are skipped.
+    pub async fn get_all_tickers(&self) -> Result<HashMap<String, Ticker>> {
+        let result: HashMap<String, Value> = self.public_request("/public/Ticker", HashMap::new()).await?;
+        
+        let (tickers, failed) = parse_tickers(result);
+        
+        if !failed.is_empty() {
+            log_debug!("Skipped unparseable tickers: {}", failed.join(", "));
+        }
+        
+        Ok(tickers)
     }
     
     /// Get OHLC data
-    pub async fn get_ohlc(&self, pair: &str, interval: Option<u32>, since: Option<u64>) -> Result<(Vec<OHLC>, u64)> {
+    ///
+    /// Pass the page's [`Paginated::next_since`] as `since` to fetch newer candles.
+    pub async fn get_ohlc(&self, pair: &str, interval: Option<u32>, since: Option<u64>) -> Result<Paginated<Vec<OHLC>>> {
         let mut params = HashMap::new();
         params.insert("pair".to_string(), pair.to_string());
         
@@ -131,26 +190,13 @@ impl<'a> PublicApi<'a> {
             params.insert("since".to_string(), since.to_string());
         }
         
-        let url = build_url(&self.client.config.api_url, "/0/public/OHLC", Some(&params));
-        
-        let response = self.client.http_client()
-            .get(&url)
-            .send()
-            .await?
-            .json::<KrakenResponse<HashMap<String, Value>>>()
-            .await?;
-        
-        if !response.error.is_empty() {
-            return Err(Error::Api(response.error.join(", ")));
-        }
-        
-        let result = response.result.ok_or_else(|| Error::Api("No result data".to_string()))?;
+        let result: HashMap<String, Value> = self.public_request("/public/OHLC", params).await?;
         
         // Extract the last field which is the 'last' timestamp
-        let last = result.get("last").and_then(|v| v.as_u64()).ok_or_else(|| Error::Api("Missing 'last' field".to_string()))?;
+        let last = result.get("last").and_then(|v| v.as_u64()).ok_or_else(|| Error::Api("Missing 'last' field".into()))?;
         
         // Extract the OHLC data
-        let ohlc_data = result.get(pair).and_then(|v| v.as_array()).ok_or_else(|| Error::Api(format!("Missing data for pair {}", pair)))?;
+        let ohlc_data = result.get_pair(pair).and_then(|v| v.as_array()).ok_or_else(|| Error::Api(format!("Missing data for pair {}", pair).into()))?;
         
         // Parse the OHLC data
         let mut ohlc_vec = Vec::new();
@@ -172,75 +218,93 @@ impl<'a> PublicApi<'a> {
             }
         }
         
-        Ok((ohlc_vec, last))
+        Ok(Paginated { data: ohlc_vec, cursor: last })
+    }
+    
+    /// Get OHLC data for a typed interval
+    pub async fn get_ohlc_with_interval(&self, pair: &str, interval: Interval, since: Option<u64>) -> Result<Paginated<Vec<OHLC>>> {
+        self.get_ohlc(pair, Some(interval.as_minutes()), since).await
+    }
+    
+    /// Get the full OHLC history for a pair starting at `from`
+    ///
+    /// Follows the `last` cursor returned by each page until no newer data is
+    /// available, waiting on the rate limiter between calls.
+    pub async fn get_ohlc_history(&self, pair: &str, interval: u32, from: u64) -> Result<Vec<OHLC>> {
+        let mut history: Vec<OHLC> = Vec::new();
+        let mut since = from;
+        
+        loop {
+            self.client.rate_limiter().wait(Tier::Tier1).await;
+            
+            let page = self.get_ohlc(pair, Some(interval), Some(since)).await?;
+            let next_since = page.next_since();
+            
+            // The final (still forming) candle of the previous page reappears at the
+            // start of the next one, so drop anything the new page supersedes
+            if let Some(first) = page.data.first() {
+                let first_time = first.time;
+                history.retain(|candle| candle.time < first_time);
+                history.extend(page.data);
+            }
+            
+            // Caught up once the cursor stops advancing
+            if next_since <= since {
+                break;
+            }
+            
+            since = next_since;
+        }
+        
+        Ok(history)
     }
     
     /// Get order book
+    ///
+    /// Order books are keyed by Kraken's pair name; use [`PairLookup::get_pair`] to
+    /// look them up by the name that was requested.
     pub async fn get_orderbook(&self, pair: &str, count: Option<u32>) -> Result<HashMap<String, Orderbook>> {
         let mut params = HashMap::new();
         params.insert("pair".to_string(), pair.to_string());
         
         if let Some(count) = count {
+            if !(1..=500).contains(&count) {
+                return Err(Error::Other(format!("Order book count must be between 1 and 500, got {}", count)));
+            }
+            
             params.insert("count".to_string(), count.to_string());
         }
         
-        let url = build_url(&self.client.config.api_url, "/0/public/Depth", Some(&params));
-        
-        let response = self.client.http_client()
-            .get(&url)
-            .send()
-            .await?
-            .json::<KrakenResponse<HashMap<String, Value>>>()
-            .await?;
-        
-        if !response.error.is_empty() {
-            return Err(Error::Api(response.error.join(", ")));
-        }
-        
-        let result = response.result.ok_or_else(|| Error::Api("No result data".to_string()))?;
+        let result: HashMap<String, Value> = self.public_request("/public/Depth", params).await?;
         
         let mut orderbooks = HashMap::new();
         
         for (pair_name, orderbook_data) in result {
-            let asks_data = orderbook_data.get("asks").and_then(|v| v.as_array()).ok_or_else(|| Error::Api("Missing 'asks' field".to_string()))?;
-            let bids_data = orderbook_data.get("bids").and_then(|v| v.as_array()).ok_or_else(|| Error::Api("Missing 'bids' field".to_string()))?;
+            let asks_data = orderbook_data.get("asks").and_then(|v| v.as_array()).ok_or_else(|| Error::Api("Missing 'asks' field".into()))?;
+            let bids_data = orderbook_data.get("bids").and_then(|v| v.as_array()).ok_or_else(|| Error::Api("Missing 'bids' field".into()))?;
             
-            let mut asks = Vec::new();
-            for ask in asks_data {
-                if let Some(arr) = ask.as_array() {
-                    if arr.len() >= 2 {
-                        let entry = OrderbookEntry {
-                            price: arr[0].as_str().unwrap_or("0").to_string(),
-                            volume: arr[1].as_str().unwrap_or("0").to_string(),
-                            timestamp: arr.get(2).and_then(|v| v.as_f64()),
-                        };
-                        asks.push(entry);
-                    }
-                }
-            }
+            let asks = asks_data.iter().filter_map(OrderbookEntry::from_level).collect();
+            let bids = bids_data.iter().filter_map(OrderbookEntry::from_level).collect();
             
-            let mut bids = Vec::new();
-            for bid in bids_data {
-                if let Some(arr) = bid.as_array() {
-                    if arr.len() >= 2 {
-                        let entry = OrderbookEntry {
-                            price: arr[0].as_str().unwrap_or("0").to_string(),
-                            volume: arr[1].as_str().unwrap_or("0").to_string(),
-                            timestamp: arr.get(2).and_then(|v| v.as_f64()),
-                        };
-                        bids.push(entry);
-                    }
-                }
-            }
-            
-            orderbooks.insert(pair_name, Orderbook { asks, bids });
+            orderbooks.insert(pair_name, Orderbook::new(asks, bids));
         }
         
         Ok(orderbooks)
     }
     
+    /// Get the order book of a single pair
+    pub async fn get_orderbook_for(&self, pair: &str, count: Option<u32>) -> Result<Orderbook> {
+        let orderbooks = self.get_orderbook(pair, count).await?;
+        
+        orderbooks.get_pair(pair).cloned().ok_or_else(|| Error::Api(format!("Missing data for pair {}", pair).into()))
+    }
+    
     /// Get recent trades
-    pub async fn get_trades(&self, pair: &str, since: Option<u64>) -> Result<(Vec<Trade>, u64)> {
+    ///
+    /// Pass the page's [`Paginated::next_since`] as `since` to fetch newer trades, or a
+    /// nanosecond timestamp converted with `TradeCursor::from`. `count` sets the page
+    /// size, up to 1000 trades.
+    pub async fn get_trades(&self, pair: &str, since: Option<TradeCursor>, count: Option<u32>) -> Result<Paginated<Vec<Trade>, TradeCursor>> {
         let mut params = HashMap::new();
         params.insert("pair".to_string(), pair.to_string());
         
@@ -248,26 +312,25 @@ impl<'a> PublicApi<'a> {
             params.insert("since".to_string(), since.to_string());
         }
         
-        let url = build_url(&self.client.config.api_url, "/0/public/Trades", Some(&params));
-        
-        let response = self.client.http_client()
-            .get(&url)
-            .send()
-            .await?
-            .json::<KrakenResponse<HashMap<String, Value>>>()
-            .await?;
-        
-        if !response.error.is_empty() {
-            return Err(Error::Api(response.error.join(", ")));
+        if let Some(count) = count {
+            if !(1..=1000).contains(&count) {
+                return Err(Error::Other(format!("Trade count must be between 1 and 1000, got {}", count)));
+            }
+            
+            params.insert("count".to_string(), count.to_string());
         }
         
-        let result = response.result.ok_or_else(|| Error::Api("No result data".to_string()))?;
+        let result: HashMap<String, Value> = self.public_request("/public/Trades", params).await?;
         
-        // Extract the last field which is the 'last' timestamp
-        let last = result.get("last").and_then(|v| v.as_u64()).ok_or_else(|| Error::Api("Missing 'last' field".to_string()))?;
+        // Extract the 'last' cursor, sent as a string but kept exact if it is a number
+        let last = match result.get("last") {
+            Some(Value::String(last)) => TradeCursor(last.clone()),
+            Some(Value::Number(last)) => TradeCursor(last.to_string()),
+            _ => return Err(Error::Api("Missing 'last' field".into())),
+        };
         
         // Extract the trades data
-        let trades_data = result.get(pair).and_then(|v| v.as_array()).ok_or_else(|| Error::Api(format!("Missing data for pair {}", pair)))?;
+        let trades_data = result.get_pair(pair).and_then(|v| v.as_array()).ok_or_else(|| Error::Api(format!("Missing data for pair {}", pair).into()))?;
         
         // Parse the trades data
         let mut trades_vec = Vec::new();
@@ -287,6 +350,363 @@ impl<'a> PublicApi<'a> {
             }
         }
         
-        Ok((trades_vec, last))
+        Ok(Paginated { data: trades_vec, cursor: last })
+    }
+    
+    /// Get recent spreads
+    ///
+    /// Spreads are returned by ascending time; use [`crate::models::market::SpreadStats`]
+    /// for statistics over the window.
+    pub async fn get_recent_spreads(&self, pair: &str, since: Option<u64>) -> Result<Paginated<Vec<Spread>>> {
+        let mut params = HashMap::new();
+        params.insert("pair".to_string(), pair.to_string());
+        
+        if let Some(since) = since {
+            params.insert("since".to_string(), since.to_string());
+        }
+        
+        let result: HashMap<String, Value> = self.public_request("/public/Spread", params).await?;
+        
+        // Extract the last field which is the 'last' timestamp
+        let last = result.get("last").and_then(|v| v.as_u64()).ok_or_else(|| Error::Api("Missing 'last' field".into()))?;
+        
+        // Extract the spread data
+        let spread_data = result.get_pair(pair).and_then(|v| v.as_array()).ok_or_else(|| Error::Api(format!("Missing data for pair {}", pair).into()))?;
+        
+        // Parse the spread data
+        let mut spreads = Vec::new();
+        for item in spread_data {
+            if let Some(arr) = item.as_array() {
+                if arr.len() >= 3 {
+                    let spread = Spread {
+                        time: arr[0].as_i64().unwrap_or(0),
+                        bid: arr[1].as_str().unwrap_or("0").to_string(),
+                        ask: arr[2].as_str().unwrap_or("0").to_string(),
+                    };
+                    spreads.push(spread);
+                }
+            }
+        }
+        
+        Ok(Paginated { data: spreads, cursor: last })
+    }
+}
+
+/// Parse ticker data per pair, collecting the names of pairs that fail to parse
+fn parse_tickers(result: HashMap<String, Value>) -> (HashMap<String, Ticker>, Vec<String>) {
+    let mut tickers = HashMap::new();
+    let mut failed = Vec::new();
+    
+    for (pair_name, ticker_data) in result {
+        match serde_json::from_value::<Ticker>(ticker_data) {
+            Ok(ticker) => {
+                tickers.insert(pair_name, ticker);
+            }
+            Err(_) => failed.push(pair_name),
+        }
+    }
+    
+    (tickers, failed)
+}
+
+/// Pass the public endpoint methods to `$callback`, after the tokens in its braces
+///
+/// [`PublicApiTrait`] and the blocking client are both generated from this one list.
+macro_rules! public_methods {
+    ($callback:ident! { $($args:tt)* }) => {
+        $callback! {
+            $($args)*
+            
+            /// Get server time
+            fn get_server_time() -> ServerTime;
+            
+            /// Get system status
+            fn get_system_status() -> SystemStatus;
+            
+            /// Get asset info
+            fn get_assets(assets: Option<Vec<&str>>) -> HashMap<String, AssetInfo>;
+            
+            /// Get tradable asset pairs
+            fn get_asset_pairs(pairs: Option<Vec<&str>>) -> HashMap<String, AssetPair>;
+            
+            /// Get tradable asset pairs, restricted to the given level of info
+            fn get_asset_pairs_with_info(pairs: Option<Vec<&str>>, info: AssetPairInfo) -> HashMap<String, AssetPair>;
+            
+            /// Get ticker information
+            fn get_ticker(pairs: Vec<&str>) -> (HashMap<String, Ticker>, Vec<String>);
+            
+            /// Get ticker information for many pairs, `chunk_size` pairs per request
+            fn get_tickers_chunked(pairs: Vec<&str>, chunk_size: usize) -> HashMap<String, Ticker>;
+            
+            /// Get ticker information for every tradable pair
+            fn get_all_tickers() -> HashMap<String, Ticker>;
+            
+            /// Get OHLC data
+            fn get_ohlc(pair: &str, interval: Option<u32>, since: Option<u64>) -> Paginated<Vec<OHLC>>;
+            
+            /// Get OHLC data for a typed interval
+            fn get_ohlc_with_interval(pair: &str, interval: Interval, since: Option<u64>) -> Paginated<Vec<OHLC>>;
+            
+            /// Get the full OHLC history for a pair starting at `from`
+            fn get_ohlc_history(pair: &str, interval: u32, from: u64) -> Vec<OHLC>;
+            
+            /// Get order book
+            fn get_orderbook(pair: &str, count: Option<u32>) -> HashMap<String, Orderbook>;
+            
+            /// Get the order book of a single pair
+            fn get_orderbook_for(pair: &str, count: Option<u32>) -> Orderbook;
+            
+            /// Get recent trades
+            fn get_trades(pair: &str, since: Option<TradeCursor>, count: Option<u32>) -> Paginated<Vec<Trade>, TradeCursor>;
+            
+            /// Get recent spreads
+            fn get_recent_spreads(pair: &str, since: Option<u64>) -> Paginated<Vec<Spread>>;
+        }
+    };
+}
+#[cfg(feature = "blocking")]
+pub(crate) use public_methods;
+
+public_methods!(api_trait! {
+    /// Public API endpoints as a trait
+    ///
+    /// Implemented by [`PublicApi`]. Depend on this trait to swap in a fake in tests.
+    PublicApiTrait for PublicApi;
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_parse_tickers_skips_malformed_pairs() {
+        let mut result = HashMap::new();
+        result.insert("XXBTZUSD".to_string(), json!({
+            "a": ["30000.0", "1", "1.000"],
+            "b": ["29999.0", "1", "1.000"],
+            "c": ["30000.0", "0.1"],
+            "v": ["100.0", "200.0"],
+            "p": ["29900.0", "29800.0"],
+            "t": [1000, 2000],
+            "l": ["29000.0", "28000.0"],
+            "h": ["31000.0", "32000.0"],
+            "o": "29500.0"
+        }));
+        result.insert("NEWPAIR".to_string(), json!({ "a": "unexpected" }));
+        
+        let (tickers, failed) = parse_tickers(result);
+        assert!(tickers.contains_key("XXBTZUSD"));
+        assert_eq!(failed, vec!["NEWPAIR".to_string()]);
+    }
+    
+    #[tokio::test]
+    async fn test_mock_transport_response_is_parsed() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        let transport = MockTransport::new(|request| {
+            assert!(request.url.ends_with("/0/public/Time"));
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"unixtime":1688669448,"rfc1123":"Thu, 06 Jul 23 18:50:48 +0000"}}"#))
+        });
+        
+        let client = KrakenClient::with_transport(Config::new(), transport).unwrap();
+        let time = client.public().get_server_time().await.unwrap();
+        
+        assert_eq!(time.unixtime, 1688669448);
+    }
+    
+    #[tokio::test]
+    async fn test_public_api_trait() {
+        use crate::config::Config;
+        use crate::transport::mock::MockTransport;
+        
+        let transport = MockTransport::json(r#"{"error":[],"result":{"unixtime":1688669448,"rfc1123":""}}"#);
+        
+        /// Fake whose every call fails
+        struct UnavailablePublicApi;
+        
+        public_methods!(unimplemented_api! { PublicApiTrait for UnavailablePublicApi; });
+        
+        async fn server_time(api: &dyn PublicApiTrait) -> Result<i64> {
+            Ok(api.get_server_time().await?.unixtime)
+        }
+        
+        let client = KrakenClient::with_transport(Config::new(), transport).unwrap();
+        assert_eq!(server_time(&client.public()).await.unwrap(), 1688669448);
+        
+        let error = server_time(&UnavailablePublicApi).await.unwrap_err();
+        assert!(matches!(error, Error::Other(ref msg) if msg == "get_server_time is not implemented"));
+    }
+    
+    #[tokio::test]
+    async fn test_get_trades_count() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        
+        let transport = MockTransport::new(|request| {
+            assert!(request.url.contains("count=1000"));
+            
+            // The cursor is sent back exactly as it was received
+            if request.url.contains("since=") {
+                assert!(request.url.contains("since=1688669448123456789"));
+            }
+            
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"XXBTZUSD":[["30000.0","0.1",1688669448.1,"b","l",""]],"last":"1688669448123456789"}}"#))
+        });
+        
+        let client = KrakenClient::with_transport(Config::new(), transport).unwrap();
+        let page = client.public().get_trades("XBTUSD", None, Some(1000)).await.unwrap();
+        assert_eq!(page.data.len(), 1);
+        assert_eq!(page.next_since().as_str(), "1688669448123456789");
+        
+        client.public().get_trades("XBTUSD", Some(page.next_since()), Some(1000)).await.unwrap();
+        
+        for count in [0, 1001] {
+            let result = client.public().get_trades("XBTUSD", None, Some(count)).await;
+            assert!(matches!(result, Err(Error::Other(_))));
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_get_tickers_chunked() {
+        use crate::config::Config;
+        use crate::transport::{mock::MockTransport, TransportResponse};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        
+        static REQUESTS: AtomicUsize = AtomicUsize::new(0);
+        
+        // Returns a ticker for every requested pair, counting the requests
+        let transport = MockTransport::new(|request| {
+            REQUESTS.fetch_add(1, Ordering::SeqCst);
+            
+            let url = url::Url::parse(&request.url).unwrap();
+            let pairs = url.query_pairs().find(|(key, _)| key == "pair").unwrap().1.into_owned();
+            assert!(pairs.split(',').count() <= 2);
+            
+            let ticker = json!({
+                "a": ["30000.0", "1", "1.000"],
+                "b": ["29999.0", "1", "1.000"],
+                "c": ["30000.0", "0.1"],
+                "v": ["100.0", "200.0"],
+                "p": ["29900.0", "29800.0"],
+                "t": [1000, 2000],
+                "l": ["29000.0", "28000.0"],
+                "h": ["31000.0", "32000.0"],
+                "o": "29500.0"
+            });
+            let result: serde_json::Map<String, Value> = pairs.split(',').map(|pair| (pair.to_string(), ticker.clone())).collect();
+            
+            Ok(TransportResponse::json(json!({ "error": [], "result": result }).to_string()))
+        });
+        
+        let client = KrakenClient::with_transport(Config::new(), transport).unwrap();
+        let tickers = client.public().get_tickers_chunked(vec!["XBTUSD", "ETHUSD", "DOTUSD", "SOLUSD", "ADAUSD"], 2).await.unwrap();
+        
+        assert_eq!(tickers.len(), 5);
+        assert_eq!(REQUESTS.load(Ordering::SeqCst), 3);
+        assert!(client.public().get_tickers_chunked(vec!["XBTUSD"], 0).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_rate_limit_error_is_mapped() {
+        use crate::config::Config;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/0/public/Time"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "error": ["EAPI:Rate limit exceeded"] })))
+            .mount(&server)
+            .await;
+        
+        let client = KrakenClient::new(Config::new().with_api_url(server.uri())).unwrap();
+        let error = client.public().get_server_time().await.unwrap_err();
+        
+        assert!(matches!(error, Error::RateLimit(ref code) if code == "EAPI:Rate limit exceeded"));
+        assert!(error.request_id().is_some());
+    }
+    
+    #[tokio::test]
+    async fn test_non_success_status_is_reported() {
+        use crate::config::Config;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/0/public/Time"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("<html>Service temporarily unavailable</html>"))
+            .mount(&server)
+            .await;
+        
+        let client = KrakenClient::new(Config::new().with_api_url(server.uri())).unwrap();
+        let error = client.public().get_server_time().await.unwrap_err();
+        
+        assert!(matches!(error, Error::HttpStatus { code: 503, ref body } if body.contains("temporarily unavailable")));
+    }
+    
+    #[tokio::test]
+    async fn test_warnings_do_not_fail_the_call() {
+        use crate::config::Config;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/0/public/Time"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "error": ["WService:Degraded"],
+                "result": { "unixtime": 1688669448, "rfc1123": "Thu, 06 Jul 23 18:50:48 +0000" }
+            })))
+            .mount(&server)
+            .await;
+        
+        let client = KrakenClient::new(Config::new().with_api_url(server.uri())).unwrap();
+        let time = client.public().get_server_time().await.unwrap();
+        
+        assert_eq!(time.unixtime, 1688669448);
+    }
+    
+    #[cfg(feature = "logging")]
+    #[tokio::test]
+    async fn test_failed_call_logs_error_codes() {
+        use crate::config::Config;
+        use log::{Level, Log, Metadata, Record};
+        use std::sync::Mutex;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        
+        struct CaptureLogger(Mutex<Vec<(Level, String)>>);
+        
+        impl Log for CaptureLogger {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+            
+            fn log(&self, record: &Record) {
+                self.0.lock().unwrap().push((record.level(), record.args().to_string()));
+            }
+            
+            fn flush(&self) {}
+        }
+        
+        static LOGGER: CaptureLogger = CaptureLogger(Mutex::new(Vec::new()));
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+        
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/0/public/Time"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "error": ["EGeneral:Invalid arguments"] })))
+            .mount(&server)
+            .await;
+        
+        let client = KrakenClient::new(Config::new().with_api_url(server.uri())).unwrap();
+        assert!(client.public().get_server_time().await.is_err());
+        
+        let records = LOGGER.0.lock().unwrap();
+        assert!(records.iter().any(|(level, message)| *level == Level::Warn && message.contains("EGeneral:Invalid arguments")));
     }
 }
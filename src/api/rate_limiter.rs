@@ -1,12 +1,35 @@
 //! Rate limiter implementation for the Kraken API
 
 use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
+/// Source of the current time for rate limiting
+///
+/// Defaults to [`SystemClock`]; a mock clock can be supplied to test time-based
+/// behavior deterministically.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Get the current instant
+    fn now(&self) -> Instant;
+}
+
+/// Clock backed by [`Instant::now`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
 /// Rate limiter for the Kraken API
 ///
+/// Used to pace repeated public calls; private calls are gated by the shared
+/// [`PrivateRateLimiter`] counter instead.
+///
 /// Kraken API has different rate limits for different endpoints:
 /// - Tier 1: 15 calls per 45 seconds
 /// - Tier 2: 20 calls per 60 seconds
@@ -50,16 +73,20 @@ struct TokenBucket {
     
     /// Last refill time
     last_refill: Instant,
+    
+    /// Source of the current time
+    clock: Arc<dyn Clock>,
 }
 
 impl TokenBucket {
     /// Create a new token bucket
-    fn new(max_tokens: u32, refill_time: Duration) -> Self {
+    fn new(max_tokens: u32, refill_time: Duration, clock: Arc<dyn Clock>) -> Self {
         Self {
             max_tokens,
             tokens: max_tokens,
             refill_time,
-            last_refill: Instant::now(),
+            last_refill: clock.now(),
+            clock,
         }
     }
     
@@ -76,15 +103,22 @@ impl TokenBucket {
     }
     
     /// Refill the bucket
+    ///
+    /// Only whole intervals are consumed, so the time towards the next token carries
+    /// over between calls. A full bucket restarts the clock instead of banking idle time.
     fn refill(&mut self) {
-        let now = Instant::now();
+        let now = self.clock.now();
         let elapsed = now.duration_since(self.last_refill);
         
         if elapsed >= self.refill_time {
-            let refills = (elapsed.as_secs_f64() / self.refill_time.as_secs_f64()) as u32;
-            let new_tokens = self.tokens + refills;
-            self.tokens = new_tokens.min(self.max_tokens);
-            self.last_refill = now;
+            let refills = (elapsed.as_nanos() / self.refill_time.as_nanos()).min(self.max_tokens as u128) as u32;
+            self.tokens = (self.tokens + refills).min(self.max_tokens);
+            
+            if self.tokens == self.max_tokens {
+                self.last_refill = now;
+            } else {
+                self.last_refill += self.refill_time * refills;
+            }
         }
     }
     
@@ -95,8 +129,7 @@ impl TokenBucket {
         if self.tokens > 0 {
             Duration::from_secs(0)
         } else {
-            let now = Instant::now();
-            let elapsed = now.duration_since(self.last_refill);
+            let elapsed = self.clock.now().duration_since(self.last_refill);
             
             if elapsed >= self.refill_time {
                 Duration::from_secs(0)
@@ -110,19 +143,24 @@ impl TokenBucket {
 impl RateLimiter {
     /// Create a new rate limiter
     pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+    
+    /// Create a new rate limiter that reads the time from `clock`
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
         let mut tiers = HashMap::new();
         
         // Tier 1: 15 calls per 45 seconds
-        tiers.insert(Tier::Tier1, TokenBucket::new(15, Duration::from_secs(45)));
+        tiers.insert(Tier::Tier1, TokenBucket::new(15, Duration::from_secs(45), clock.clone()));
         
         // Tier 2: 20 calls per 60 seconds
-        tiers.insert(Tier::Tier2, TokenBucket::new(20, Duration::from_secs(60)));
+        tiers.insert(Tier::Tier2, TokenBucket::new(20, Duration::from_secs(60), clock.clone()));
         
         // Tier 3: 20 calls per 60 seconds
-        tiers.insert(Tier::Tier3, TokenBucket::new(20, Duration::from_secs(60)));
+        tiers.insert(Tier::Tier3, TokenBucket::new(20, Duration::from_secs(60), clock.clone()));
         
         // Tier 4: 15 calls per 60 seconds
-        tiers.insert(Tier::Tier4, TokenBucket::new(15, Duration::from_secs(60)));
+        tiers.insert(Tier::Tier4, TokenBucket::new(15, Duration::from_secs(60), clock));
         
         Self {
             tiers: Arc::new(Mutex::new(tiers)),
@@ -142,11 +180,18 @@ impl RateLimiter {
         }
     }
     
-    /// Wait for a token to be available
+    /// Wait until a token has been taken for the given tier
+    ///
+    /// Concurrent waiters each take their own token, so calls beyond the bucket size
+    /// are spaced out by the refill time instead of all firing once it elapses.
     pub async fn wait(&self, tier: Tier) {
-        let wait_time = self.acquire(tier).await;
-        
-        if wait_time > Duration::from_secs(0) {
+        loop {
+            let wait_time = self.acquire(tier).await;
+            
+            if wait_time == Duration::from_secs(0) {
+                return;
+            }
+            
             tokio::time::sleep(wait_time).await;
         }
     }
@@ -157,3 +202,517 @@ impl Default for RateLimiter {
         Self::new()
     }
 }
+
+/// Account verification tier, which determines the order rate limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VerificationTier {
+    /// Starter accounts
+    #[default]
+    Starter,
+    
+    /// Intermediate accounts
+    Intermediate,
+    
+    /// Pro accounts
+    Pro,
+}
+
+impl VerificationTier {
+    /// Maximum value of the per-pair order rate counter
+    pub fn max_order_counter(&self) -> f64 {
+        match self {
+            VerificationTier::Starter => 60.0,
+            VerificationTier::Intermediate => 125.0,
+            VerificationTier::Pro => 180.0,
+        }
+    }
+    
+    /// Amount the per-pair order rate counter decays by each second
+    pub fn order_counter_decay(&self) -> f64 {
+        match self {
+            VerificationTier::Starter => 1.0,
+            VerificationTier::Intermediate => 2.34,
+            VerificationTier::Pro => 3.75,
+        }
+    }
+    
+    /// Maximum value of the shared private API counter
+    pub fn max_api_counter(&self) -> f64 {
+        match self {
+            VerificationTier::Starter => 15.0,
+            VerificationTier::Intermediate => 20.0,
+            VerificationTier::Pro => 20.0,
+        }
+    }
+    
+    /// Amount the shared private API counter decays by each second
+    pub fn api_counter_decay(&self) -> f64 {
+        match self {
+            VerificationTier::Starter => 0.33,
+            VerificationTier::Intermediate => 0.5,
+            VerificationTier::Pro => 1.0,
+        }
+    }
+}
+
+/// Points a private endpoint adds to the shared private API counter
+///
+/// Order placement and cancellation are limited by the per-pair order counter
+/// instead, so they cost nothing here.
+pub fn endpoint_cost(endpoint: &str) -> f64 {
+    match endpoint.rsplit('/').next() {
+        Some("Ledgers" | "QueryLedgers" | "TradesHistory") => 2.0,
+        Some("AddOrder" | "AddOrderBatch" | "AmendOrder" | "EditOrder" | "CancelOrder" | "CancelAll" | "CancelAllOrdersAfter") => 0.0,
+        _ => 1.0,
+    }
+}
+
+/// Rate limiter for the private Kraken API
+///
+/// Kraken tracks a single counter shared by all private endpoints:
+/// - Each call increments the counter by the endpoint's cost (see [`endpoint_cost`])
+/// - The counter decays continuously at a rate based on the account verification tier
+///
+/// Calls are delayed until the counter has decayed enough to stay below the tier maximum.
+#[derive(Debug, Clone)]
+pub struct PrivateRateLimiter {
+    /// Account verification tier
+    tier: VerificationTier,
+    
+    /// Shared counter
+    counter: Arc<Mutex<DecayingCounter>>,
+}
+
+impl PrivateRateLimiter {
+    /// Create a new private API rate limiter for the given verification tier
+    pub fn new(tier: VerificationTier) -> Self {
+        Self::with_clock(tier, Arc::new(SystemClock))
+    }
+    
+    /// Create a new private API rate limiter that reads the time from `clock`
+    pub fn with_clock(tier: VerificationTier, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            tier,
+            counter: Arc::new(Mutex::new(DecayingCounter::new(clock))),
+        }
+    }
+    
+    /// Get the verification tier
+    pub fn tier(&self) -> VerificationTier {
+        self.tier
+    }
+    
+    /// Get the current counter value
+    pub async fn counter(&self) -> f64 {
+        let mut counter = self.counter.lock().await;
+        counter.decay(self.tier.api_counter_decay());
+        counter.value
+    }
+    
+    /// Add `cost` to the counter, returning how long to wait if it would exceed the maximum
+    pub async fn acquire(&self, cost: f64) -> Duration {
+        let mut counter = self.counter.lock().await;
+        counter.decay(self.tier.api_counter_decay());
+        
+        let excess = counter.value + cost - self.tier.max_api_counter();
+        
+        if excess <= 0.0 {
+            counter.value += cost;
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(excess / self.tier.api_counter_decay())
+        }
+    }
+    
+    /// Wait until `cost` can be added to the counter without exceeding the maximum
+    pub async fn wait(&self, cost: f64) {
+        if cost <= 0.0 {
+            return;
+        }
+        
+        loop {
+            let wait_time = self.acquire(cost).await;
+            
+            if wait_time == Duration::from_secs(0) {
+                break;
+            }
+            
+            tokio::time::sleep(wait_time).await;
+        }
+    }
+}
+
+/// Order rate limiter for the Kraken API
+///
+/// Kraken tracks a separate counter per pair for order placement and cancellation:
+/// - Each AddOrder increments the counter by 1
+/// - Each CancelOrder increments the counter by a penalty that shrinks as the order ages
+/// - The counter decays continuously at a rate based on the account verification tier
+///
+/// Orders are delayed until the counter has decayed enough to stay below the tier maximum.
+#[derive(Debug, Clone)]
+pub struct OrderRateLimiter {
+    /// Account verification tier
+    tier: VerificationTier,
+    
+    /// Counter state
+    state: Arc<Mutex<OrderRateState>>,
+    
+    /// Source of the current time
+    clock: Arc<dyn Clock>,
+}
+
+/// Counter state shared by clones of an order rate limiter
+#[derive(Debug, Default)]
+struct OrderRateState {
+    /// Per-pair counters
+    counters: HashMap<String, DecayingCounter>,
+    
+    /// Pair and placement time of orders placed through this limiter, by transaction ID
+    orders: HashMap<String, (String, Instant)>,
+}
+
+/// Counter that decays continuously over time
+#[derive(Debug, Clone)]
+struct DecayingCounter {
+    /// Counter value
+    value: f64,
+    
+    /// Last time the decay was applied
+    last_update: Instant,
+    
+    /// Source of the current time
+    clock: Arc<dyn Clock>,
+}
+
+impl DecayingCounter {
+    /// Create a counter at zero
+    fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            value: 0.0,
+            last_update: clock.now(),
+            clock,
+        }
+    }
+    
+    /// Apply the decay since the last update
+    fn decay(&mut self, rate: f64) {
+        let now = self.clock.now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f64();
+        self.value = (self.value - elapsed * rate).max(0.0);
+        self.last_update = now;
+    }
+}
+
+/// Age after which cancelling an order no longer adds a penalty
+const CANCEL_PENALTY_WINDOW: Duration = Duration::from_secs(300);
+
+/// Penalty added to the order counter for cancelling an order of the given age
+fn cancel_penalty(age: Duration) -> f64 {
+    match age.as_secs() {
+        0..=4 => 8.0,
+        5..=9 => 6.0,
+        10..=14 => 5.0,
+        15..=44 => 4.0,
+        45..=89 => 2.0,
+        90..=299 => 1.0,
+        _ => 0.0,
+    }
+}
+
+impl OrderRateLimiter {
+    /// Create a new order rate limiter for the given verification tier
+    pub fn new(tier: VerificationTier) -> Self {
+        Self::with_clock(tier, Arc::new(SystemClock))
+    }
+    
+    /// Create a new order rate limiter that reads the time from `clock`
+    pub fn with_clock(tier: VerificationTier, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            tier,
+            state: Arc::new(Mutex::new(OrderRateState::default())),
+            clock,
+        }
+    }
+    
+    /// Get the verification tier
+    pub fn tier(&self) -> VerificationTier {
+        self.tier
+    }
+    
+    /// Get the current counter value for a pair
+    pub async fn counter(&self, pair: &str) -> f64 {
+        let mut state = self.state.lock().await;
+        
+        match state.counters.get_mut(pair) {
+            Some(counter) => {
+                counter.decay(self.tier.order_counter_decay());
+                counter.value
+            }
+            None => 0.0,
+        }
+    }
+    
+    /// Add `cost` to the counter for a pair, returning how long to wait if it would exceed the maximum
+    pub async fn acquire(&self, pair: &str, cost: f64) -> Duration {
+        let mut state = self.state.lock().await;
+        
+        let counter = state.counters.entry(pair.to_string()).or_insert_with(|| DecayingCounter::new(self.clock.clone()));
+        counter.decay(self.tier.order_counter_decay());
+        
+        let excess = counter.value + cost - self.tier.max_order_counter();
+        
+        if excess <= 0.0 {
+            counter.value += cost;
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(excess / self.tier.order_counter_decay())
+        }
+    }
+    
+    /// Wait until `cost` can be added to the counter for a pair without exceeding the maximum
+    pub async fn wait(&self, pair: &str, cost: f64) {
+        loop {
+            let wait_time = self.acquire(pair, cost).await;
+            
+            if wait_time == Duration::from_secs(0) {
+                break;
+            }
+            
+            tokio::time::sleep(wait_time).await;
+        }
+    }
+    
+    /// Record an order placed on a pair so its cancellation penalty can be computed later
+    ///
+    /// Orders that have filled, expired or been cancelled some other way are never
+    /// taken, so orders too old to carry a penalty are dropped here.
+    pub async fn record_order(&self, txid: &str, pair: &str) {
+        let now = self.clock.now();
+        let mut state = self.state.lock().await;
+        state.orders.retain(|_, (_, placed)| now.duration_since(*placed) < CANCEL_PENALTY_WINDOW);
+        state.orders.insert(txid.to_string(), (pair.to_string(), now));
+    }
+    
+    /// Take the pair and cancellation penalty of a recorded order
+    ///
+    /// Returns `None` if the order was not placed through this limiter.
+    pub async fn take_cancel_penalty(&self, txid: &str) -> Option<(String, f64)> {
+        let now = self.clock.now();
+        let mut state = self.state.lock().await;
+        
+        state.orders.remove(txid).map(|(pair, placed)| (pair, cancel_penalty(now.duration_since(placed))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_cancel_penalty() {
+        assert_eq!(cancel_penalty(Duration::from_secs(1)), 8.0);
+        assert_eq!(cancel_penalty(Duration::from_secs(12)), 5.0);
+        assert_eq!(cancel_penalty(Duration::from_secs(120)), 1.0);
+        assert_eq!(cancel_penalty(Duration::from_secs(600)), 0.0);
+    }
+    
+    /// Clock that only moves when advanced
+    #[derive(Debug)]
+    struct MockClock(std::sync::Mutex<Instant>);
+    
+    impl MockClock {
+        fn new() -> Arc<Self> {
+            Arc::new(Self(std::sync::Mutex::new(Instant::now())))
+        }
+        
+        fn advance(&self, duration: Duration) {
+            *self.0.lock().unwrap() += duration;
+        }
+    }
+    
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+    
+    #[test]
+    fn test_token_bucket_drip_feed_accumulates() {
+        let clock = MockClock::new();
+        let mut bucket = TokenBucket::new(3, Duration::from_secs(10), clock.clone());
+        bucket.tokens = 0;
+        let start = bucket.last_refill;
+        
+        // Partial intervals add up to a token across calls
+        clock.advance(Duration::from_secs(4));
+        bucket.refill();
+        clock.advance(Duration::from_secs(4));
+        bucket.refill();
+        assert_eq!(bucket.tokens, 0);
+        
+        clock.advance(Duration::from_secs(4));
+        bucket.refill();
+        assert_eq!(bucket.tokens, 1);
+        assert_eq!(bucket.last_refill, start + Duration::from_secs(10));
+        
+        // The 2 seconds past the first interval count towards the next token
+        clock.advance(Duration::from_secs(7));
+        bucket.refill();
+        assert_eq!(bucket.tokens, 1);
+        clock.advance(Duration::from_secs(1));
+        bucket.refill();
+        assert_eq!(bucket.tokens, 2);
+    }
+    
+    #[test]
+    fn test_token_bucket_keeps_remainder_after_long_gap() {
+        let clock = MockClock::new();
+        let mut bucket = TokenBucket::new(5, Duration::from_secs(10), clock.clone());
+        bucket.tokens = 0;
+        let start = bucket.last_refill;
+        
+        clock.advance(Duration::from_secs(25));
+        bucket.refill();
+        assert_eq!(bucket.tokens, 2);
+        assert_eq!(bucket.last_refill, start + Duration::from_secs(20));
+        
+        clock.advance(Duration::from_secs(5));
+        bucket.refill();
+        assert_eq!(bucket.tokens, 3);
+    }
+    
+    #[test]
+    fn test_token_bucket_full_does_not_bank_idle_time() {
+        let clock = MockClock::new();
+        let mut bucket = TokenBucket::new(2, Duration::from_secs(10), clock.clone());
+        let start = bucket.last_refill;
+        
+        clock.advance(Duration::from_secs(100));
+        bucket.refill();
+        assert_eq!(bucket.tokens, 2);
+        assert_eq!(bucket.last_refill, start + Duration::from_secs(100));
+        
+        // Draining the bucket right away waits a full interval for the next token
+        assert!(bucket.take());
+        assert!(bucket.take());
+        assert!(!bucket.take());
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(bucket.time_until_next_token(), Duration::from_secs(5));
+    }
+    
+    #[tokio::test]
+    async fn test_rate_limiter_with_mock_clock() {
+        let clock = MockClock::new();
+        let limiter = RateLimiter::with_clock(clock.clone());
+        
+        for _ in 0..15 {
+            assert_eq!(limiter.acquire(Tier::Tier1).await, Duration::from_secs(0));
+        }
+        
+        assert_eq!(limiter.acquire(Tier::Tier1).await, Duration::from_secs(45));
+        
+        clock.advance(Duration::from_secs(45));
+        assert_eq!(limiter.acquire(Tier::Tier1).await, Duration::from_secs(0));
+    }
+    
+    /// Clock that follows tokio's (pausable) time
+    #[derive(Debug)]
+    struct TokioClock;
+    
+    impl Clock for TokioClock {
+        fn now(&self) -> Instant {
+            tokio::time::Instant::now().into_std()
+        }
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_waits_are_spaced_out() {
+        let limiter = RateLimiter::with_clock(Arc::new(TokioClock));
+        let start = tokio::time::Instant::now();
+        
+        for _ in 0..15 {
+            limiter.wait(Tier::Tier1).await;
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(0));
+        
+        // Each waiter takes its own token, one refill interval apart
+        let waits = (0..3).map(|_| async {
+            limiter.wait(Tier::Tier1).await;
+            start.elapsed()
+        });
+        let mut elapsed = futures::future::join_all(waits).await;
+        elapsed.sort();
+        
+        assert_eq!(elapsed, vec![Duration::from_secs(45), Duration::from_secs(90), Duration::from_secs(135)]);
+    }
+    
+    #[test]
+    fn test_endpoint_cost() {
+        assert_eq!(endpoint_cost("/0/private/Ledgers"), 2.0);
+        assert_eq!(endpoint_cost("/0/private/TradesHistory"), 2.0);
+        assert_eq!(endpoint_cost("/0/private/AddOrder"), 0.0);
+        assert_eq!(endpoint_cost("/0/private/Balance"), 1.0);
+        assert_eq!(endpoint_cost("/0/private/Staking/Assets"), 1.0);
+    }
+    
+    #[tokio::test]
+    async fn test_private_rate_limiter_counter() {
+        let clock = MockClock::new();
+        let limiter = PrivateRateLimiter::with_clock(VerificationTier::Intermediate, clock.clone());
+        
+        assert_eq!(limiter.acquire(2.0).await, Duration::from_secs(0));
+        assert_eq!(limiter.acquire(1.0).await, Duration::from_secs(0));
+        assert_eq!(limiter.counter().await, 3.0);
+        
+        // Exceeding the maximum reports a wait instead of charging the counter
+        assert_eq!(limiter.acquire(20.0).await, Duration::from_secs(6));
+        assert_eq!(limiter.counter().await, 3.0);
+        
+        // The counter decays by 0.5 per second
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(limiter.counter().await, 1.0);
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(limiter.counter().await, 0.0);
+        assert_eq!(limiter.acquire(20.0).await, Duration::from_secs(0));
+    }
+    
+    #[tokio::test]
+    async fn test_order_rate_limiter_counter() {
+        let clock = MockClock::new();
+        let limiter = OrderRateLimiter::with_clock(VerificationTier::Starter, clock.clone());
+        
+        assert_eq!(limiter.acquire("XBTUSD", 1.0).await, Duration::from_secs(0));
+        assert_eq!(limiter.counter("XBTUSD").await, 1.0);
+        assert_eq!(limiter.counter("ETHUSD").await, 0.0);
+        
+        // Exceeding the maximum reports a wait instead of charging the counter
+        assert_eq!(limiter.acquire("XBTUSD", 60.0).await, Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(limiter.counter("XBTUSD").await, 0.0);
+        assert_eq!(limiter.acquire("XBTUSD", 60.0).await, Duration::from_secs(0));
+        
+        // The cancellation penalty shrinks as the order ages
+        limiter.record_order("OABC", "XBTUSD").await;
+        limiter.record_order("ODEF", "XBTUSD").await;
+        assert_eq!(limiter.take_cancel_penalty("OABC").await, Some(("XBTUSD".to_string(), 8.0)));
+        assert!(limiter.take_cancel_penalty("OABC").await.is_none());
+        
+        clock.advance(Duration::from_secs(12));
+        assert_eq!(limiter.take_cancel_penalty("ODEF").await, Some(("XBTUSD".to_string(), 5.0)));
+    }
+    
+    #[tokio::test]
+    async fn test_record_order_prunes_old_orders() {
+        let clock = MockClock::new();
+        let limiter = OrderRateLimiter::with_clock(VerificationTier::Starter, clock.clone());
+        
+        limiter.record_order("OOLD", "XBTUSD").await;
+        clock.advance(CANCEL_PENALTY_WINDOW);
+        limiter.record_order("ONEW", "XBTUSD").await;
+        
+        let state = limiter.state.lock().await;
+        assert!(!state.orders.contains_key("OOLD"));
+        assert!(state.orders.contains_key("ONEW"));
+    }
+}
@@ -1,23 +1,120 @@
 //! WebSocket API implementation for the Kraken API
 
-use futures::{SinkExt, StreamExt};
-use serde_json::Value;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures::{future, stream, SinkExt, Stream, StreamExt};
+use percent_encoding::percent_decode_str;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::{handshake::client::Response, Message};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 use url::Url;
 
 use crate::client::KrakenClient;
+use crate::config::EndpointClass;
 use crate::error::{Error, Result};
-use crate::models::websocket::{WebSocketMessage, WebSocketSubscriptionRequest, WebSocketUnsubscriptionRequest};
+use crate::models::trading::Order;
+use crate::models::websocket::{ConnectionState, WebSocketAddOrderStatus, WebSocketChannel, WebSocketCancelOrderStatus, WebSocketMessage, WebSocketSubscription, WebSocketSubscriptionRequest, WebSocketSubscriptionType, WebSocketUnsubscriptionRequest};
+
+/// Replies awaited by in-flight requests, keyed by request ID
+type PendingReplies = Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+/// Subscribed channels, keyed by channel ID
+type Channels = Arc<std::sync::Mutex<HashMap<u64, WebSocketChannel>>>;
+
+/// Subscriptions awaiting their `subscriptionStatus` message
+type SubscriptionWaiters = Arc<std::sync::Mutex<Vec<SubscriptionWaiter>>>;
+
+/// A subscription awaiting its `subscriptionStatus` message
+struct SubscriptionWaiter {
+    /// Subscription name
+    name: WebSocketSubscriptionType,
+    
+    /// Subscribed pair, if any
+    pair: Option<String>,
+    
+    /// Sender for the status message
+    reply: oneshot::Sender<WebSocketMessage>,
+}
+
+/// Major version of the WebSocket API whose message layouts this client was tested against
+const TESTED_MAJOR_VERSION: &str = "1";
+
+/// How long [`WebSocketApi::shutdown`] waits for the close frame to be written
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest proxy response header accepted when opening a tunnel
+const MAX_PROXY_RESPONSE_BYTES: usize = 8192;
+
+/// Check whether a WebSocket API version reported by Kraken has been tested with this client
+pub fn is_tested_version(version: &str) -> bool {
+    version.split('.').next() == Some(TESTED_MAJOR_VERSION)
+}
 
 /// WebSocket API
 pub struct WebSocketApi {
     /// WebSocket URL
     ws_url: String,
     
+    /// WebSocket URL for private channels
+    ws_auth_url: String,
+    
+    /// Whether the current connection is to the authenticated endpoint
+    authenticated: bool,
+    
+    /// Timeout for establishing the connection
+    connect_timeout: Duration,
+    
+    /// Timeout for the reply to an order request
+    request_timeout: Duration,
+    
+    /// HTTP proxy the connection is tunnelled through
+    proxy: Option<String>,
+    
+    /// Send every order as validate-only
+    dry_run: bool,
+    
+    /// Interval between pings sent to keep the connection alive
+    heartbeat_interval: Option<Duration>,
+    
+    /// Time without any incoming message after which the connection is considered dead
+    liveness_timeout: Option<Duration>,
+    
     /// Message sender
     tx: Option<mpsc::Sender<Message>>,
+    
+    /// Replies awaited by in-flight order requests
+    pending: PendingReplies,
+    
+    /// Subscriptions awaiting confirmation
+    subscription_waiters: SubscriptionWaiters,
+    
+    /// Next request ID
+    next_reqid: Arc<AtomicU64>,
+    
+    /// State of the current connection
+    state: Arc<watch::Sender<ConnectionState>>,
+    
+    /// API version reported by the current connection
+    version: Arc<std::sync::Mutex<Option<String>>>,
+    
+    /// Channels subscribed on the current connection
+    channels: Channels,
+    
+    /// Task forwarding outgoing messages to the socket
+    writer: Option<JoinHandle<()>>,
+    
+    /// Task forwarding incoming messages from the socket
+    reader: Option<JoinHandle<()>>,
+    
+    /// Task sending periodic pings
+    heartbeat: Option<JoinHandle<()>>,
 }
 
 impl WebSocketApi {
@@ -25,22 +122,128 @@ impl WebSocketApi {
     pub fn new(client: &KrakenClient) -> Self {
         Self {
             ws_url: client.config.ws_url.clone(),
+            ws_auth_url: client.config.ws_auth_url.clone(),
+            authenticated: false,
+            connect_timeout: client.config.request_timeout(EndpointClass::WebSocketConnect),
+            request_timeout: client.config.request_timeout(EndpointClass::WebSocketRequest),
+            proxy: client.config.proxy.clone().filter(|_| client.config.ws_proxy),
+            dry_run: client.config.dry_run,
+            heartbeat_interval: None,
+            liveness_timeout: None,
             tx: None,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            subscription_waiters: Arc::new(std::sync::Mutex::new(Vec::new())),
+            next_reqid: Arc::new(AtomicU64::new(1)),
+            state: Arc::new(watch::channel(ConnectionState::Disconnected).0),
+            version: Arc::new(std::sync::Mutex::new(None)),
+            channels: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            writer: None,
+            reader: None,
+            heartbeat: None,
         }
     }
     
+    /// Send a ping at this interval while connected
+    ///
+    /// Pings make the server answer even when no subscribed data is flowing, so a
+    /// stalled connection is caught by the liveness timeout.
+    pub fn with_heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.heartbeat_interval = Some(heartbeat_interval);
+        self
+    }
+    
+    /// Consider the connection dead if no message arrives within this time
+    ///
+    /// When it expires, an error is sent on the message channel and the connection is
+    /// closed, leaving it to the caller to reconnect.
+    pub fn with_liveness_timeout(mut self, liveness_timeout: Duration) -> Self {
+        self.liveness_timeout = Some(liveness_timeout);
+        self
+    }
+    
     /// Connect to the WebSocket API
+    ///
+    /// Error events and failed subscription statuses from Kraken are delivered as
+    /// [`Error::WebSocket`] items on the channel, without closing the connection.
     pub async fn connect(&mut self) -> Result<mpsc::Receiver<Result<WebSocketMessage>>> {
+        self.connect_to(false).await
+    }
+    
+    /// Connect to the authenticated WebSocket API, required for private channels
+    pub async fn connect_auth(&mut self) -> Result<mpsc::Receiver<Result<WebSocketMessage>>> {
+        self.connect_to(true).await
+    }
+    
+    /// Connect to the WebSocket API, returning the messages as a [`Stream`]
+    ///
+    /// Equivalent to [`WebSocketApi::connect`], but composes with `futures`
+    /// combinators such as `filter` and `map`.
+    pub async fn connect_stream(&mut self) -> Result<impl Stream<Item = Result<WebSocketMessage>>> {
+        self.connect().await.map(receiver_stream)
+    }
+    
+    /// Connect to the authenticated WebSocket API, returning the messages as a [`Stream`]
+    pub async fn connect_auth_stream(&mut self) -> Result<impl Stream<Item = Result<WebSocketMessage>>> {
+        self.connect_auth().await.map(receiver_stream)
+    }
+    
+    /// Connect to the endpoint serving the given subscription
+    ///
+    /// Private channels use the authenticated URL, public channels the regular one.
+    pub async fn connect_for(&mut self, request: &WebSocketSubscriptionRequest) -> Result<mpsc::Receiver<Result<WebSocketMessage>>> {
+        self.connect_to(request.subscription.name.is_private()).await
+    }
+    
+    /// Connect to either the public or the authenticated WebSocket URL
+    async fn connect_to(&mut self, authenticated: bool) -> Result<mpsc::Receiver<Result<WebSocketMessage>>> {
+        // Tear down any previous connection so its tasks don't outlive it
+        self.abort_tasks();
+        
+        let ws_url = if authenticated { &self.ws_auth_url } else { &self.ws_url };
+        
         // Create message channels
         let (tx, mut rx) = mpsc::channel::<Message>(100);
         let (message_tx, message_rx) = mpsc::channel::<Result<WebSocketMessage>>(100);
         
         // Store the channel
+        let heartbeat_tx = tx.clone();
         self.tx = Some(tx);
+        self.authenticated = authenticated;
+        
+        // Give each connection its own state so a stale reader can't overwrite it
+        let state = Arc::new(watch::channel(ConnectionState::Connecting).0);
+        self.state = state.clone();
+        let version = Arc::new(std::sync::Mutex::new(None));
+        self.version = version.clone();
+        let channels: Channels = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        self.channels = channels.clone();
         
         // Connect to the WebSocket
-        let url = Url::parse(&self.ws_url).map_err(|e| Error::WebSocket(format!("Invalid URL: {}", e)))?;
-        let (ws_stream, _) = connect_async(url).await.map_err(|e| Error::WebSocket(format!("Connection error: {}", e)))?;
+        let connect_timeout = self.connect_timeout;
+        let proxy = self.proxy.as_deref();
+        let connected = async {
+            let url = Url::parse(ws_url).map_err(|e| Error::WebSocket(format!("Invalid URL: {}", e)))?;
+            let proxy = match proxy {
+                Some(proxy) => tunnel_proxy(proxy, &url)?,
+                None => None,
+            };
+            let connect = async {
+                match proxy {
+                    Some(proxy) => connect_via_proxy(proxy, url).await,
+                    None => connect_async(url).await.map_err(|e| Error::WebSocket(format!("Connection error: {}", e))),
+                }
+            };
+            tokio::time::timeout(connect_timeout, connect)
+                .await
+                .map_err(|_| Error::Timeout(format!("WebSocket connection to {} was not established within {:?}", ws_url, connect_timeout)))?
+        };
+        let (ws_stream, _) = match connected.await {
+            Ok(connected) => connected,
+            Err(e) => {
+                state.send_replace(ConnectionState::Disconnected);
+                return Err(e);
+            }
+        };
         let (write, mut read) = ws_stream.split();
         
         // Wrap the write sink in an Arc<Mutex<_>> to share between tasks
@@ -48,31 +251,81 @@ impl WebSocketApi {
         let write_clone = write.clone();
         
         // Spawn a task to forward messages from the channel to the WebSocket
-        tokio::spawn(async move {
+        let writer = tokio::spawn(async move {
             while let Some(message) = rx.recv().await {
                 let mut write_lock = write_clone.lock().await;
                 if let Err(e) = write_lock.send(message).await {
-                    eprintln!("Error sending message: {}", e);
+                    log_warn!("Error sending WebSocket message: {}", e);
                     break;
                 }
             }
         });
+        let writer_abort = writer.abort_handle();
+        self.writer = Some(writer);
         
         // Spawn a task to forward messages from the WebSocket to the channel
-        tokio::spawn(async move {
-            while let Some(message) = read.next().await {
+        let pending = self.pending.clone();
+        let subscription_waiters = self.subscription_waiters.clone();
+        let liveness_timeout = self.liveness_timeout;
+        self.reader = Some(tokio::spawn(async move {
+            loop {
+                let message = match liveness_timeout {
+                    Some(liveness_timeout) => match tokio::time::timeout(liveness_timeout, read.next()).await {
+                        Ok(message) => message,
+                        Err(_) => {
+                            let _ = message_tx.send(Err(Error::WebSocket(format!("No message received for {:?}, connection is dead", liveness_timeout)))).await;
+                            
+                            // Stop the writer and close the socket, without waiting long on a peer that stopped answering
+                            writer_abort.abort();
+                            let _ = tokio::time::timeout(Duration::from_secs(1), async { write.lock().await.close().await }).await;
+                            break;
+                        }
+                    },
+                    None => read.next().await,
+                };
+                
+                let message = match message {
+                    Some(message) => message,
+                    None => break,
+                };
+                
                 match message {
                     Ok(Message::Text(text)) => {
+                        // Hand replies, including error events, to the request awaiting them
+                        if let Some((reqid, reply)) = parse_reply(&text) {
+                            if let Some(sender) = pending.lock().await.remove(&reqid) {
+                                let _ = sender.send(reply);
+                                continue;
+                            }
+                        }
+                        
                         let result = match serde_json::from_str::<WebSocketMessage>(&text) {
-                            Ok(msg) => Ok(msg),
+                            Ok(msg) => {
+                                if let WebSocketMessage::SystemStatus { ref status, version: ref api_version, .. } = msg {
+                                    if !is_tested_version(api_version) {
+                                        log_warn!("WebSocket API version {} has not been tested with this client", api_version);
+                                    }
+                                    
+                                    *version.lock().unwrap() = Some(api_version.clone());
+                                    state.send_replace(ConnectionState::from_system_status(status));
+                                }
+                                
+                                record_subscription_status(&channels, &msg);
+                                notify_subscription_waiters(&subscription_waiters, &msg);
+                                
+                                match protocol_error(&msg) {
+                                    Some(e) => Err(e),
+                                    None => Ok(msg),
+                                }
+                            }
                             Err(e) => {
-                                eprintln!("Error parsing message: {}", e);
+                                log_debug!("Message is not a typed WebSocket message: {}", e);
                                 
                                 // Try to parse as array
                                 match serde_json::from_str::<Vec<Value>>(&text) {
                                     Ok(array) => Ok(WebSocketMessage::DataArray(array)),
                                     Err(e2) => {
-                                        eprintln!("Error parsing message as array: {}", e2);
+                                        log_debug!("Message is not a data array: {}", e2);
                                         
                                         // Return as generic value
                                         match serde_json::from_str::<Value>(&text) {
@@ -84,19 +337,19 @@ impl WebSocketApi {
                             }
                         };
                         
-                        if let Err(e) = message_tx.send(result).await {
-                            eprintln!("Error forwarding message to channel: {}", e);
+                        // The receiver was dropped, so nobody is listening anymore
+                        if message_tx.send(result).await.is_err() {
                             break;
                         }
                     }
                     Ok(Message::Binary(data)) => {
-                        eprintln!("Received binary message: {} bytes", data.len());
+                        log_debug!("Ignoring binary WebSocket message: {} bytes", data.len());
                     }
                     Ok(Message::Ping(data)) => {
                         // Automatically respond with a pong
                         let mut write_lock = write.lock().await;
                         if let Err(e) = write_lock.send(Message::Pong(data)).await {
-                            eprintln!("Error sending pong: {}", e);
+                            let _ = message_tx.send(Err(Error::WebSocket(format!("Failed to send pong: {}", e)))).await;
                             break;
                         }
                     }
@@ -104,25 +357,98 @@ impl WebSocketApi {
                         // Ignore pong messages
                     }
                     Ok(Message::Frame(frame)) => {
-                        eprintln!("Received frame message: {:?}", frame);
+                        log_debug!("Ignoring raw WebSocket frame: {:?}", frame);
                     }
                     Ok(Message::Close(frame)) => {
-                        eprintln!("WebSocket closed: {:?}", frame);
+                        log_debug!("WebSocket closed: {:?}", frame);
                         break;
                     }
                     Err(e) => {
-                        eprintln!("WebSocket error: {}", e);
+                        let _ = message_tx.send(Err(Error::WebSocket(format!("Connection error: {}", e)))).await;
                         break;
                     }
                 }
             }
-        });
+            
+            // Fail any requests still waiting for a reply
+            pending.lock().await.clear();
+            subscription_waiters.lock().unwrap().clear();
+            state.send_replace(ConnectionState::Disconnected);
+        }));
+        
+        // Ping periodically until the connection goes down
+        if let Some(heartbeat_interval) = self.heartbeat_interval {
+            let mut state = self.state.subscribe();
+            self.heartbeat = Some(tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(heartbeat_interval);
+                
+                // The first tick completes immediately
+                ticker.tick().await;
+                
+                loop {
+                    tokio::select! {
+                        _ = ticker.tick() => {
+                            if heartbeat_tx.send(Message::Ping(vec![])).await.is_err() {
+                                break;
+                            }
+                        }
+                        _ = async { state.wait_for(|state| *state == ConnectionState::Disconnected).await.map(|_| ()) } => break,
+                    }
+                }
+            }));
+        }
         
         Ok(message_rx)
     }
     
+    /// Get the state of the current connection
+    pub fn state(&self) -> ConnectionState {
+        *self.state.borrow()
+    }
+    
+    /// Get the API version reported in the `systemStatus` message of the current connection
+    ///
+    /// Returns `None` until the first `systemStatus` message has been received.
+    pub fn version(&self) -> Option<String> {
+        self.version.lock().unwrap().clone()
+    }
+    
+    /// Wait until Kraken reports the system as online
+    ///
+    /// Resolves once the initial `systemStatus` message with status `online` arrives,
+    /// so subscriptions sent afterwards don't race the connection setup. Fails if the
+    /// connection is closed first.
+    pub async fn wait_ready(&self) -> Result<()> {
+        let mut rx = self.state.subscribe();
+        
+        loop {
+            match *rx.borrow_and_update() {
+                ConnectionState::Online => return Ok(()),
+                ConnectionState::Disconnected => return Err(Error::WebSocket("Not connected to WebSocket".to_string())),
+                ConnectionState::Connecting | ConnectionState::Maintenance => {}
+            }
+            
+            rx.changed().await.map_err(|_| Error::WebSocket("Connection closed before it was ready".to_string()))?;
+        }
+    }
+    
     /// Subscribe to a channel
+    ///
+    /// Private channels (`ownTrades`, `openOrders`) require a token and a connection
+    /// made with [`WebSocketApi::connect_auth`], while public channels require a
+    /// connection made with [`WebSocketApi::connect`].
     pub async fn subscribe(&self, request: WebSocketSubscriptionRequest) -> Result<()> {
+        let private = request.subscription.name.is_private();
+        
+        if private && request.subscription.token.is_none() {
+            return Err(Error::WebSocket("Private channel subscription requires a token".to_string()));
+        }
+        
+        if self.tx.is_some() && private != self.authenticated {
+            let expected = if private { "authenticated" } else { "public" };
+            return Err(Error::WebSocket(format!("Subscription requires a connection to the {} WebSocket URL", expected)));
+        }
+        
         let message = serde_json::to_string(&request).map_err(|e| Error::WebSocket(format!("Failed to serialize subscription request: {}", e)))?;
         
         if let Some(tx) = &self.tx {
@@ -134,6 +460,53 @@ impl WebSocketApi {
         Ok(())
     }
     
+    /// Subscribe to a channel and wait for Kraken to confirm it
+    ///
+    /// Resolves to the `subscriptionStatus` message of each requested pair, matched
+    /// by pair and subscription name, in request order. Fails if Kraken reports an
+    /// error for any pair or if not every pair is confirmed within `timeout`.
+    pub async fn subscribe_confirmed(&self, request: WebSocketSubscriptionRequest, timeout: Duration) -> Result<Vec<WebSocketMessage>> {
+        let pairs = match request.pair {
+            Some(ref pairs) => pairs.iter().cloned().map(Some).collect(),
+            None => vec![None],
+        };
+        
+        let receivers: Vec<_> = {
+            let mut waiters = self.subscription_waiters.lock().unwrap();
+            
+            // Drop waiters whose caller has given up
+            waiters.retain(|waiter| !waiter.reply.is_closed());
+            
+            pairs.into_iter()
+                .map(|pair| {
+                    let (reply, rx) = oneshot::channel();
+                    waiters.push(SubscriptionWaiter { name: request.subscription.name, pair, reply });
+                    rx
+                })
+                .collect()
+        };
+        
+        self.subscribe(request).await?;
+        
+        let confirmations = future::try_join_all(receivers.into_iter().map(|rx| async {
+            rx.await.map_err(|_| Error::WebSocket("Connection closed before the subscription was confirmed".to_string()))
+        }));
+        
+        let statuses = tokio::time::timeout(timeout, confirmations)
+            .await
+            .map_err(|_| Error::WebSocket(format!("Subscription was not confirmed within {:?}", timeout)))??;
+        
+        for status in &statuses {
+            if let WebSocketMessage::SubscriptionStatus { status, error_message, .. } = status {
+                if status == "error" {
+                    return Err(Error::WebSocket(error_message.clone().unwrap_or_else(|| "Subscription failed".to_string())));
+                }
+            }
+        }
+        
+        Ok(statuses)
+    }
+    
     /// Unsubscribe from a channel
     pub async fn unsubscribe(&self, request: WebSocketUnsubscriptionRequest) -> Result<()> {
         let message = serde_json::to_string(&request).map_err(|e| Error::WebSocket(format!("Failed to serialize unsubscription request: {}", e)))?;
@@ -147,7 +520,114 @@ impl WebSocketApi {
         Ok(())
     }
     
-    /// Send a ping message
+    /// Place an order over the authenticated WebSocket
+    ///
+    /// Resolves once the matching `addOrderStatus` reply arrives. In dry-run mode the
+    /// order is always sent with `validate` set, so it is checked but never placed.
+    pub async fn add_order(&self, token: &str, order: &Order) -> Result<WebSocketAddOrderStatus> {
+        order.validate_local()?;
+        
+        let reqid = self.next_reqid.fetch_add(1, Ordering::Relaxed);
+        
+        let mut frame = add_order_fields(order);
+        frame.insert("event".to_string(), json!("addOrder"));
+        frame.insert("token".to_string(), json!(token));
+        frame.insert("reqid".to_string(), json!(reqid));
+        
+        if self.dry_run {
+            frame.insert("validate".to_string(), json!("true"));
+        }
+        
+        let reply = self.request(reqid, Value::Object(frame)).await?;
+        let status: WebSocketAddOrderStatus = serde_json::from_value(reply)?;
+        
+        if status.status == "error" {
+            return Err(Error::WebSocket(status.error_message.unwrap_or_else(|| "Failed to add order".to_string())));
+        }
+        
+        if self.dry_run {
+            log_warn!("Dry run: {} order for {} was validated but not submitted", order.ordertype, order.pair);
+        }
+        
+        Ok(status)
+    }
+    
+    /// Cancel an order over the authenticated WebSocket
+    ///
+    /// Resolves once the matching `cancelOrderStatus` reply arrives.
+    pub async fn cancel_order(&self, token: &str, txid: &str) -> Result<WebSocketCancelOrderStatus> {
+        let reqid = self.next_reqid.fetch_add(1, Ordering::Relaxed);
+        
+        let frame = json!({
+            "event": "cancelOrder",
+            "token": token,
+            "reqid": reqid,
+            "txid": [txid],
+        });
+        
+        let reply = self.request(reqid, frame).await?;
+        let status: WebSocketCancelOrderStatus = serde_json::from_value(reply)?;
+        
+        if status.status == "error" {
+            return Err(Error::WebSocket(status.error_message.unwrap_or_else(|| "Failed to cancel order".to_string())));
+        }
+        
+        Ok(status)
+    }
+    
+    /// Send a frame and wait for the reply carrying the same request ID
+    ///
+    /// Fails with [`Error::Timeout`] if no reply arrives within the
+    /// [`EndpointClass::WebSocketRequest`] timeout.
+    async fn request(&self, reqid: u64, frame: Value) -> Result<Value> {
+        let tx = self.tx.as_ref().ok_or_else(|| Error::WebSocket("Not connected to WebSocket".to_string()))?;
+        
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.pending.lock().await.insert(reqid, reply_tx);
+        
+        if let Err(e) = tx.send(Message::Text(frame.to_string())).await {
+            self.pending.lock().await.remove(&reqid);
+            return Err(Error::WebSocket(format!("Failed to send request: {}", e)));
+        }
+        
+        let reply = match tokio::time::timeout(self.request_timeout, reply_rx).await {
+            Ok(reply) => reply.map_err(|_| Error::WebSocket("Connection closed before a reply was received".to_string()))?,
+            Err(_) => {
+                self.pending.lock().await.remove(&reqid);
+                return Err(Error::Timeout(format!("No reply to request {} within {:?}", reqid, self.request_timeout)));
+            }
+        };
+        
+        if reply.get("event").and_then(|v| v.as_str()) == Some("error") {
+            let error_message = reply.get("errorMessage").and_then(|v| v.as_str()).unwrap_or("unknown error");
+            return Err(Error::WebSocket(format!("Kraken reported an error: {}", error_message)));
+        }
+        
+        Ok(reply)
+    }
+    
+    /// Unsubscribe from a channel by the channel ID received in its `subscriptionStatus` message
+    pub async fn unsubscribe_channel(&self, channel_id: u64) -> Result<()> {
+        let request = self.channels.lock().unwrap()
+            .get(&channel_id)
+            .map(WebSocketChannel::unsubscription_request)
+            .ok_or_else(|| Error::WebSocket(format!("Unknown channel ID {}", channel_id)))?;
+        
+        self.unsubscribe(request).await
+    }
+    
+    /// Find the ID of a subscribed channel by channel name (e.g. `ticker` or `book-10`) and pair
+    pub fn channel_id(&self, channel_name: &str, pair: &str) -> Option<u64> {
+        self.channels.lock().unwrap()
+            .iter()
+            .find(|(_, channel)| channel.channel_name.as_deref() == Some(channel_name) && channel.pair.as_deref() == Some(pair))
+            .map(|(channel_id, _)| *channel_id)
+    }
+    
+    /// Send a transport-level ping frame, e.g. to keep the connection alive
+    ///
+    /// Kraken answers with a pong frame, which isn't passed on as a message. Use
+    /// [`WebSocketApi::ping_with_reqid`] for a reply that can be matched.
     pub async fn ping(&self) -> Result<()> {
         if let Some(tx) = &self.tx {
             tx.send(Message::Ping(vec![])).await.map_err(|e| Error::WebSocket(format!("Failed to send ping: {}", e)))?;
@@ -158,6 +638,21 @@ impl WebSocketApi {
         Ok(())
     }
     
+    /// Send Kraken's application-level `ping` event
+    ///
+    /// Kraken replies with a [`WebSocketMessage::Pong`] carrying the same `reqid`,
+    /// which arrives on the message channel like any other message.
+    pub async fn ping_with_reqid(&self, reqid: u64) -> Result<()> {
+        let tx = self.tx.as_ref().ok_or_else(|| Error::WebSocket("Not connected to WebSocket".to_string()))?;
+        
+        let frame = json!({
+            "event": "ping",
+            "reqid": reqid,
+        });
+        
+        tx.send(Message::Text(frame.to_string())).await.map_err(|e| Error::WebSocket(format!("Failed to send ping: {}", e)))
+    }
+    
     /// Close the connection
     pub async fn close(&self) -> Result<()> {
         if let Some(tx) = &self.tx {
@@ -168,4 +663,645 @@ impl WebSocketApi {
         
         Ok(())
     }
+    
+    /// Close the connection and stop its background tasks
+    ///
+    /// Sends a close frame and waits for it to be written before stopping the
+    /// reader. Dropping the `WebSocketApi` also stops the tasks, but without the
+    /// close handshake.
+    pub async fn shutdown(&mut self) {
+        if let Some(heartbeat) = self.heartbeat.take() {
+            heartbeat.abort();
+        }
+        
+        // Dropping the sender ends the writer once the close frame is flushed
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(Message::Close(None)).await;
+        }
+        
+        if let Some(mut writer) = self.writer.take() {
+            if tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut writer).await.is_err() {
+                writer.abort();
+            }
+        }
+        
+        if let Some(reader) = self.reader.take() {
+            reader.abort();
+        }
+        
+        self.pending.lock().await.clear();
+        self.state.send_replace(ConnectionState::Disconnected);
+    }
+    
+    /// Abort the tasks of the current connection, dropping the socket
+    fn abort_tasks(&mut self) {
+        for task in [self.writer.take(), self.reader.take(), self.heartbeat.take()].into_iter().flatten() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for WebSocketApi {
+    fn drop(&mut self) {
+        self.abort_tasks();
+    }
+}
+
+/// Wrap a message receiver in a [`Stream`] that ends when the connection closes
+fn receiver_stream(rx: mpsc::Receiver<Result<WebSocketMessage>>) -> impl Stream<Item = Result<WebSocketMessage>> {
+    stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|message| (message, rx))
+    })
+}
+
+/// Track channels as Kraken confirms subscriptions and unsubscriptions
+fn record_subscription_status(channels: &Channels, message: &WebSocketMessage) {
+    if let WebSocketMessage::SubscriptionStatus { channel_id: Some(channel_id), channel_name, pair, status, subscription, .. } = message {
+        let mut channels = channels.lock().unwrap();
+        
+        match status.as_str() {
+            "subscribed" => {
+                channels.insert(*channel_id, WebSocketChannel {
+                    channel_name: channel_name.clone(),
+                    pair: pair.clone(),
+                    subscription: subscription.clone(),
+                });
+            }
+            "unsubscribed" => {
+                channels.remove(channel_id);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Hand a `subscribed` or `error` status to the first subscription waiting for it
+fn notify_subscription_waiters(waiters: &SubscriptionWaiters, message: &WebSocketMessage) {
+    if let WebSocketMessage::SubscriptionStatus { pair, status, subscription, .. } = message {
+        if status != "subscribed" && status != "error" {
+            return;
+        }
+        
+        let mut waiters = waiters.lock().unwrap();
+        let index = waiters.iter().position(|waiter| {
+            waiter.name == subscription.name && waiter.pair == *pair && !waiter.reply.is_closed()
+        });
+        
+        if let Some(index) = index {
+            let _ = waiters.remove(index).reply.send(message.clone());
+        }
+    }
+}
+
+/// Turn an `error` event or a failed subscription status from Kraken into an error
+fn protocol_error(message: &WebSocketMessage) -> Option<Error> {
+    let (error_message, subscription, pair) = match message {
+        WebSocketMessage::Error { error_message, subscription, pair, .. } => (Some(error_message), subscription.as_ref(), pair),
+        WebSocketMessage::SubscriptionStatus { status, error_message, subscription, pair, .. } if status == "error" => (error_message.as_ref(), Some(subscription), pair),
+        _ => return None,
+    };
+    
+    let error_message = error_message.map(String::as_str).unwrap_or("unknown error");
+    
+    let message = match (subscription, pair) {
+        (Some(subscription), Some(pair)) => format!("Subscription to {} for {} failed: {}", subscription_name(subscription), pair, error_message),
+        (Some(subscription), None) => format!("Subscription to {} failed: {}", subscription_name(subscription), error_message),
+        (None, _) => format!("Kraken reported an error: {}", error_message),
+    };
+    
+    Some(Error::WebSocket(message))
+}
+
+/// Get the channel name of a subscription as Kraken spells it
+fn subscription_name(subscription: &WebSocketSubscription) -> String {
+    serde_json::to_value(subscription.name)
+        .ok()
+        .and_then(|name| name.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// Extract the request ID of a reply to a request, such as an `addOrderStatus` or `error` event
+///
+/// Pongs and subscription statuses also carry a `reqid` but have their own consumers.
+fn parse_reply(text: &str) -> Option<(u64, Value)> {
+    let value: Value = serde_json::from_str(text).ok()?;
+    
+    match value.get("event").and_then(|v| v.as_str()) {
+        Some("pong") | Some("subscriptionStatus") | None => None,
+        Some(_) => {
+            let reqid = value.get("reqid").and_then(|v| v.as_u64())?;
+            Some((reqid, value))
+        }
+    }
+}
+
+/// Build the order fields of an `addOrder` frame
+fn add_order_fields(order: &Order) -> Map<String, Value> {
+    let mut fields = Map::new();
+    
+    fields.insert("pair".to_string(), json!(order.pair));
+    fields.insert("type".to_string(), json!(order.type_.to_string()));
+    fields.insert("ordertype".to_string(), json!(order.ordertype.to_string()));
+    fields.insert("volume".to_string(), json!(order.volume));
+    
+    if let Some(ref price) = order.price {
+        fields.insert("price".to_string(), json!(price));
+    }
+    
+    if let Some(ref price2) = order.price2 {
+        fields.insert("price2".to_string(), json!(price2));
+    }
+    
+    if let Some(ref leverage) = order.leverage {
+        fields.insert("leverage".to_string(), json!(leverage));
+    }
+    
+    if let Some(ref oflags) = order.oflags {
+        fields.insert("oflags".to_string(), json!(oflags));
+    }
+    
+    if let Some(ref starttm) = order.starttm {
+        fields.insert("starttm".to_string(), json!(starttm));
+    }
+    
+    if let Some(ref expiretm) = order.expiretm {
+        fields.insert("expiretm".to_string(), json!(expiretm));
+    }
+    
+    if let Some(ref deadline) = order.deadline {
+        fields.insert("deadline".to_string(), json!(deadline));
+    }
+    
+    if let Some(reduce_only) = order.reduce_only {
+        fields.insert("reduce_only".to_string(), json!(reduce_only));
+    }
+    
+    if let Some(trigger) = order.trigger {
+        fields.insert("trigger".to_string(), json!(trigger.to_string()));
+    }
+    
+    if let Some(userref) = order.userref {
+        fields.insert("userref".to_string(), json!(userref.to_string()));
+    }
+    
+    if let Some(ref cl_ord_id) = order.cl_ord_id {
+        fields.insert("cl_ord_id".to_string(), json!(cl_ord_id));
+    }
+    
+    if let Some(validate) = order.validate {
+        fields.insert("validate".to_string(), json!(validate.to_string()));
+    }
+    
+    if let Some(ref close_ordertype) = order.close_ordertype {
+        fields.insert("close[ordertype]".to_string(), json!(close_ordertype.to_string()));
+    }
+    
+    if let Some(ref close_price) = order.close_price {
+        fields.insert("close[price]".to_string(), json!(close_price));
+    }
+    
+    if let Some(ref close_price2) = order.close_price2 {
+        fields.insert("close[price2]".to_string(), json!(close_price2));
+    }
+    
+    fields
+}
+
+/// Pick the proxy to tunnel a WebSocket connection through, or `None` to connect directly
+///
+/// Only `http://` proxies can be tunnelled through; other schemes fall back to a direct
+/// connection with a warning. Hosts listed in `NO_PROXY` are also connected to directly.
+fn tunnel_proxy(proxy: &str, url: &Url) -> Result<Option<Url>> {
+    let proxy = Url::parse(proxy).map_err(|e| Error::WebSocket(format!("Invalid proxy URL: {}", e)))?;
+    
+    if proxy.scheme() != "http" {
+        log_warn!("WebSocket connections cannot be tunnelled through a {} proxy, connecting directly", proxy.scheme());
+        return Ok(None);
+    }
+    
+    let no_proxy = std::env::var("NO_PROXY").or_else(|_| std::env::var("no_proxy")).unwrap_or_default();
+    if url.host_str().is_some_and(|host| bypasses_proxy(host, &no_proxy)) {
+        return Ok(None);
+    }
+    
+    Ok(Some(proxy))
+}
+
+/// Whether `host` matches an entry of a `NO_PROXY` list
+///
+/// Entries are comma-separated domains, matching the domain and its subdomains, or `*` for every host.
+fn bypasses_proxy(host: &str, no_proxy: &str) -> bool {
+    no_proxy.split(',').map(str::trim).filter(|entry| !entry.is_empty()).any(|entry| {
+        let domain = entry.trim_start_matches('.');
+        entry == "*" || host.eq_ignore_ascii_case(domain) || host.to_ascii_lowercase().ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+    })
+}
+
+/// Connect to a WebSocket URL through an HTTP proxy, using a `CONNECT` tunnel
+///
+/// Credentials in the proxy URL are sent as basic `Proxy-Authorization`.
+async fn connect_via_proxy(proxy: Url, url: Url) -> Result<(WebSocketStream<MaybeTlsStream<TcpStream>>, Response)> {
+    let proxy_host = proxy.host_str().ok_or_else(|| Error::WebSocket("Proxy URL has no host".to_string()))?;
+    let proxy_port = proxy.port_or_known_default().unwrap_or(80);
+    let host = url.host_str().ok_or_else(|| Error::WebSocket("WebSocket URL has no host".to_string()))?;
+    let port = url.port_or_known_default().ok_or_else(|| Error::WebSocket("WebSocket URL has no port".to_string()))?;
+    let target = format!("{}:{}", host, port);
+    
+    let mut stream = TcpStream::connect((proxy_host, proxy_port)).await
+        .map_err(|e| Error::WebSocket(format!("Failed to connect to proxy: {}", e)))?;
+    
+    let mut request = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", target, target);
+    if !proxy.username().is_empty() {
+        let username = percent_decode_str(proxy.username()).decode_utf8_lossy();
+        let password = percent_decode_str(proxy.password().unwrap_or_default()).decode_utf8_lossy();
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", BASE64.encode(format!("{}:{}", username, password))));
+    }
+    request.push_str("\r\n");
+    
+    stream.write_all(request.as_bytes()).await
+        .map_err(|e| Error::WebSocket(format!("Failed to send proxy request: {}", e)))?;
+    
+    // Read the response header a byte at a time so nothing sent through the tunnel is consumed
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_PROXY_RESPONSE_BYTES {
+            return Err(Error::WebSocket("Proxy response header is too large".to_string()));
+        }
+        
+        let byte = stream.read_u8().await
+            .map_err(|e| Error::WebSocket(format!("Failed to read proxy response: {}", e)))?;
+        response.push(byte);
+    }
+    
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        return Err(Error::WebSocket(format!("Proxy refused the tunnel: {}", status_line)));
+    }
+    
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    let connected = tokio_tungstenite::client_async_tls(url, stream).await;
+    #[cfg(not(any(feature = "native-tls", feature = "rustls-tls")))]
+    let connected = tokio_tungstenite::client_async(url, MaybeTlsStream::Plain(stream)).await;
+    
+    connected.map_err(|e| Error::WebSocket(format!("Connection error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_is_tested_version() {
+        assert!(is_tested_version("1.9.0"));
+        assert!(is_tested_version("1"));
+        assert!(!is_tested_version("2.0.0"));
+        assert!(!is_tested_version("10.0"));
+    }
+    
+    #[test]
+    fn test_record_subscription_status() {
+        let channels: Channels = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let status = |status: &str| serde_json::from_value::<WebSocketMessage>(json!({
+            "channelID": 10001,
+            "channelName": "book-10",
+            "event": "subscriptionStatus",
+            "pair": "XBT/EUR",
+            "status": status,
+            "subscription": { "depth": 10, "name": "book" }
+        })).unwrap();
+        
+        record_subscription_status(&channels, &status("subscribed"));
+        
+        let request = channels.lock().unwrap()[&10001].unsubscription_request();
+        assert_eq!(serde_json::to_value(&request).unwrap(), json!({
+            "event": "unsubscribe",
+            "subscription": { "depth": 10, "name": "book" },
+            "pair": ["XBT/EUR"]
+        }));
+        
+        record_subscription_status(&channels, &status("unsubscribed"));
+        assert!(channels.lock().unwrap().is_empty());
+    }
+    
+    /// Accept one WebSocket connection and report when the client goes away
+    async fn spawn_server() -> (String, oneshot::Receiver<Option<Message>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (closed_tx, closed_rx) = oneshot::channel();
+        
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            
+            // Report the last frame seen before the connection ended
+            let mut last = None;
+            while let Some(Ok(message)) = ws.next().await {
+                last = Some(message);
+            }
+            let _ = closed_tx.send(last);
+        });
+        
+        (url, closed_rx)
+    }
+    
+    fn websocket_api(url: &str) -> WebSocketApi {
+        let client = KrakenClient::new(crate::config::Config::new().with_ws_url(url)).unwrap();
+        WebSocketApi::new(&client)
+    }
+    
+    #[tokio::test]
+    async fn test_drop_closes_connection() {
+        let (url, closed) = spawn_server().await;
+        let mut ws = websocket_api(&url);
+        let _messages = ws.connect().await.unwrap();
+        
+        drop(ws);
+        
+        tokio::time::timeout(Duration::from_secs(5), closed).await.unwrap().unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_shutdown_sends_close_frame() {
+        let (url, closed) = spawn_server().await;
+        let mut ws = websocket_api(&url);
+        let _messages = ws.connect().await.unwrap();
+        
+        ws.shutdown().await;
+        
+        let last = tokio::time::timeout(Duration::from_secs(5), closed).await.unwrap().unwrap();
+        assert!(matches!(last, Some(Message::Close(_))));
+        assert_eq!(ws.state(), ConnectionState::Disconnected);
+    }
+    
+    #[tokio::test]
+    async fn test_liveness_timeout_reports_stalled_connection() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        
+        // Complete the handshake and stall, then report whether the client closed the socket
+        let (closed_tx, closed) = oneshot::channel();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            
+            while let Some(Ok(message)) = ws.next().await {
+                if message.is_close() {
+                    break;
+                }
+            }
+            let _ = closed_tx.send(());
+        });
+        
+        let mut ws = websocket_api(&url)
+            .with_heartbeat_interval(Duration::from_millis(50))
+            .with_liveness_timeout(Duration::from_millis(200));
+        let mut messages = ws.connect().await.unwrap();
+        
+        let message = tokio::time::timeout(Duration::from_secs(5), messages.recv()).await.unwrap();
+        assert!(matches!(message, Some(Err(Error::WebSocket(_)))));
+        assert!(messages.recv().await.is_none());
+        assert_eq!(ws.state(), ConnectionState::Disconnected);
+        
+        tokio::time::timeout(Duration::from_secs(5), closed).await.unwrap().unwrap();
+        assert!(ws.writer.as_ref().unwrap().is_finished());
+    }
+    
+    #[tokio::test]
+    async fn test_heartbeat_keeps_connection_alive() {
+        let (url, _closed) = spawn_server().await;
+        
+        let mut ws = websocket_api(&url)
+            .with_heartbeat_interval(Duration::from_millis(50))
+            .with_liveness_timeout(Duration::from_millis(300));
+        let mut messages = ws.connect().await.unwrap();
+        
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        
+        assert!(messages.try_recv().is_err());
+        assert_ne!(ws.state(), ConnectionState::Disconnected);
+    }
+    
+    #[tokio::test]
+    async fn test_subscribe_confirmed() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        
+        // Confirm XBT/USD and reject any other pair
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                let request: Value = serde_json::from_str(&text).unwrap();
+                
+                for pair in request["pair"].as_array().unwrap() {
+                    let status = if pair == "XBT/USD" {
+                        json!({"channelID": 42, "channelName": "ticker", "event": "subscriptionStatus", "pair": pair, "status": "subscribed", "subscription": {"name": "ticker"}})
+                    } else {
+                        json!({"errorMessage": "Currency pair not supported", "event": "subscriptionStatus", "pair": pair, "status": "error", "subscription": {"name": "ticker"}})
+                    };
+                    ws.send(Message::Text(status.to_string())).await.unwrap();
+                }
+            }
+        });
+        
+        let mut ws = websocket_api(&url);
+        let _messages = ws.connect().await.unwrap();
+        let ticker = |pair: &str| WebSocketSubscriptionRequest::new_with_type(WebSocketSubscriptionType::Ticker).add_pair(pair);
+        
+        let statuses = ws.subscribe_confirmed(ticker("XBT/USD"), Duration::from_secs(5)).await.unwrap();
+        assert!(matches!(statuses[..], [WebSocketMessage::SubscriptionStatus { channel_id: Some(42), .. }]));
+        
+        let error = ws.subscribe_confirmed(ticker("ABC/USD"), Duration::from_secs(5)).await.unwrap_err();
+        assert!(error.to_string().contains("Currency pair not supported"));
+    }
+    
+    #[tokio::test]
+    async fn test_connect_timeout() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        
+        // Accept the TCP connection but never answer the handshake
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+        
+        let config = crate::config::Config::new().with_ws_url(&url).with_ws_connect_timeout(Duration::from_millis(100));
+        let mut ws = WebSocketApi::new(&KrakenClient::new(config).unwrap());
+        
+        let error = ws.connect().await.unwrap_err();
+        assert!(matches!(error, Error::Timeout(_)));
+        assert!(error.is_retryable());
+        assert_eq!(ws.state(), ConnectionState::Disconnected);
+    }
+    
+    #[tokio::test]
+    async fn test_ping_with_reqid() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        
+        // Answer each ping event with a pong carrying its request ID
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                let request: Value = serde_json::from_str(&text).unwrap();
+                assert_eq!(request["event"], "ping");
+                
+                let pong = json!({"event": "pong", "reqid": request["reqid"]});
+                ws.send(Message::Text(pong.to_string())).await.unwrap();
+            }
+        });
+        
+        let mut ws = websocket_api(&url);
+        let mut messages = ws.connect().await.unwrap();
+        
+        ws.ping_with_reqid(42).await.unwrap();
+        
+        let message = tokio::time::timeout(Duration::from_secs(5), messages.recv()).await.unwrap().unwrap().unwrap();
+        assert!(matches!(message, WebSocketMessage::Pong { req_id: Some(42), .. }));
+    }
+    
+    #[tokio::test]
+    async fn test_connect_through_proxy() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = format!("http://user:p%40ss@{}", listener.local_addr().unwrap());
+        
+        // Accept the tunnel, then serve the WebSocket over it
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(socket.read_u8().await.unwrap());
+            }
+            let request = String::from_utf8(request).unwrap();
+            assert!(request.starts_with("CONNECT ws.kraken.test:80 HTTP/1.1\r\n"));
+            assert!(request.contains(&format!("Proxy-Authorization: Basic {}\r\n", BASE64.encode("user:p@ss"))));
+            
+            socket.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").await.unwrap();
+            
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            ws.send(Message::Text(json!({"event": "heartbeat"}).to_string())).await.unwrap();
+            while ws.next().await.is_some() {}
+        });
+        
+        let config = crate::config::Config::new()
+            .with_ws_url("ws://ws.kraken.test")
+            .with_proxy(proxy)
+            .with_websocket_proxy(true);
+        let mut ws = WebSocketApi::new(&KrakenClient::new(config).unwrap());
+        let mut messages = ws.connect().await.unwrap();
+        
+        let message = tokio::time::timeout(Duration::from_secs(5), messages.recv()).await.unwrap().unwrap().unwrap();
+        assert!(matches!(message, WebSocketMessage::Heartbeat { .. }));
+    }
+    
+    #[test]
+    fn test_proxy_bypass() {
+        assert!(bypasses_proxy("ws.kraken.com", "localhost, .kraken.com"));
+        assert!(bypasses_proxy("kraken.com", "kraken.com"));
+        assert!(bypasses_proxy("ws.kraken.com", "*"));
+        assert!(!bypasses_proxy("ws.kraken.com", "localhost,example.com"));
+        assert!(!bypasses_proxy("notkraken.com", "kraken.com"));
+        assert!(!bypasses_proxy("ws.kraken.com", ""));
+        
+        let url = Url::parse("wss://ws.kraken.com").unwrap();
+        assert_eq!(tunnel_proxy("socks5://127.0.0.1:1080", &url).unwrap(), None);
+        assert_eq!(tunnel_proxy("https://127.0.0.1:8443", &url).unwrap(), None);
+        assert!(tunnel_proxy("not a url", &url).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_request_reply_and_timeout() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        
+        // Reject the first cancel with an error event and never answer the second
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            
+            let text = ws.next().await.unwrap().unwrap().into_text().unwrap();
+            let request: Value = serde_json::from_str(&text).unwrap();
+            let reply = json!({"errorMessage": "EGeneral:Invalid arguments", "event": "error", "reqid": request["reqid"], "status": "error"});
+            ws.send(Message::Text(reply.to_string())).await.unwrap();
+            
+            while ws.next().await.is_some() {}
+        });
+        
+        let config = crate::config::Config::new()
+            .with_ws_url(&url)
+            .with_request_timeout(EndpointClass::WebSocketRequest, Duration::from_millis(200));
+        let mut ws = WebSocketApi::new(&KrakenClient::new(config).unwrap());
+        let _messages = ws.connect().await.unwrap();
+        
+        let error = ws.cancel_order("token", "OHYO67-6LP66-HMQ437").await.unwrap_err();
+        assert!(error.to_string().contains("EGeneral:Invalid arguments"));
+        
+        let error = ws.cancel_order("token", "OHYO67-6LP66-HMQ437").await.unwrap_err();
+        assert!(matches!(error, Error::Timeout(_)));
+        assert!(ws.pending.lock().await.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_dry_run_validates_add_order() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        
+        // Only accept orders that are validate-only
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                let request: Value = serde_json::from_str(&text).unwrap();
+                let status = if request["validate"] == "true" { "ok" } else { "error" };
+                
+                let reply = json!({"event": "addOrderStatus", "reqid": request["reqid"], "status": status, "descr": "buy 0.01 XBTUSD @ limit 30000.0"});
+                ws.send(Message::Text(reply.to_string())).await.unwrap();
+            }
+        });
+        
+        let config = crate::config::Config::new().with_ws_url(&url).with_dry_run(true);
+        let mut ws = WebSocketApi::new(&KrakenClient::new(config).unwrap());
+        let _messages = ws.connect().await.unwrap();
+        
+        let order = Order::new("XBTUSD", crate::models::trading::OrderSide::Buy, crate::models::trading::OrderType::Limit, "0.01").with_price("30000.0");
+        let status = tokio::time::timeout(Duration::from_secs(5), ws.add_order("token", &order)).await.unwrap().unwrap();
+        assert_eq!(status.status, "ok");
+    }
+    
+    #[test]
+    fn test_add_order_fields_deadline() {
+        let order = Order::new("XBTUSD", crate::models::trading::OrderSide::Buy, crate::models::trading::OrderType::Limit, "0.01")
+            .with_price("30000.0")
+            .with_deadline("2023-07-06T18:50:48.000Z");
+        
+        let fields = add_order_fields(&order);
+        assert_eq!(fields["deadline"], "2023-07-06T18:50:48.000Z");
+        assert_eq!(fields["price"], "30000.0");
+        
+        let order = Order::new("XBTUSD", crate::models::trading::OrderSide::Buy, crate::models::trading::OrderType::Market, "0.01");
+        assert!(!add_order_fields(&order).contains_key("deadline"));
+    }
+    
+    #[test]
+    fn test_protocol_error() {
+        let message = |value: Value| serde_json::from_value::<WebSocketMessage>(value).unwrap();
+        
+        let failed = message(json!({"errorMessage": "Currency pair not supported", "event": "subscriptionStatus", "pair": "ABC/USD", "status": "error", "subscription": {"name": "ticker"}}));
+        assert_eq!(protocol_error(&failed).unwrap().to_string(), "WebSocket error: Subscription to ticker for ABC/USD failed: Currency pair not supported");
+        
+        let malformed = message(json!({"errorMessage": "Malformed request", "event": "error", "status": "error"}));
+        assert!(matches!(malformed, WebSocketMessage::Error { .. }));
+        assert_eq!(protocol_error(&malformed).unwrap().to_string(), "WebSocket error: Kraken reported an error: Malformed request");
+        
+        let subscribed = message(json!({"channelID": 42, "channelName": "ticker", "event": "subscriptionStatus", "pair": "XBT/USD", "status": "subscribed", "subscription": {"name": "ticker"}}));
+        assert!(protocol_error(&subscribed).is_none());
+    }
 }
@@ -3,6 +3,7 @@
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use hmac::{Hmac, Mac};
 use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::error::{Error, Result};
@@ -15,14 +16,13 @@ pub fn generate_nonce() -> u64 {
         .as_millis() as u64
 }
 
-/// Sign a message with the API secret
-pub fn sign_message(path: &str, nonce: u64, postdata: &str, api_secret: &str) -> Result<String> {
-    // Decode the API secret
-    let secret = match BASE64.decode(api_secret) {
-        Ok(s) => s,
-        Err(_) => return Err(Error::Auth("Invalid API secret".to_string())),
-    };
+/// Decode a base64 encoded API secret
+pub fn decode_api_secret(api_secret: &str) -> Result<Vec<u8>> {
+    BASE64.decode(api_secret).map_err(|_| Error::Auth("Invalid API secret".to_string()))
+}
 
+/// Sign a message with the decoded API secret
+pub fn sign_message(path: &str, nonce: u64, postdata: &str, secret: &[u8]) -> Result<String> {
     // Create the message to sign
     // Format: nonce + postdata
     let message = format!("{}{}", nonce, postdata);
@@ -38,7 +38,7 @@ pub fn sign_message(path: &str, nonce: u64, postdata: &str, api_secret: &str) ->
     mac_data.extend_from_slice(&message_hash);
 
     // Create the HMAC-SHA512 signature
-    let mut mac = Hmac::<Sha512>::new_from_slice(&secret)
+    let mut mac = Hmac::<Sha512>::new_from_slice(secret)
         .map_err(|_| Error::Auth("Failed to create HMAC".to_string()))?;
     mac.update(&mac_data);
     let signature = mac.finalize().into_bytes();
@@ -47,6 +47,42 @@ pub fn sign_message(path: &str, nonce: u64, postdata: &str, api_secret: &str) ->
     Ok(BASE64.encode(signature))
 }
 
+/// Signs private API requests
+///
+/// The default [`HmacSigner`] signs with the API secret. Implement this to sign with
+/// a key held elsewhere, such as an HSM or a remote signing service, and install it
+/// with [`crate::Config::with_signer`].
+pub trait Signer: fmt::Debug + Send + Sync {
+    /// Sign a request, returning the value of the `API-Sign` header
+    fn sign(&self, path: &str, nonce: u64, postdata: &str) -> Result<String>;
+}
+
+/// Signs requests with HMAC-SHA512 over the decoded API secret
+#[derive(Clone)]
+pub struct HmacSigner {
+    /// Decoded API secret
+    secret: Vec<u8>,
+}
+
+impl HmacSigner {
+    /// Create a signer from a base64 encoded API secret
+    pub fn new(api_secret: &str) -> Result<Self> {
+        Ok(Self { secret: decode_api_secret(api_secret)? })
+    }
+}
+
+impl fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HmacSigner").finish_non_exhaustive()
+    }
+}
+
+impl Signer for HmacSigner {
+    fn sign(&self, path: &str, nonce: u64, postdata: &str) -> Result<String> {
+        sign_message(path, nonce, postdata, &self.secret)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -67,8 +103,25 @@ mod tests {
         let nonce = 1614232229325;
         let postdata = "nonce=1614232229325";
         let api_secret = "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==";
+        let secret = decode_api_secret(api_secret).unwrap();
         
-        let signature = sign_message(path, nonce, postdata, api_secret);
+        let signature = sign_message(path, nonce, postdata, &secret);
         assert!(signature.is_ok());
     }
+    
+    #[test]
+    fn test_hmac_signer() {
+        let signer = HmacSigner::new("c2VjcmV0").unwrap();
+        let signature = signer.sign("/0/private/Balance", 1, "nonce=1").unwrap();
+        assert_eq!(signature, sign_message("/0/private/Balance", 1, "nonce=1", b"secret").unwrap());
+        
+        assert!(!format!("{:?}", signer).contains("secret"));
+        assert!(matches!(HmacSigner::new("not base64!"), Err(Error::Auth(_))));
+    }
+    
+    #[test]
+    fn test_decode_api_secret() {
+        assert!(decode_api_secret("c2VjcmV0").is_ok());
+        assert!(matches!(decode_api_secret("not base64!"), Err(Error::Auth(_))));
+    }
 }
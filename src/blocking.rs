@@ -0,0 +1,140 @@
+//! Blocking client for the Kraken API
+//!
+//! Wraps the async [`crate::KrakenClient`] and drives each request to completion on
+//! a private current-thread runtime, so it can be used without an async runtime.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::client::KrakenClient as AsyncKrakenClient;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::models::account::{AddExportResponse, Balance, BalanceEx, ClosedOrders, ExportFormat, ExportReport, ExportStatus, Ledger, OpenOrders, OpenOrdersWithTrades, OpenPositions, Page, PagedResult, RemoveExportResponse, RemoveExportType, StakeableAsset, StakingResponse, StakingTransaction, TradeBalance, TradeHistory, Wallet, WalletTransferResponse};
+use crate::models::market::{AssetInfo, AssetPair, AssetPairInfo, Interval, OHLC, Orderbook, Paginated, ServerTime, Spread, SystemStatus, Ticker, Trade, TradeCursor};
+use crate::models::trading::{AmendOrderResponse, BatchOrder, CancelResult, CancelTarget, Order, OrderAmend, OrderInfo, OrderResponse, TradeInfo};
+use crate::models::websocket::WebSocketToken;
+
+/// Generate blocking wrappers for async API methods
+macro_rules! blocking_methods {
+    ($api:ident; $($(#[$meta:meta])* fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            $(#[$meta])*
+            pub fn $name(&self, $($arg: $ty),*) -> Result<$ret> {
+                self.client.block_on(self.client.inner.$api().$name($($arg),*))
+            }
+        )*
+    };
+}
+
+/// Blocking Kraken API client
+#[derive(Debug, Clone)]
+pub struct KrakenClient {
+    /// Async client the requests are made with
+    inner: AsyncKrakenClient,
+    
+    /// Runtime the requests are driven on
+    runtime: Arc<Runtime>,
+}
+
+impl KrakenClient {
+    /// Create a new blocking Kraken API client with the given configuration
+    pub fn new(config: Config) -> Result<Self> {
+        Self::from_async(AsyncKrakenClient::new(config)?)
+    }
+    
+    /// Create a new blocking Kraken API client with default configuration
+    #[allow(clippy::should_implement_trait)]
+    pub fn default() -> Result<Self> {
+        Self::new(Config::default())
+    }
+    
+    /// Create a blocking client from an async client
+    pub fn from_async(inner: AsyncKrakenClient) -> Result<Self> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| Error::Other(format!("Failed to create runtime: {}", e)))?;
+        
+        Ok(Self { inner, runtime: Arc::new(runtime) })
+    }
+    
+    /// Get the underlying async client
+    pub fn inner(&self) -> &AsyncKrakenClient {
+        &self.inner
+    }
+    
+    /// Measure the offset between the local clock and Kraken's server time
+    ///
+    /// See [`AsyncKrakenClient::sync_time`].
+    pub fn sync_time(&self) -> Result<chrono::Duration> {
+        self.block_on(self.inner.sync_time())
+    }
+    
+    /// Get the public API
+    pub fn public(&self) -> PublicApi<'_> {
+        PublicApi { client: self }
+    }
+    
+    /// Get the private API
+    pub fn private(&self) -> PrivateApi<'_> {
+        PrivateApi { client: self }
+    }
+    
+    /// Run a request to completion on the private runtime
+    ///
+    /// Blocking inside an async runtime would panic, so that case is reported as an error.
+    fn block_on<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        if Handle::try_current().is_ok() {
+            return Err(Error::Other("The blocking client can't be used from within an async runtime".to_string()));
+        }
+        
+        self.runtime.block_on(future)
+    }
+}
+
+/// Blocking public API endpoints
+pub struct PublicApi<'a> {
+    /// Reference to the blocking client
+    client: &'a KrakenClient,
+}
+
+impl<'a> PublicApi<'a> {
+    crate::api::public::public_methods!(blocking_methods! { public; });
+}
+
+/// Blocking private API endpoints
+pub struct PrivateApi<'a> {
+    /// Reference to the blocking client
+    client: &'a KrakenClient,
+}
+
+impl<'a> PrivateApi<'a> {
+    crate::api::private::private_methods!(blocking_methods! { private; });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock::MockTransport;
+    
+    const SERVER_TIME: &str = r#"{"error":[],"result":{"unixtime":1688669448,"rfc1123":"Thu, 06 Jul 23 18:50:48 +0000"}}"#;
+    
+    #[test]
+    fn test_blocking_request() {
+        let client = KrakenClient::from_async(AsyncKrakenClient::with_transport(Config::new(), MockTransport::json(SERVER_TIME)).unwrap()).unwrap();
+        
+        assert_eq!(client.public().get_server_time().unwrap().unixtime, 1688669448);
+    }
+    
+    #[test]
+    fn test_blocking_inside_runtime_is_an_error() {
+        let client = KrakenClient::from_async(AsyncKrakenClient::with_transport(Config::new(), MockTransport::json(SERVER_TIME)).unwrap()).unwrap();
+        
+        let runtime = Runtime::new().unwrap();
+        let result = runtime.block_on(async { client.public().get_server_time() });
+        
+        assert!(matches!(result, Err(Error::Other(_))));
+    }
+}
@@ -1,11 +1,22 @@
 //! Kraken API client implementation
 
-use reqwest::{Client as HttpClient, ClientBuilder};
+use chrono::{DateTime, Duration as TimeDelta, Utc};
+use reqwest::{Client as HttpClient, ClientBuilder, Proxy};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
 
-use crate::api::{public::PublicApi, private::PrivateApi, websocket::WebSocketApi, rate_limiter::RateLimiter};
+use crate::api::{public::PublicApi, private::PrivateApi, websocket::WebSocketApi, metadata::MetadataCache, rate_limiter::{OrderRateLimiter, PrivateRateLimiter, RateLimiter}};
+use crate::auth::{HmacSigner, Signer};
 use crate::config::Config;
-use crate::error::Result;
+use crate::error::{Error, Result};
+#[cfg(feature = "metrics")]
+use crate::metrics::{Metrics, NoopMetrics, RequestOutcome};
+use crate::models::market::{AssetInfo, AssetPair};
+use crate::transport::{response_too_large, Transport, TransportRequest, TransportResponse};
+use crate::utils::PairLookup;
 
 /// Kraken API client
 #[derive(Debug, Clone)]
@@ -13,36 +24,161 @@ pub struct KrakenClient {
     /// Client configuration
     pub config: Config,
     
-    /// HTTP client
-    http_client: HttpClient,
+    /// Transport used to send REST requests
+    transport: Arc<dyn Transport>,
+    
+    /// HTTP client behind `transport`, unless a custom transport was given
+    http_client: Option<HttpClient>,
     
     /// Rate limiter
     rate_limiter: RateLimiter,
+    
+    /// Order rate limiter
+    order_rate_limiter: OrderRateLimiter,
+    
+    /// Rate limiter shared by all private endpoints
+    private_rate_limiter: PrivateRateLimiter,
+    
+    /// Signer for private requests
+    signer: Option<Arc<dyn Signer>>,
+    
+    /// Offset of the server clock from the local clock in milliseconds
+    time_offset: Arc<AtomicI64>,
+    
+    /// Last nonce handed out, so nonces keep increasing when the offset changes
+    last_nonce: Arc<AtomicU64>,
+    
+    /// Cached asset and asset pair metadata
+    metadata: MetadataCache,
+    
+    /// Hook invoked around each REST request
+    #[cfg(feature = "metrics")]
+    metrics: Arc<dyn Metrics>,
 }
 
 impl KrakenClient {
     /// Create a new Kraken API client with the given configuration
+    ///
+    /// Returns an error if an API secret is set but is not valid base64.
     pub fn new(config: Config) -> Result<Self> {
-        let http_client = ClientBuilder::new()
+        let mut builder = ClientBuilder::new()
             .timeout(Duration::from_secs(config.timeout))
-            .user_agent(&config.user_agent)
-            .build()?;
+            .user_agent(&config.user_agent);
+        
+        if let Some(ref proxy) = config.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        
+        if let Some(pool_idle_timeout) = config.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(Duration::from_secs(pool_idle_timeout));
+        }
+        
+        // reqwest sends the matching `Accept-Encoding` header and decompresses the response
+        #[cfg(feature = "gzip")]
+        {
+            builder = builder.gzip(config.compression);
+        }
+        
+        #[cfg(feature = "brotli")]
+        {
+            builder = builder.brotli(config.compression);
+        }
+        
+        let http_client = builder.build()?;
+        
+        Self::with_http_client(config, http_client)
+    }
+    
+    /// Create a new Kraken API client using a pre-built HTTP client
+    ///
+    /// The user agent, proxy, pool and compression settings in `config` are ignored in
+    /// favour of those of `http_client`. The request timeouts in `config` still apply.
+    pub fn with_http_client(config: Config, http_client: HttpClient) -> Result<Self> {
+        let mut client = Self::with_transport(config, http_client.clone())?;
+        client.http_client = Some(http_client);
+        Ok(client)
+    }
+    
+    /// Create a new Kraken API client that sends REST requests through `transport`
+    ///
+    /// Useful for injecting a mock transport in tests. Each request carries its timeout
+    /// from `config` for the transport to apply; the user agent, proxy, pool and
+    /// compression settings in `config` are ignored.
+    pub fn with_transport(config: Config, transport: impl Transport + 'static) -> Result<Self> {
+        let signer = match config.signer {
+            Some(ref signer) => Some(signer.clone()),
+            None => match config.api_secret.as_deref() {
+                Some(api_secret) => Some(Arc::new(HmacSigner::new(api_secret)?) as Arc<dyn Signer>),
+                None => None,
+            },
+        };
+        let order_rate_limiter = OrderRateLimiter::new(config.verification_tier);
+        let private_rate_limiter = PrivateRateLimiter::new(config.verification_tier);
         
         Ok(Self {
             config,
-            http_client,
+            transport: Arc::new(transport),
+            http_client: None,
             rate_limiter: RateLimiter::new(),
+            order_rate_limiter,
+            private_rate_limiter,
+            signer,
+            time_offset: Arc::new(AtomicI64::new(0)),
+            last_nonce: Arc::new(AtomicU64::new(0)),
+            metadata: MetadataCache::new(),
+            #[cfg(feature = "metrics")]
+            metrics: Arc::new(NoopMetrics),
         })
     }
     
     /// Create a new Kraken API client with default configuration
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Result<Self> {
         Self::new(Config::default())
     }
     
-    /// Get the HTTP client
-    pub fn http_client(&self) -> &HttpClient {
-        &self.http_client
+    /// Get the transport used to send REST requests
+    pub fn transport(&self) -> &dyn Transport {
+        self.transport.as_ref()
+    }
+    
+    /// Get the HTTP client, or `None` if the client was created with a custom transport
+    pub fn http_client(&self) -> Option<&HttpClient> {
+        self.http_client.as_ref()
+    }
+    
+    /// Report REST requests to `metrics`
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+    
+    /// Send a REST request, turning a non-2xx response into an error
+    ///
+    /// Also enforces the configured maximum response size.
+    pub(crate) async fn send(&self, endpoint: &str, mut request: TransportRequest) -> Result<TransportResponse> {
+        request.max_response_bytes = self.config.max_response_bytes;
+        
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        #[cfg(feature = "metrics")]
+        self.metrics.on_request_start(endpoint);
+        
+        let response = self.transport.send(request).await
+            .and_then(|response| match self.config.max_response_bytes {
+                // Transports other than reqwest may not enforce the limit themselves
+                Some(max_response_bytes) if response.body.len() > max_response_bytes => Err(response_too_large(max_response_bytes)),
+                _ => Ok(response),
+            })
+            .and_then(TransportResponse::error_for_status);
+        
+        #[cfg(feature = "metrics")]
+        self.metrics.on_request_end(endpoint, start.elapsed(), RequestOutcome::from_response(&response));
+        #[cfg(not(feature = "metrics"))]
+        let _ = endpoint;
+        
+        response
     }
     
     /// Get the rate limiter
@@ -50,13 +186,107 @@ impl KrakenClient {
         &self.rate_limiter
     }
     
+    /// Get the order rate limiter
+    pub fn order_rate_limiter(&self) -> &OrderRateLimiter {
+        &self.order_rate_limiter
+    }
+    
+    /// Get the rate limiter shared by all private endpoints
+    pub fn private_rate_limiter(&self) -> &PrivateRateLimiter {
+        &self.private_rate_limiter
+    }
+    
+    /// Get the cache used by [`KrakenClient::asset_pair`] and [`KrakenClient::asset_info`]
+    pub fn metadata_cache(&self) -> &MetadataCache {
+        &self.metadata
+    }
+    
+    /// Get the metadata for an asset pair, by any of its names
+    ///
+    /// Served from the metadata cache when [`Config::with_metadata_ttl`] is set.
+    pub async fn asset_pair(&self, pair: &str) -> Result<AssetPair> {
+        let asset_pairs = self.metadata
+            .asset_pairs(self.config.metadata_ttl, || async { self.public().get_asset_pairs(None).await })
+            .await?;
+        
+        asset_pairs.get_pair(pair).cloned().ok_or_else(|| Error::Other(format!("Unknown asset pair: {}", pair)))
+    }
+    
+    /// Get the metadata for an asset, by its canonical or alternate name
+    ///
+    /// Served from the metadata cache when [`Config::with_metadata_ttl`] is set.
+    pub async fn asset_info(&self, asset: &str) -> Result<AssetInfo> {
+        let assets = self.metadata
+            .assets(self.config.metadata_ttl, || async { self.public().get_assets(None).await })
+            .await?;
+        
+        assets.get(asset)
+            .or_else(|| assets.values().find(|info| info.altname == asset))
+            .cloned()
+            .ok_or_else(|| Error::Other(format!("Unknown asset: {}", asset)))
+    }
+    
+    /// Measure the offset between the local clock and Kraken's server time
+    ///
+    /// The offset is applied to [`KrakenClient::adjusted_now`] and to the nonces of
+    /// later private requests. Returns the measured skew, which is positive when the
+    /// local clock is behind. Kraken reports time in whole seconds, so the skew is only
+    /// accurate to about a second.
+    pub async fn sync_time(&self) -> Result<TimeDelta> {
+        let before = Utc::now().timestamp_millis();
+        let server_time = self.public().get_server_time().await?;
+        let after = Utc::now().timestamp_millis();
+        
+        // Compare against the middle of the request and of the reported second
+        let local = before + (after - before) / 2;
+        let server = server_time.unixtime * 1000 + 500;
+        
+        self.time_offset.store(server - local, Ordering::Relaxed);
+        
+        Ok(self.clock_skew())
+    }
+    
+    /// Get the skew measured by the last [`KrakenClient::sync_time`]
+    ///
+    /// Zero until the time has been synced.
+    pub fn clock_skew(&self) -> TimeDelta {
+        TimeDelta::milliseconds(self.time_offset.load(Ordering::Relaxed))
+    }
+    
+    /// Get the current time, corrected by the measured clock skew
+    ///
+    /// Use this when computing absolute times for order parameters, such as
+    /// [`crate::models::trading::Order::with_deadline_at`].
+    pub fn adjusted_now(&self) -> DateTime<Utc> {
+        Utc::now() + self.clock_skew()
+    }
+    
+    /// Generate a nonce from the adjusted time
+    ///
+    /// Nonces always increase, even if a later sync moves the clock backwards.
+    pub(crate) fn next_nonce(&self) -> u64 {
+        let now = self.adjusted_now().timestamp_millis().max(0) as u64;
+        let previous = self.last_nonce
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| Some(now.max(last + 1)))
+            .unwrap_or_default();
+        
+        now.max(previous + 1)
+    }
+    
+    /// Get the signer for private requests
+    ///
+    /// This is the signer from the configuration, or else one using the API secret.
+    pub(crate) fn signer(&self) -> Option<&dyn Signer> {
+        self.signer.as_deref()
+    }
+    
     /// Get the public API
-    pub fn public(&self) -> PublicApi {
+    pub fn public(&self) -> PublicApi<'_> {
         PublicApi::new(self)
     }
     
     /// Get the private API
-    pub fn private(&self) -> PrivateApi {
+    pub fn private(&self) -> PrivateApi<'_> {
         PrivateApi::new(self)
     }
     
@@ -65,3 +295,171 @@ impl KrakenClient {
         WebSocketApi::new(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::transport::mock::MockTransport;
+    
+    #[test]
+    fn test_new_rejects_invalid_secret() {
+        let config = Config::new().with_api_key("key").with_api_secret("not base64!");
+        assert!(matches!(KrakenClient::new(config), Err(Error::Auth(_))));
+        
+        let config = Config::new().with_api_key("key").with_api_secret("c2VjcmV0");
+        let client = KrakenClient::new(config).unwrap();
+        let signature = client.signer().unwrap().sign("/0/private/Balance", 1, "nonce=1").unwrap();
+        assert_eq!(signature, crate::auth::sign_message("/0/private/Balance", 1, "nonce=1", b"secret").unwrap());
+    }
+    
+    #[test]
+    fn test_http_client_accessor() {
+        assert!(KrakenClient::new(Config::default()).unwrap().http_client().is_some());
+        assert!(KrakenClient::with_transport(Config::default(), HttpClient::new()).unwrap().http_client().is_none());
+    }
+    
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn test_compression_sets_accept_encoding() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/0/public/Time"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"error":[],"result":{"unixtime":1688669448,"rfc1123":""}}"#))
+            .mount(&server)
+            .await;
+        
+        for compression in [true, false] {
+            let config = Config::new().with_api_url(server.uri()).with_compression(compression);
+            KrakenClient::new(config).unwrap().public().get_server_time().await.unwrap();
+        }
+        
+        let requests = server.received_requests().await.unwrap();
+        let accept_encoding = |i: usize| requests[i].headers.get(&"accept-encoding".into()).map(|v| v.as_str().to_string());
+        
+        assert!(accept_encoding(0).unwrap().contains("gzip"));
+        assert!(accept_encoding(1).is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_max_response_bytes() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/0/public/Time"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"error":[],"result":{"unixtime":1688669448,"rfc1123":""}}"#))
+            .mount(&server)
+            .await;
+        
+        let config = Config::new().with_api_url(server.uri()).with_max_response_bytes(1000);
+        assert!(KrakenClient::new(config).unwrap().public().get_server_time().await.is_ok());
+        
+        let config = Config::new().with_api_url(server.uri()).with_max_response_bytes(20);
+        let error = KrakenClient::new(config).unwrap().public().get_server_time().await.unwrap_err();
+        assert!(matches!(error, Error::Api(ref msg) if msg.contains("20 bytes")));
+    }
+    
+    #[tokio::test]
+    async fn test_sync_time() {
+        // Reports a server clock one hour ahead of the local clock
+        let transport = MockTransport::new(|_| {
+            let unixtime = Utc::now().timestamp() + 3600;
+            Ok(TransportResponse::json(format!(r#"{{"error":[],"result":{{"unixtime":{},"rfc1123":""}}}}"#, unixtime)))
+        });
+        
+        let client = KrakenClient::with_transport(Config::new(), transport).unwrap();
+        assert_eq!(client.clock_skew(), TimeDelta::zero());
+        
+        let skew = client.sync_time().await.unwrap();
+        assert!((skew - TimeDelta::hours(1)).num_milliseconds().abs() <= 1000);
+        assert!(client.adjusted_now() - Utc::now() > TimeDelta::minutes(59));
+        
+        let nonce = client.next_nonce();
+        assert!(nonce as i64 > Utc::now().timestamp_millis() + 3_000_000);
+        
+        // Nonces keep increasing when the clock is synced backwards
+        client.time_offset.store(0, Ordering::Relaxed);
+        assert!(client.next_nonce() > nonce);
+    }
+    
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn test_metrics_hook() {
+        use crate::metrics::{Metrics, RequestOutcome};
+        use std::sync::Mutex;
+        
+        // Answers the server time, reports an API error for assets and fails every other request
+        let transport = MockTransport::new(|request| {
+            if request.url.contains("/Time") {
+                return Ok(TransportResponse::json(r#"{"error":["WGeneral:Deprecated"],"result":{"unixtime":1688669448,"rfc1123":""}}"#));
+            }
+            
+            if request.url.contains("/Assets") {
+                return Ok(TransportResponse::json(r#"{"error":["EGeneral:Invalid arguments"]}"#));
+            }
+            
+            Ok(TransportResponse { status: 503, headers: Default::default(), body: b"unavailable".to_vec() })
+        });
+        
+        #[derive(Debug, Default)]
+        struct RecordingMetrics {
+            started: Arc<Mutex<Vec<String>>>,
+            ended: Arc<Mutex<Vec<(String, RequestOutcome)>>>,
+        }
+        
+        impl Metrics for RecordingMetrics {
+            fn on_request_start(&self, endpoint: &str) {
+                self.started.lock().unwrap().push(endpoint.to_string());
+            }
+            
+            fn on_request_end(&self, endpoint: &str, _duration: Duration, outcome: RequestOutcome) {
+                self.ended.lock().unwrap().push((endpoint.to_string(), outcome));
+            }
+        }
+        
+        let metrics = RecordingMetrics::default();
+        let started = metrics.started.clone();
+        let ended = metrics.ended.clone();
+        let client = KrakenClient::with_transport(Config::new(), transport).unwrap().with_metrics(metrics);
+        
+        client.public().get_server_time().await.unwrap();
+        assert!(client.public().get_system_status().await.is_err());
+        assert!(client.public().get_assets(None).await.is_err());
+        
+        assert_eq!(*started.lock().unwrap(), vec!["/0/public/Time", "/0/public/SystemStatus", "/0/public/Assets"]);
+        assert_eq!(*ended.lock().unwrap(), vec![
+            ("/0/public/Time".to_string(), RequestOutcome::Success),
+            ("/0/public/SystemStatus".to_string(), RequestOutcome::HttpStatus(503)),
+            ("/0/public/Assets".to_string(), RequestOutcome::ApiError),
+        ]);
+    }
+    
+    #[tokio::test]
+    async fn test_asset_pair_is_cached() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let requests = calls.clone();
+        let transport = MockTransport::new(move |request| {
+            assert!(request.url.contains("/0/public/AssetPairs"));
+            requests.fetch_add(1, Ordering::SeqCst);
+            
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"XXBTZUSD":{"altname":"XBTUSD","wsname":"XBT/USD","pair_decimals":1,"lot_decimals":8}}}"#))
+        });
+        
+        let config = Config::new().with_metadata_ttl(Duration::from_secs(60));
+        let client = KrakenClient::with_transport(config, transport).unwrap();
+        
+        assert_eq!(client.asset_pair("XBTUSD").await.unwrap().pair_decimals, Some(1));
+        assert_eq!(client.asset_pair("XBT/USD").await.unwrap().altname.as_deref(), Some("XBTUSD"));
+        assert!(client.asset_pair("ETHUSD").await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        
+        client.metadata_cache().clear().await;
+        client.asset_pair("XBTUSD").await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
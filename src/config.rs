@@ -1,5 +1,49 @@
 //! Configuration for the Kraken API client
 
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::rate_limiter::VerificationTier;
+use crate::auth::Signer;
+use crate::error::{Error, Result};
+
+/// Categories of requests that can be given their own timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndpointClass {
+    /// Public REST endpoints
+    Public,
+    
+    /// Private REST endpoints
+    Private,
+    
+    /// Export report endpoints (`AddExport`, `ExportStatus`, `RetrieveExport` and `RemoveExport`), which can be slow to respond
+    Export,
+    
+    /// WebSocket connection establishment
+    WebSocketConnect,
+    
+    /// Replies to WebSocket order requests
+    WebSocketRequest,
+}
+
+impl EndpointClass {
+    /// Get the class of a REST endpoint path
+    pub fn for_endpoint(endpoint: &str) -> Self {
+        let name = endpoint.rsplit('/').next().unwrap_or_default();
+        
+        if matches!(name, "AddExport" | "ExportStatus" | "RetrieveExport" | "RemoveExport") {
+            EndpointClass::Export
+        } else if endpoint.contains("/private/") {
+            EndpointClass::Private
+        } else {
+            EndpointClass::Public
+        }
+    }
+}
+
 /// Configuration for the Kraken API client
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -9,17 +53,65 @@ pub struct Config {
     /// API secret for authenticated requests
     pub api_secret: Option<String>,
     
+    /// Signer used instead of signing with `api_secret`
+    pub signer: Option<Arc<dyn Signer>>,
+    
+    /// Two-factor password sent with every private request
+    ///
+    /// Required when two-factor authentication is enabled on the API key. Withdrawals
+    /// in particular often require it.
+    pub otp: Option<String>,
+    
     /// Base URL for the Kraken API
     pub api_url: String,
     
+    /// API version, used as the first segment of every REST endpoint path
+    pub api_version: String,
+    
     /// WebSocket URL for the Kraken API
     pub ws_url: String,
     
+    /// WebSocket URL for private channels of the Kraken API
+    pub ws_auth_url: String,
+    
     /// Timeout for HTTP requests in seconds
     pub timeout: u64,
     
+    /// Timeout overrides for specific categories of requests
+    pub request_timeouts: HashMap<EndpointClass, Duration>,
+    
     /// User agent string
     pub user_agent: String,
+    
+    /// Account verification tier, used for order rate limiting
+    pub verification_tier: VerificationTier,
+    
+    /// Proxy URL for HTTP requests
+    pub proxy: Option<String>,
+    
+    /// Tunnel WebSocket connections through `proxy` as well
+    pub ws_proxy: bool,
+    
+    /// Idle timeout for pooled HTTP connections in seconds
+    pub pool_idle_timeout: Option<u64>,
+    
+    /// Send every order as validate-only, so no order is ever placed
+    pub dry_run: bool,
+    
+    /// Request compressed responses when the `gzip` or `brotli` feature is enabled
+    pub compression: bool,
+    
+    /// Maximum size of a REST response body in bytes
+    pub max_response_bytes: Option<usize>,
+    
+    /// How long cached asset and asset pair metadata stays fresh, or `None` to not cache it
+    pub metadata_ttl: Option<Duration>,
+    
+    /// Number of times `add_order` is retried after a retryable error
+    pub order_retries: u32,
+    
+    /// Delay before the first retry of `add_order`, doubled for each further retry
+    pub order_retry_delay: Duration,
 }
 
 impl Default for Config {
@@ -27,10 +119,25 @@ impl Default for Config {
         Self {
             api_key: None,
             api_secret: None,
+            signer: None,
+            otp: None,
             api_url: "https://api.kraken.com".to_string(),
+            api_version: "0".to_string(),
             ws_url: "wss://ws.kraken.com".to_string(),
+            ws_auth_url: "wss://ws-auth.kraken.com".to_string(),
             timeout: 30,
+            request_timeouts: HashMap::new(),
             user_agent: format!("kraken_client/{}", env!("CARGO_PKG_VERSION")),
+            verification_tier: VerificationTier::default(),
+            proxy: None,
+            ws_proxy: false,
+            pool_idle_timeout: None,
+            dry_run: false,
+            compression: true,
+            max_response_bytes: None,
+            metadata_ttl: None,
+            order_retries: 0,
+            order_retry_delay: Duration::from_secs(1),
         }
     }
 }
@@ -41,6 +148,65 @@ impl Config {
         Self::default()
     }
     
+    /// Create a new configuration from environment variables
+    ///
+    /// Reads `KRAKEN_API_KEY` and `KRAKEN_API_SECRET`, and optionally `KRAKEN_API_URL`,
+    /// `KRAKEN_WS_URL` and `KRAKEN_WS_AUTH_URL`. Unset variables keep their default values. Returns an
+    /// error if `KRAKEN_API_SECRET` is set but is not valid base64.
+    pub fn from_env() -> Result<Self> {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+    
+    /// Create a new configuration from variables returned by `lookup`
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config = Self::default();
+        
+        if let Some(api_key) = lookup("KRAKEN_API_KEY") {
+            config.api_key = Some(api_key);
+        }
+        
+        if let Some(api_secret) = lookup("KRAKEN_API_SECRET") {
+            BASE64.decode(&api_secret)
+                .map_err(|e| Error::Auth(format!("KRAKEN_API_SECRET is not valid base64: {}", e)))?;
+            config.api_secret = Some(api_secret);
+        }
+        
+        if let Some(api_url) = lookup("KRAKEN_API_URL") {
+            config.api_url = api_url;
+        }
+        
+        if let Some(ws_url) = lookup("KRAKEN_WS_URL") {
+            config.ws_url = ws_url;
+        }
+        
+        if let Some(ws_auth_url) = lookup("KRAKEN_WS_AUTH_URL") {
+            config.ws_auth_url = ws_auth_url;
+        }
+        
+        Ok(config)
+    }
+    
+    /// Check that the configuration can be used for private requests
+    ///
+    /// Fails if the API key or secret is missing, or if the secret is not valid base64,
+    /// so apps that need private access can fail at startup instead of on their first
+    /// private request. The secret isn't needed when a signer is set.
+    pub fn require_auth(self) -> Result<Self> {
+        if self.api_key.as_deref().unwrap_or_default().is_empty() {
+            return Err(Error::Auth("API key not set".to_string()));
+        }
+        
+        if self.signer.is_some() {
+            return Ok(self);
+        }
+        
+        let api_secret = self.api_secret.as_deref().ok_or_else(|| Error::Auth("API secret not set".to_string()))?;
+        BASE64.decode(api_secret)
+            .map_err(|e| Error::Auth(format!("API secret is not valid base64: {}", e)))?;
+        
+        Ok(self)
+    }
+    
     /// Set the API key
     pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
         self.api_key = Some(api_key.into());
@@ -53,27 +219,226 @@ impl Config {
         self
     }
     
+    /// Sign private requests with `signer` instead of the API secret
+    pub fn with_signer(mut self, signer: impl Signer + 'static) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+    
+    /// Set the two-factor password for private requests
+    pub fn with_otp(mut self, otp: impl Into<String>) -> Self {
+        self.otp = Some(otp.into());
+        self
+    }
+    
     /// Set the API URL
     pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
         self.api_url = api_url.into();
         self
     }
     
+    /// Set the API version, e.g. `"0"` for endpoints under `/0/`
+    pub fn with_api_version(mut self, api_version: impl Into<String>) -> Self {
+        self.api_version = api_version.into();
+        self
+    }
+    
+    /// Get the full path of a REST endpoint such as `/public/Time`, including the API version
+    pub fn endpoint_path(&self, endpoint: &str) -> String {
+        if self.api_version.is_empty() {
+            return endpoint.to_string();
+        }
+        
+        format!("/{}{}", self.api_version, endpoint)
+    }
+    
     /// Set the WebSocket URL
     pub fn with_ws_url(mut self, ws_url: impl Into<String>) -> Self {
         self.ws_url = ws_url.into();
         self
     }
     
+    /// Set the WebSocket URL for private channels
+    pub fn with_ws_auth_url(mut self, ws_auth_url: impl Into<String>) -> Self {
+        self.ws_auth_url = ws_auth_url.into();
+        self
+    }
+    
     /// Set the timeout
     pub fn with_timeout(mut self, timeout: u64) -> Self {
         self.timeout = timeout;
         self
     }
     
+    /// Set the timeout for a category of requests, overriding the default timeout
+    pub fn with_request_timeout(mut self, endpoint_class: EndpointClass, timeout: Duration) -> Self {
+        self.request_timeouts.insert(endpoint_class, timeout);
+        self
+    }
+    
+    /// Set how long to wait for a WebSocket connection to be established
+    ///
+    /// Shorthand for `with_request_timeout(EndpointClass::WebSocketConnect, timeout)`.
+    /// Defaults to the general `timeout`.
+    pub fn with_ws_connect_timeout(self, timeout: Duration) -> Self {
+        self.with_request_timeout(EndpointClass::WebSocketConnect, timeout)
+    }
+    
+    /// Get the timeout for a category of requests
+    pub fn request_timeout(&self, endpoint_class: EndpointClass) -> Duration {
+        self.request_timeouts
+            .get(&endpoint_class)
+            .copied()
+            .unwrap_or_else(|| Duration::from_secs(self.timeout))
+    }
+    
     /// Set the user agent
     pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
         self.user_agent = user_agent.into();
         self
     }
+    
+    /// Set the account verification tier
+    pub fn with_verification_tier(mut self, verification_tier: VerificationTier) -> Self {
+        self.verification_tier = verification_tier;
+        self
+    }
+    
+    /// Set the proxy URL for HTTP requests
+    ///
+    /// WebSocket connections only use it once enabled with [`Config::with_websocket_proxy`].
+    pub fn with_proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+    
+    /// Tunnel WebSocket connections through the proxy, off by default
+    ///
+    /// Connections are tunnelled with `CONNECT`, which needs an `http://` proxy. With any other
+    /// proxy scheme, or a host listed in `NO_PROXY`, the connection is made directly.
+    pub fn with_websocket_proxy(mut self, ws_proxy: bool) -> Self {
+        self.ws_proxy = ws_proxy;
+        self
+    }
+    
+    /// Set the idle timeout for pooled HTTP connections
+    pub fn with_connection_pool_idle_timeout(mut self, pool_idle_timeout: u64) -> Self {
+        self.pool_idle_timeout = Some(pool_idle_timeout);
+        self
+    }
+    
+    /// Enable or disable dry-run mode
+    ///
+    /// In dry-run mode, `add_order` and `add_order_batch`, over REST or WebSocket, always
+    /// send `validate=true`, regardless of the order's own `validate` flag, so orders are
+    /// checked by Kraken but never placed. `amend_order` is refused.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+    
+    /// Enable or disable compressed responses
+    ///
+    /// Only has an effect when the crate is built with the `gzip` or `brotli` feature,
+    /// in which case it is enabled by default.
+    pub fn with_compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+    
+    /// Fail REST requests whose response body is larger than `max_response_bytes`
+    ///
+    /// Protects against running out of memory when `api_url` points at a misbehaving
+    /// server or proxy. Unlimited by default.
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_response_bytes);
+        self
+    }
+    
+    /// Cache the metadata looked up by [`crate::KrakenClient::asset_pair`] and
+    /// [`crate::KrakenClient::asset_info`] for `metadata_ttl`
+    ///
+    /// Off by default, in which case every lookup fetches from Kraken.
+    pub fn with_metadata_ttl(mut self, metadata_ttl: Duration) -> Self {
+        self.metadata_ttl = Some(metadata_ttl);
+        self
+    }
+    
+    /// Retry `add_order` up to `order_retries` times after a retryable error
+    ///
+    /// Orders without a client order ID are given a generated one, so if a failed
+    /// attempt did place the order, Kraken rejects the retry instead of placing it
+    /// twice. Off by default.
+    pub fn with_order_retries(mut self, order_retries: u32) -> Self {
+        self.order_retries = order_retries;
+        self
+    }
+    
+    /// Set the delay before the first retry of `add_order`, doubled for each further retry
+    pub fn with_order_retry_delay(mut self, order_retry_delay: Duration) -> Self {
+        self.order_retry_delay = order_retry_delay;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_from_env() {
+        // Read from a map rather than the process environment, which other tests share
+        let mut vars = HashMap::new();
+        vars.insert("KRAKEN_API_KEY", "key");
+        vars.insert("KRAKEN_API_SECRET", "c2VjcmV0");
+        vars.insert("KRAKEN_API_URL", "https://example.com");
+        
+        let config = Config::from_lookup(|name| vars.get(name).map(|value| value.to_string())).unwrap();
+        assert_eq!(config.api_key.as_deref(), Some("key"));
+        assert_eq!(config.api_secret.as_deref(), Some("c2VjcmV0"));
+        assert_eq!(config.api_url, "https://example.com");
+        assert_eq!(config.ws_url, "wss://ws.kraken.com");
+        
+        // An invalid secret is rejected up front
+        vars.insert("KRAKEN_API_SECRET", "not base64!");
+        let config = Config::from_lookup(|name| vars.get(name).map(|value| value.to_string()));
+        assert!(matches!(config, Err(Error::Auth(_))));
+    }
+    
+    #[test]
+    fn test_require_auth() {
+        assert!(Config::new().with_api_key("key").with_api_secret("c2VjcmV0").require_auth().is_ok());
+        
+        let missing_key = Config::new().with_api_secret("c2VjcmV0").require_auth();
+        assert!(matches!(missing_key, Err(Error::Auth(ref msg)) if msg == "API key not set"));
+        
+        let missing_secret = Config::new().with_api_key("key").require_auth();
+        assert!(matches!(missing_secret, Err(Error::Auth(ref msg)) if msg == "API secret not set"));
+        
+        let invalid_secret = Config::new().with_api_key("key").with_api_secret("not base64!").require_auth();
+        assert!(matches!(invalid_secret, Err(Error::Auth(_))));
+    }
+    
+    #[test]
+    fn test_request_timeout() {
+        let config = Config::new()
+            .with_timeout(10)
+            .with_request_timeout(EndpointClass::Export, Duration::from_secs(120));
+        
+        assert_eq!(config.request_timeout(EndpointClass::Export), Duration::from_secs(120));
+        assert_eq!(config.request_timeout(EndpointClass::Private), Duration::from_secs(10));
+        assert_eq!(EndpointClass::for_endpoint("/0/private/RetrieveExport"), EndpointClass::Export);
+        assert_eq!(EndpointClass::for_endpoint("/0/private/ExportStatus"), EndpointClass::Export);
+        assert_eq!(EndpointClass::for_endpoint("/private/AddExport"), EndpointClass::Export);
+        assert_eq!(EndpointClass::for_endpoint("/0/private/NotAnExport"), EndpointClass::Private);
+        assert_eq!(EndpointClass::for_endpoint("/0/private/Balance"), EndpointClass::Private);
+        assert_eq!(EndpointClass::for_endpoint("/0/public/Time"), EndpointClass::Public);
+    }
+    
+    #[test]
+    fn test_endpoint_path() {
+        assert_eq!(Config::new().endpoint_path("/public/Time"), "/0/public/Time");
+        assert_eq!(Config::new().with_api_version("1").endpoint_path("/private/Balance"), "/1/private/Balance");
+        assert_eq!(Config::new().with_api_version("").endpoint_path("/public/Time"), "/public/Time");
+    }
 }
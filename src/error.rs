@@ -1,5 +1,7 @@
 //! Error handling for the Kraken API client
 
+use std::fmt;
+use std::ops::Deref;
 use thiserror::Error;
 
 /// Result type for the Kraken API client
@@ -20,9 +22,19 @@ pub enum Error {
     #[error("URL error: {0}")]
     Url(#[from] url::ParseError),
 
+    /// Non-success HTTP status, e.g. an HTML error page from a proxy during an outage
+    #[error("HTTP status {code}: {body}")]
+    HttpStatus {
+        /// Status code
+        code: u16,
+        
+        /// Response body
+        body: ErrorMessage,
+    },
+
     /// API error
     #[error("API error: {0}")]
-    Api(String),
+    Api(ErrorMessage),
 
     /// Authentication error
     #[error("Authentication error: {0}")]
@@ -30,7 +42,15 @@ pub enum Error {
 
     /// Rate limit error
     #[error("Rate limit error: {0}")]
-    RateLimit(String),
+    RateLimit(ErrorMessage),
+
+    /// Operation did not complete in time
+    #[error("Timeout: {0}")]
+    Timeout(String),
+
+    /// Kraken is unavailable or too busy, e.g. during maintenance
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(ErrorMessage),
 
     /// WebSocket error
     #[error("WebSocket error: {0}")]
@@ -40,3 +60,197 @@ pub enum Error {
     #[error("Other error: {0}")]
     Other(String),
 }
+
+/// Message of an error returned by a REST API request
+///
+/// Derefs to the message and compares equal to plain strings, so it can be matched
+/// like one. Errors from the request helpers also carry the ID that appears in the
+/// request's log lines, which is included when the message is displayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorMessage {
+    /// Message text
+    message: String,
+    
+    /// Unique ID of the request that caused the error
+    request_id: Option<String>,
+}
+
+impl ErrorMessage {
+    /// Get the message text
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    
+    /// Get the ID of the request that caused the error, if known
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+}
+
+impl From<String> for ErrorMessage {
+    fn from(message: String) -> Self {
+        Self { message, request_id: None }
+    }
+}
+
+impl From<&str> for ErrorMessage {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl Deref for ErrorMessage {
+    type Target = str;
+    
+    fn deref(&self) -> &str {
+        &self.message
+    }
+}
+
+impl PartialEq<str> for ErrorMessage {
+    fn eq(&self, other: &str) -> bool {
+        self.message == other
+    }
+}
+
+impl PartialEq<&str> for ErrorMessage {
+    fn eq(&self, other: &&str) -> bool {
+        self.message == *other
+    }
+}
+
+impl fmt::Display for ErrorMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.request_id {
+            Some(ref request_id) => write!(f, "{} (request {})", self.message, request_id),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Kraken error codes reported when a rate limit is exceeded
+const RATE_LIMIT_ERRORS: &[&str] = &["EAPI:Rate limit exceeded", "EOrder:Rate limit exceeded"];
+
+/// Kraken error codes reported during maintenance or under heavy load
+const SERVICE_UNAVAILABLE_ERRORS: &[&str] = &["EService:Unavailable", "EService:Busy"];
+
+impl Error {
+    /// Create an error from the `error` array of a Kraken API response
+    pub(crate) fn from_api_errors(errors: &[String]) -> Self {
+        if let Some(code) = errors.iter().find(|e| RATE_LIMIT_ERRORS.iter().any(|r| e.starts_with(r))) {
+            return Error::RateLimit(code.as_str().into());
+        }
+        
+        if let Some(code) = errors.iter().find(|e| SERVICE_UNAVAILABLE_ERRORS.iter().any(|s| e.starts_with(s))) {
+            return Error::ServiceUnavailable(code.as_str().into());
+        }
+        
+        Error::Api(errors.join(", ").into())
+    }
+    
+    /// Whether the request may succeed if retried after backing off
+    ///
+    /// True for rate limits, maintenance, timeouts, connection failures and
+    /// `429` or `5xx` responses.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::ServiceUnavailable(_) | Error::RateLimit(_) | Error::Timeout(_) => true,
+            Error::HttpStatus { code, .. } => *code == 429 || *code >= 500,
+            Error::Http(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+    
+    /// Get the message of an error reported by Kraken or by the HTTP status
+    fn message_mut(&mut self) -> Option<&mut ErrorMessage> {
+        match self {
+            Error::Api(message) | Error::RateLimit(message) | Error::ServiceUnavailable(message) => Some(message),
+            Error::HttpStatus { body, .. } => Some(body),
+            _ => None,
+        }
+    }
+    
+    /// Tag an error with the ID of the request that caused it
+    ///
+    /// Keeps an ID that is already set. Errors without an [`ErrorMessage`] are
+    /// returned unchanged.
+    pub(crate) fn with_request_id(mut self, request_id: &str) -> Self {
+        if let Some(message) = self.message_mut() {
+            message.request_id.get_or_insert_with(|| request_id.to_string());
+        }
+        
+        self
+    }
+    
+    /// Get the ID of the request that caused this error, if known
+    ///
+    /// Set on API, rate limit, service unavailable and HTTP status errors returned
+    /// by REST API calls.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            Error::Api(message) | Error::RateLimit(message) | Error::ServiceUnavailable(message) => message.request_id(),
+            Error::HttpStatus { body, .. } => body.request_id(),
+            _ => None,
+        }
+    }
+}
+
+/// Split the `error` array of a Kraken API response into errors and warnings
+///
+/// Kraken prefixes each message with its severity: `E` for errors and `W` for
+/// warnings, which can accompany an otherwise successful result.
+pub(crate) fn split_warnings(messages: Vec<String>) -> (Vec<String>, Vec<String>) {
+    messages.into_iter().partition(|message| !message.starts_with('W'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_from_api_errors() {
+        let error = Error::from_api_errors(&["EOrder:Rate limit exceeded".to_string()]);
+        assert!(matches!(error, Error::RateLimit(ref code) if code == "EOrder:Rate limit exceeded"));
+        
+        let error = Error::from_api_errors(&["EGeneral:Invalid arguments".to_string()]);
+        assert!(matches!(error, Error::Api(_)));
+        assert!(!error.is_retryable());
+        
+        let error = Error::from_api_errors(&["EService:Unavailable".to_string()]);
+        assert!(matches!(error, Error::ServiceUnavailable(ref code) if code == "EService:Unavailable"));
+        assert!(error.is_retryable());
+        
+        let error = Error::from_api_errors(&["EService:Busy".to_string()]);
+        assert!(matches!(error, Error::ServiceUnavailable(_)));
+        
+        assert!(Error::HttpStatus { code: 503, body: "".into() }.is_retryable());
+        assert!(!Error::HttpStatus { code: 404, body: "".into() }.is_retryable());
+    }
+    
+    #[test]
+    fn test_with_request_id() {
+        let error = Error::Api("EGeneral:Invalid arguments".into()).with_request_id("abc");
+        assert_eq!(error.request_id(), Some("abc"));
+        assert!(matches!(error, Error::Api(ref msg) if msg == "EGeneral:Invalid arguments"));
+        assert_eq!(error.to_string(), "API error: EGeneral:Invalid arguments (request abc)");
+        
+        // Tagging twice keeps the original ID
+        let error = error.with_request_id("def");
+        assert_eq!(error.request_id(), Some("abc"));
+        
+        // Errors without a message are left as they are
+        let error = Error::Other("Chunk size must be at least 1".to_string()).with_request_id("abc");
+        assert_eq!(error.request_id(), None);
+        assert!(matches!(error, Error::Other(_)));
+    }
+    
+    #[test]
+    fn test_split_warnings() {
+        let (errors, warnings) = split_warnings(vec![
+            "WService:Degraded".to_string(),
+            "EGeneral:Invalid arguments".to_string(),
+        ]);
+        assert_eq!(errors, vec!["EGeneral:Invalid arguments".to_string()]);
+        assert_eq!(warnings, vec!["WService:Degraded".to_string()]);
+    }
+}
@@ -4,18 +4,32 @@
 //! It supports both public and private API endpoints, as well as WebSocket connections
 //! for real-time data.
 
+#[macro_use]
+mod macros;
+
 pub mod client;
 pub mod error;
 pub mod models;
 pub mod api;
 pub mod auth;
 pub mod config;
+pub mod transport;
 pub mod utils;
 
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+#[cfg(feature = "replay")]
+pub mod replay;
+
 // Re-export commonly used types
 pub use client::KrakenClient;
-pub use error::Error;
+pub use error::{Error, ErrorMessage};
 pub use config::Config;
+pub use rust_decimal::Decimal;
 
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,67 @@
+//! Internal logging macros
+//!
+//! These forward to the `log` crate when the `logging` feature is enabled and
+//! compile to nothing otherwise.
+
+/// Log a message at debug level
+macro_rules! log_debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "logging")]
+        log::debug!($($arg)*);
+        
+        #[cfg(not(feature = "logging"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+/// Log a message at warn level
+macro_rules! log_warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "logging")]
+        log::warn!($($arg)*);
+        
+        #[cfg(not(feature = "logging"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+/// Define an async trait over API endpoint methods, implemented by forwarding to the
+/// inherent methods of the API struct
+macro_rules! api_trait {
+    ($(#[$trait_meta:meta])* $trait_name:ident for $api:ident; $($(#[$meta:meta])* fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(#[$trait_meta])*
+        #[async_trait::async_trait]
+        pub trait $trait_name: Send + Sync {
+            $(
+                $(#[$meta])*
+                async fn $name(&self, $($arg: $ty),*) -> $crate::error::Result<$ret>;
+            )*
+        }
+        
+        #[async_trait::async_trait]
+        impl $trait_name for $api<'_> {
+            $(
+                async fn $name(&self, $($arg: $ty),*) -> $crate::error::Result<$ret> {
+                    $api::$name(self, $($arg),*).await
+                }
+            )*
+        }
+    };
+}
+
+/// Implement an API trait for a test double whose every method fails with
+/// [`crate::error::Error::Other`]
+#[cfg(test)]
+macro_rules! unimplemented_api {
+    ($trait_name:ident for $fake:ident; $($(#[$meta:meta])* fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        #[async_trait::async_trait]
+        impl $trait_name for $fake {
+            $(
+                async fn $name(&self, $($arg: $ty),*) -> $crate::error::Result<$ret> {
+                    $(let _ = $arg;)*
+                    Err($crate::error::Error::Other(format!("{} is not implemented", stringify!($name))))
+                }
+            )*
+        }
+    };
+}
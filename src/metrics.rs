@@ -0,0 +1,85 @@
+//! Metrics hook for REST requests
+//!
+//! Implement [`Metrics`] and install it with [`crate::KrakenClient::with_metrics`] to
+//! feed request counts, latencies and error rates into a metrics system.
+
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+use crate::error::{split_warnings, Error, Result};
+use crate::transport::TransportResponse;
+
+/// Outcome of a REST request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestOutcome {
+    /// A 2xx response was received
+    Success,
+    
+    /// A 2xx response was received, but Kraken reported errors in its body
+    ApiError,
+    
+    /// A non-2xx response was received
+    HttpStatus(u16),
+    
+    /// The request timed out
+    Timeout,
+    
+    /// The request failed before a response was received
+    Failed,
+}
+
+impl RequestOutcome {
+    /// Get the outcome of a sent request
+    pub(crate) fn from_response(response: &Result<TransportResponse>) -> Self {
+        match response {
+            Ok(response) if has_api_errors(response) => RequestOutcome::ApiError,
+            Ok(_) => RequestOutcome::Success,
+            Err(Error::HttpStatus { code, .. }) => RequestOutcome::HttpStatus(*code),
+            Err(Error::Http(e)) if e.is_timeout() => RequestOutcome::Timeout,
+            Err(_) => RequestOutcome::Failed,
+        }
+    }
+    
+    /// Whether the request succeeded
+    pub fn is_success(&self) -> bool {
+        matches!(self, RequestOutcome::Success)
+    }
+}
+
+/// Error array of a Kraken response body
+#[derive(Deserialize)]
+struct ErrorArray {
+    #[serde(default)]
+    error: Vec<String>,
+}
+
+/// Check whether a JSON response body reports errors, ignoring warnings
+fn has_api_errors(response: &TransportResponse) -> bool {
+    if !response.content_type().is_some_and(|v| v.contains("application/json")) {
+        return false;
+    }
+    
+    serde_json::from_slice::<ErrorArray>(&response.body)
+        .map(|body| !split_warnings(body.error).0.is_empty())
+        .unwrap_or(false)
+}
+
+/// Callbacks invoked around each REST request
+///
+/// Both callbacks default to doing nothing. `endpoint` is the API path, such as
+/// `/0/public/Ticker`. Errors Kraken reports in the body of a 2xx response are
+/// reported as [`RequestOutcome::ApiError`].
+pub trait Metrics: fmt::Debug + Send + Sync {
+    /// Called before a request is sent
+    fn on_request_start(&self, _endpoint: &str) {}
+    
+    /// Called once a request has completed
+    fn on_request_end(&self, _endpoint: &str, _duration: Duration, _outcome: RequestOutcome) {}
+}
+
+/// Metrics hook that does nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {}
@@ -1,13 +1,70 @@
 //! Account data models for the Kraken API
 
-use serde::Deserialize;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::models::trading::{parse_order_flags, OrderFlag, OrderStatus, ParsedOrderDescr, TradeInfo};
 
 /// Account balance
 pub type Balance = HashMap<String, String>;
 
+/// Build a [`Balance`] from a raw balance response
+///
+/// Numbers are kept as their string form. If Kraken returns a richer object for an
+/// asset, its `balance` (or `b`) field is used. Assets whose value can't be read are
+/// logged and skipped instead of failing the whole response.
+pub(crate) fn balance_from_values(values: HashMap<String, Value>) -> Balance {
+    values.into_iter()
+        .filter_map(|(asset, value)| {
+            let amount = match &value {
+                Value::Object(fields) => fields.get("balance").or_else(|| fields.get("b")),
+                value => Some(value),
+            };
+            let amount = amount.and_then(|amount| match amount {
+                Value::String(amount) => Some(amount.clone()),
+                Value::Number(amount) => Some(amount.to_string()),
+                _ => None,
+            });
+            
+            if amount.is_none() {
+                log_warn!("Skipping balance of {} with unexpected value {}", asset, value);
+            }
+            
+            Some((asset, amount?))
+        })
+        .collect()
+}
+
+/// Extended balance entry for a single asset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceExEntry {
+    /// Total balance
+    #[serde(alias = "b")]
+    pub balance: String,
+    
+    /// Amount held by open orders
+    pub hold_trade: String,
+    
+    /// Unverified balance, e.g. deposits that are not yet confirmed
+    pub uv: Option<String>,
+    
+    /// Credit available
+    pub credit: Option<String>,
+    
+    /// Credit in use
+    pub credit_used: Option<String>,
+}
+
+/// Extended account balance
+pub type BalanceEx = HashMap<String, BalanceExEntry>;
+
 /// Trade balance information
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TradeBalance {
     /// Equivalent balance (combined balance of all currencies)
     pub eb: String,
@@ -38,16 +95,16 @@ pub struct TradeBalance {
 }
 
 /// Open order
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenOrder {
     /// Referral order transaction ID that created this order
     pub refid: Option<String>,
     
     /// User reference ID
-    pub userref: Option<i64>,
+    pub userref: Option<i32>,
     
     /// Status of order
-    pub status: String,
+    pub status: OrderStatus,
     
     /// Unix timestamp of when order was placed
     pub opentm: f64,
@@ -92,20 +149,40 @@ pub struct OpenOrder {
     pub trades: Option<Vec<String>>,
 }
 
+impl OpenOrder {
+    /// Parse the order flags, skipping any this client doesn't know
+    pub fn oflags_parsed(&self) -> Vec<OrderFlag> {
+        parse_order_flags(&self.oflags)
+    }
+}
+
 /// Open orders
 pub type OpenOrders = HashMap<String, OpenOrder>;
 
+/// Open order together with the details of its trades
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenOrderWithTrades {
+    /// Open order
+    pub order: OpenOrder,
+    
+    /// Trades of the order, by trade ID
+    pub trades: HashMap<String, TradeInfo>,
+}
+
+/// Open orders together with the details of their trades
+pub type OpenOrdersWithTrades = HashMap<String, OpenOrderWithTrades>;
+
 /// Closed order
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClosedOrder {
     /// Referral order transaction ID that created this order
     pub refid: Option<String>,
     
     /// User reference ID
-    pub userref: Option<i64>,
+    pub userref: Option<i32>,
     
     /// Status of order
-    pub status: String,
+    pub status: OrderStatus,
     
     /// Reason order was closed
     pub reason: Option<String>,
@@ -156,16 +233,49 @@ pub struct ClosedOrder {
     pub trades: Option<Vec<String>>,
 }
 
+impl ClosedOrder {
+    /// Parse the order flags, skipping any this client doesn't know
+    pub fn oflags_parsed(&self) -> Vec<OrderFlag> {
+        parse_order_flags(&self.oflags)
+    }
+}
+
 /// Closed orders
 pub type ClosedOrders = HashMap<String, ClosedOrder>;
 
+/// Number of entries Kraken returns per page of closed orders, trades history and ledgers
+///
+/// Kraken doesn't let the page size be chosen; follow `ofs` to fetch further pages.
+pub const PAGE_SIZE: u64 = 50;
+
+/// A page of paginated results along with the total number of matching entries
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    /// Entries on this page
+    pub entries: T,
+    
+    /// Total number of entries matching the query, across all pages
+    pub count: u64,
+}
+
+/// Entries collected by following `ofs` across pages
+#[derive(Debug, Clone, Serialize)]
+pub struct PagedResult<T> {
+    /// Entries from all fetched pages
+    pub entries: T,
+    
+    /// Whether every matching entry was fetched, rather than stopping at the page limit
+    pub complete: bool,
+}
+
 /// Order description
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderDescription {
     /// Asset pair
     pub pair: String,
     
     /// Type of order (buy/sell)
+    #[serde(rename = "type")]
     pub type_: String,
     
     /// Order type
@@ -187,8 +297,17 @@ pub struct OrderDescription {
     pub close: Option<String>,
 }
 
+impl OrderDescription {
+    /// Parse the structured values out of the order description text
+    ///
+    /// Fails with a descriptive error if the text isn't in a recognized format.
+    pub fn parsed(&self) -> Result<ParsedOrderDescr> {
+        self.order.parse()
+    }
+}
+
 /// Ledger entry
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LedgerEntry {
     /// Reference ID
     pub refid: String,
@@ -197,6 +316,7 @@ pub struct LedgerEntry {
     pub time: f64,
     
     /// Type of ledger entry
+    #[serde(rename = "type")]
     pub type_: String,
     
     /// Asset class
@@ -219,7 +339,7 @@ pub struct LedgerEntry {
 pub type Ledger = HashMap<String, LedgerEntry>;
 
 /// Trade history entry
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TradeHistoryEntry {
     /// Order ID
     pub ordertxid: String,
@@ -234,6 +354,7 @@ pub struct TradeHistoryEntry {
     pub time: f64,
     
     /// Type of order (buy/sell)
+    #[serde(rename = "type")]
     pub type_: String,
     
     /// Order type
@@ -260,3 +381,463 @@ pub struct TradeHistoryEntry {
 
 /// Trade history
 pub type TradeHistory = HashMap<String, TradeHistoryEntry>;
+
+/// Open position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenPosition {
+    /// Order ID responsible for the position
+    pub ordertxid: String,
+    
+    /// Position status
+    pub posstatus: String,
+    
+    /// Asset pair
+    pub pair: String,
+    
+    /// Unix timestamp of trade
+    pub time: f64,
+    
+    /// Direction (buy/sell) of position
+    #[serde(rename = "type")]
+    pub type_: String,
+    
+    /// Order type used to open position
+    pub ordertype: String,
+    
+    /// Opening cost of position (quote currency)
+    pub cost: String,
+    
+    /// Opening fee of position (quote currency)
+    pub fee: String,
+    
+    /// Position volume (base currency)
+    pub vol: String,
+    
+    /// Quantity closed (base currency)
+    pub vol_closed: String,
+    
+    /// Initial margin consumed (quote currency)
+    pub margin: String,
+    
+    /// Current value of remaining position (only present when calculations were requested)
+    pub value: Option<String>,
+    
+    /// Unrealized profit/loss of remaining position (only present when calculations were requested)
+    pub net: Option<String>,
+    
+    /// Funding cost and term of position
+    pub terms: Option<String>,
+    
+    /// Unix timestamp of the next margin rollover fee
+    pub rollovertm: Option<String>,
+    
+    /// Comma delimited list of miscellaneous info
+    pub misc: String,
+    
+    /// Comma delimited list of order flags
+    pub oflags: String,
+}
+
+/// Open positions
+pub type OpenPositions = HashMap<String, OpenPosition>;
+
+/// Account metrics derived from the trade balance and open positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountSnapshot {
+    /// Equity = trade balance + unrealized net profit/loss
+    pub equity: Decimal,
+    
+    /// Margin used by open positions
+    pub used_margin: Decimal,
+    
+    /// Free margin available to open new positions
+    pub free_margin: Decimal,
+    
+    /// Free margin as a percentage of equity, `None` if equity is zero
+    pub free_margin_percent: Option<Decimal>,
+    
+    /// Notional value of the remaining volume of all open positions
+    ///
+    /// Each position is valued in its pair's quote currency, so this is only a
+    /// single amount when all positions share a quote currency.
+    pub exposure: Decimal,
+}
+
+impl AccountSnapshot {
+    /// Combine a trade balance and open positions into parsed metrics
+    ///
+    /// Positions are valued at their current `value` when it was requested, and at
+    /// the opening cost of their remaining volume otherwise. Fails if any field used
+    /// is not a valid number.
+    pub fn new(balance: &TradeBalance, positions: &OpenPositions) -> Result<Self> {
+        let equity = parse_amount("equity", &balance.e)?;
+        let used_margin = parse_amount("margin", &balance.m)?;
+        let free_margin = parse_amount("free margin", &balance.mf)?;
+        
+        let mut exposure = Decimal::ZERO;
+        for (id, position) in positions {
+            exposure += match position.value {
+                Some(ref value) => parse_amount(&format!("value of position {}", id), value)?,
+                None => {
+                    let cost = parse_amount(&format!("cost of position {}", id), &position.cost)?;
+                    let vol = parse_amount(&format!("volume of position {}", id), &position.vol)?;
+                    let vol_closed = parse_amount(&format!("closed volume of position {}", id), &position.vol_closed)?;
+                    
+                    if vol.is_zero() { Decimal::ZERO } else { cost * (vol - vol_closed) / vol }
+                }
+            };
+        }
+        
+        Ok(Self {
+            equity,
+            used_margin,
+            free_margin,
+            free_margin_percent: (!equity.is_zero()).then(|| free_margin / equity * Decimal::ONE_HUNDRED),
+            exposure,
+        })
+    }
+}
+
+/// Parse a decimal string field, naming the field in the error
+fn parse_amount(name: &str, value: &str) -> Result<Decimal> {
+    Decimal::from_str(value).map_err(|_| Error::Other(format!("Invalid {}: {:?}", name, value)))
+}
+
+/// Export report types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportReport {
+    /// Trades report
+    Trades,
+    
+    /// Ledgers report
+    Ledgers,
+}
+
+impl fmt::Display for ExportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ExportReport::Trades => "trades",
+            ExportReport::Ledgers => "ledgers",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Export file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ExportFormat {
+    /// Comma separated values
+    Csv,
+    
+    /// Tab separated values
+    Tsv,
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Tsv => "TSV",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Ways of removing an export report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoveExportType {
+    /// Cancel a queued report
+    Cancel,
+    
+    /// Delete a processed report
+    Delete,
+}
+
+impl fmt::Display for RemoveExportType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            RemoveExportType::Cancel => "cancel",
+            RemoveExportType::Delete => "delete",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Add export response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddExportResponse {
+    /// Report ID
+    pub id: String,
+}
+
+/// Export report status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportStatus {
+    /// Report ID
+    pub id: String,
+    
+    /// Report description
+    pub descr: String,
+    
+    /// File format
+    pub format: String,
+    
+    /// Report type
+    pub report: String,
+    
+    /// Report status (`Queued`, `Processing` or `Processed`)
+    pub status: String,
+    
+    /// Fields included in the report
+    pub fields: Option<String>,
+    
+    /// Unix timestamp of report creation
+    pub createdtm: String,
+    
+    /// Unix timestamp of report start time
+    pub starttm: Option<String>,
+    
+    /// Unix timestamp of report completion
+    pub completedtm: Option<String>,
+    
+    /// Unix timestamp of the report data start time
+    pub datastarttm: Option<String>,
+    
+    /// Unix timestamp of the report data end time
+    pub dataendtm: Option<String>,
+    
+    /// Asset class
+    pub aclass: Option<String>,
+    
+    /// Asset
+    pub asset: Option<String>,
+}
+
+/// Remove export response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveExportResponse {
+    /// Whether the report was deleted
+    pub delete: Option<bool>,
+    
+    /// Whether the report was cancelled
+    pub cancel: Option<bool>,
+}
+
+/// Wallets funds can be transferred between
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Wallet {
+    /// Spot wallet
+    #[serde(rename = "Spot Wallet")]
+    Spot,
+    
+    /// Futures wallet
+    #[serde(rename = "Futures Wallet")]
+    Futures,
+}
+
+impl fmt::Display for Wallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Wallet::Spot => "Spot Wallet",
+            Wallet::Futures => "Futures Wallet",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Wallet transfer response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletTransferResponse {
+    /// Reference ID of the transfer
+    pub refid: String,
+}
+
+/// Staking reward rate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StakingReward {
+    /// Reward earned while staking
+    pub reward: String,
+    
+    /// Reward type (e.g. `percentage`)
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+/// Minimum amounts for staking and unstaking
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StakingMinimumAmount {
+    /// Minimum amount that can be staked
+    pub staking: String,
+    
+    /// Minimum amount that can be unstaked
+    pub unstaking: String,
+}
+
+/// Asset that can be staked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StakeableAsset {
+    /// Staking method to pass to `Stake`
+    pub method: String,
+    
+    /// Asset code
+    pub asset: String,
+    
+    /// Asset code of the staked asset
+    pub staking_asset: String,
+    
+    /// Staking rewards
+    pub rewards: StakingReward,
+    
+    /// Whether staking happens on chain
+    pub on_chain: Option<bool>,
+    
+    /// Whether the asset can be staked
+    pub can_stake: Option<bool>,
+    
+    /// Whether the asset can be unstaked
+    pub can_unstake: Option<bool>,
+    
+    /// Minimum amounts for staking and unstaking
+    pub minimum_amount: Option<StakingMinimumAmount>,
+}
+
+/// Stake or unstake response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StakingResponse {
+    /// Reference ID of the staking transaction
+    pub refid: String,
+}
+
+/// Staking transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StakingTransaction {
+    /// Reference ID of the transaction
+    pub refid: String,
+    
+    /// Staking method
+    pub method: String,
+    
+    /// Asset class
+    pub aclass: String,
+    
+    /// Asset code
+    pub asset: String,
+    
+    /// Transaction amount
+    pub amount: String,
+    
+    /// Transaction fee
+    pub fee: String,
+    
+    /// Unix timestamp of the transaction
+    pub time: i64,
+    
+    /// Transaction status (`Initial`, `Pending`, `Settled`, `Success` or `Failure`)
+    pub status: String,
+    
+    /// Transaction type (`bonding`, `reward` or `unbonding`)
+    #[serde(rename = "type")]
+    pub type_: String,
+    
+    /// Unix timestamp from which the funds are staked
+    pub bond_start: Option<i64>,
+    
+    /// Unix timestamp at which the funds become available again
+    pub bond_expires: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_balance_from_values() {
+        let values: HashMap<String, Value> = serde_json::from_value(json!({
+            "XXBT": "1.2500000000",
+            "ZUSD": 100.5,
+            "XETH": {"balance": "3.0000000000", "hold_trade": "1.0000000000"},
+            "DOT": {"b": "12.5"},
+            "ADA": {"unexpected": true},
+        })).unwrap();
+        
+        let balance = balance_from_values(values);
+        assert_eq!(balance["XXBT"], "1.2500000000");
+        assert_eq!(balance["ZUSD"], "100.5");
+        assert_eq!(balance["XETH"], "3.0000000000");
+        assert_eq!(balance["DOT"], "12.5");
+        assert!(!balance.contains_key("ADA"));
+        
+        let entry: BalanceExEntry = serde_json::from_value(json!({"b": "2.0", "hold_trade": "0.5", "uv": "1.0"})).unwrap();
+        assert_eq!(entry.balance, "2.0");
+        assert_eq!(entry.uv.as_deref(), Some("1.0"));
+    }
+    
+    fn position(cost: &str, vol: &str, vol_closed: &str, value: Option<&str>) -> OpenPosition {
+        serde_json::from_value(json!({
+            "ordertxid": "OQCLML-BW3P3-BUCMWZ",
+            "posstatus": "open",
+            "pair": "XXBTZUSD",
+            "time": 1605280097.8294,
+            "type": "buy",
+            "ordertype": "limit",
+            "cost": cost,
+            "fee": "0.0",
+            "vol": vol,
+            "vol_closed": vol_closed,
+            "margin": "2000.0",
+            "value": value,
+            "misc": "",
+            "oflags": ""
+        })).unwrap()
+    }
+    
+    #[test]
+    fn test_account_snapshot() {
+        let balance: TradeBalance = serde_json::from_value(json!({
+            "eb": "10000.0", "tb": "10000.0", "m": "2000.0", "n": "500.0",
+            "c": "10000.0", "v": "10500.0", "e": "10500.0", "mf": "8500.0", "ml": "525.0"
+        })).unwrap();
+        
+        let mut positions = OpenPositions::new();
+        positions.insert("TF5GVO-T7ZZ2-6NBKBI".to_string(), position("10000.0", "1.0", "0.5", None));
+        positions.insert("T24DOR-TAFLM-ID3NYP".to_string(), position("3000.0", "0.1", "0.0", Some("3100.0")));
+        
+        let snapshot = AccountSnapshot::new(&balance, &positions).unwrap();
+        assert_eq!(snapshot.equity, Decimal::from(10500));
+        assert_eq!(snapshot.used_margin, Decimal::from(2000));
+        assert_eq!(snapshot.free_margin, Decimal::from(8500));
+        assert_eq!(snapshot.free_margin_percent.unwrap().round_dp(4), Decimal::new(809524, 4));
+        assert_eq!(snapshot.exposure, Decimal::from(5000 + 3100));
+        
+        // Amounts are exact, without float noise
+        let small_balance = TradeBalance { e: "0.3".to_string(), mf: "0.1".to_string(), ..balance.clone() };
+        let small_positions: OpenPositions = [("TF5GVO-T7ZZ2-6NBKBI".to_string(), position("0.2", "1.0", "0.0", None))].into_iter().collect();
+        let snapshot = AccountSnapshot::new(&small_balance, &small_positions).unwrap();
+        assert_eq!(snapshot.exposure + snapshot.free_margin, snapshot.equity);
+        
+        positions.insert("TYMRFG-URRG5-2ZTQSD".to_string(), position("abc", "1.0", "0.0", None));
+        let error = AccountSnapshot::new(&balance, &positions).unwrap_err();
+        assert!(error.to_string().contains("cost of position TYMRFG-URRG5-2ZTQSD"));
+    }
+    
+    #[test]
+    fn test_type_field_names() {
+        let entry: LedgerEntry = serde_json::from_value(json!({
+            "refid": "TJKLXX-PGMUI-4NTLXU", "time": 1688464484.1787, "type": "trade", "aclass": "currency",
+            "asset": "ZGBP", "amount": "-24.5000", "fee": "0.0490", "balance": "459567.9171"
+        })).unwrap();
+        assert_eq!(entry.type_, "trade");
+        assert_eq!(serde_json::to_value(&entry).unwrap()["type"], "trade");
+        
+        let trade: TradeHistoryEntry = serde_json::from_value(json!({
+            "ordertxid": "OQCLML-BW3P3-BUCMWZ", "postxid": "TKH2SE-M7IF5-CFI7LT", "pair": "XXBTZUSD",
+            "time": 1688667796.8802, "type": "sell", "ordertype": "limit", "price": "30010.00000",
+            "cost": "600.20000", "fee": "0.00000", "vol": "0.02000000", "margin": "0.00000", "misc": ""
+        })).unwrap();
+        assert_eq!(trade.type_, "sell");
+    }
+}
@@ -1,9 +1,16 @@
 //! Market data models for the Kraken API
 
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::fmt;
+
+use crate::error::{Error, Result};
 
 /// Ticker information
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Ticker {
     /// Ask array(<price>, <whole lot volume>, <lot volume>)
     pub a: Vec<String>,
@@ -33,6 +40,36 @@ pub struct Ticker {
     pub o: String,
 }
 
+impl Ticker {
+    /// Get the last trade price
+    pub fn last_price(&self) -> Result<Decimal> {
+        ticker_value("last trade price", &self.c, 0)
+    }
+    
+    /// Get the best ask price
+    pub fn best_ask(&self) -> Result<Decimal> {
+        ticker_value("ask price", &self.a, 0)
+    }
+    
+    /// Get the best bid price
+    pub fn best_bid(&self) -> Result<Decimal> {
+        ticker_value("bid price", &self.b, 0)
+    }
+    
+    /// Get the volume traded over the last 24 hours
+    pub fn volume_24h(&self) -> Result<Decimal> {
+        ticker_value("24 hour volume", &self.v, 1)
+    }
+}
+
+/// Parse one entry of a ticker array, which may be shorter than documented for some pairs
+///
+/// Values are parsed as exact decimals, so `NaN` and `inf` are rejected.
+fn ticker_value(name: &str, values: &[String], index: usize) -> Result<Decimal> {
+    let value = values.get(index).ok_or_else(|| Error::Other(format!("Ticker has no {}", name)))?;
+    value.parse().map_err(|_| Error::Other(format!("Invalid {}: {:?}", name, value)))
+}
+
 /// Orderbook entry
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OrderbookEntry {
@@ -42,22 +79,95 @@ pub struct OrderbookEntry {
     /// Volume
     pub volume: String,
     
-    /// Timestamp
-    pub timestamp: Option<f64>,
+    /// Timestamp, kept as sent so WebSocket book levels round-trip losslessly
+    pub timestamp: Option<String>,
+}
+
+impl OrderbookEntry {
+    /// Parse a `[price, volume, timestamp]` level from a REST depth or WebSocket book message
+    ///
+    /// REST depth sends the timestamp as a number while the WebSocket book sends
+    /// it as a string; both are stored as text.
+    pub fn from_level(level: &Value) -> Option<Self> {
+        let arr = level.as_array()?;
+        
+        if arr.len() < 2 {
+            return None;
+        }
+        
+        let timestamp = match arr.get(2) {
+            Some(Value::String(s)) => Some(s.clone()),
+            Some(Value::Number(n)) => Some(n.to_string()),
+            _ => None,
+        };
+        
+        Some(Self {
+            price: arr[0].as_str().unwrap_or("0").to_string(),
+            volume: arr[1].as_str().unwrap_or("0").to_string(),
+            timestamp,
+        })
+    }
+    
+    /// Parse the timestamp as a number
+    pub fn timestamp_value(&self) -> Option<f64> {
+        self.timestamp.as_ref()?.parse().ok()
+    }
+    
+    /// Parse the price as an exact decimal
+    fn price_value(&self) -> Option<Decimal> {
+        self.price.parse().ok()
+    }
 }
 
 /// Orderbook
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Orderbook {
-    /// Ask side
+    /// Ask side, sorted by ascending price
     pub asks: Vec<OrderbookEntry>,
     
-    /// Bid side
+    /// Bid side, sorted by descending price
     pub bids: Vec<OrderbookEntry>,
 }
 
+impl Orderbook {
+    /// Create a new orderbook, sorting asks ascending and bids descending by price
+    ///
+    /// Levels whose price can't be parsed are kept at the end of their side, so they
+    /// are never taken as the best bid or ask.
+    pub fn new(mut asks: Vec<OrderbookEntry>, mut bids: Vec<OrderbookEntry>) -> Self {
+        asks.sort_by_cached_key(|entry| {
+            let price = entry.price_value();
+            (price.is_none(), price)
+        });
+        bids.sort_by_cached_key(|entry| {
+            let price = entry.price_value();
+            (price.is_none(), Reverse(price))
+        });
+        
+        Self { asks, bids }
+    }
+    
+    /// Get the highest bid
+    pub fn best_bid(&self) -> Option<&OrderbookEntry> {
+        self.bids.first()
+    }
+    
+    /// Get the lowest ask
+    pub fn best_ask(&self) -> Option<&OrderbookEntry> {
+        self.asks.first()
+    }
+    
+    /// Get the difference between the lowest ask and the highest bid
+    pub fn spread(&self) -> Option<f64> {
+        let ask = self.best_ask()?.price.parse::<f64>().ok()?;
+        let bid = self.best_bid()?.price.parse::<f64>().ok()?;
+        
+        Some(ask - bid)
+    }
+}
+
 /// Trade information
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
     /// Price
     pub price: String,
@@ -78,8 +188,150 @@ pub struct Trade {
     pub misc: String,
 }
 
+/// Bid/ask spread at a point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spread {
+    /// Time
+    pub time: i64,
+    
+    /// Best bid
+    pub bid: String,
+    
+    /// Best ask
+    pub ask: String,
+}
+
+impl Spread {
+    /// Parse the bid as a number
+    pub fn bid_value(&self) -> Option<f64> {
+        self.bid.parse().ok()
+    }
+    
+    /// Parse the ask as a number
+    pub fn ask_value(&self) -> Option<f64> {
+        self.ask.parse().ok()
+    }
+    
+    /// Get the difference between the ask and the bid
+    pub fn absolute(&self) -> Option<f64> {
+        Some(self.ask_value()? - self.bid_value()?)
+    }
+    
+    /// Get the spread as a percentage of the mid price
+    pub fn percent(&self) -> Option<f64> {
+        let (bid, ask) = (self.bid_value()?, self.ask_value()?);
+        let mid = (bid + ask) / 2.0;
+        
+        if mid == 0.0 {
+            return None;
+        }
+        
+        Some((ask - bid) / mid * 100.0)
+    }
+}
+
+/// Statistics over a window of spreads, sorted by ascending time
+pub trait SpreadStats {
+    /// Get the absolute spread of the latest entry
+    fn current_spread(&self) -> Option<f64>;
+    
+    /// Get the percentage spread of the latest entry
+    fn current_spread_percent(&self) -> Option<f64>;
+    
+    /// Get the average absolute spread, weighting each entry by how long it lasted
+    ///
+    /// Each entry lasts until the next one, so the latest entry carries no weight.
+    /// Returns `None` if the window spans no time.
+    fn time_weighted_average_spread(&self) -> Option<f64>;
+}
+
+impl SpreadStats for [Spread] {
+    fn current_spread(&self) -> Option<f64> {
+        self.last()?.absolute()
+    }
+    
+    fn current_spread_percent(&self) -> Option<f64> {
+        self.last()?.percent()
+    }
+    
+    fn time_weighted_average_spread(&self) -> Option<f64> {
+        let mut weighted = 0.0;
+        let mut duration = 0.0;
+        
+        for pair in self.windows(2) {
+            if let Some(spread) = pair[0].absolute() {
+                let elapsed = (pair[1].time - pair[0].time) as f64;
+                weighted += spread * elapsed;
+                duration += elapsed;
+            }
+        }
+        
+        if duration > 0.0 {
+            Some(weighted / duration)
+        } else {
+            None
+        }
+    }
+}
+
+/// Data returned by an endpoint that is resumed with a `since` cursor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Paginated<T, C = u64> {
+    /// Returned data
+    pub data: T,
+    
+    /// Kraken's `last` cursor
+    pub cursor: C,
+}
+
+impl<T, C: Clone> Paginated<T, C> {
+    /// Value to pass as `since` to fetch the data following this page
+    pub fn next_since(&self) -> C {
+        self.cursor.clone()
+    }
+}
+
+/// Position in the trades of a pair, passed as `since` to fetch later trades
+///
+/// Either a trade ID returned as a page's `last` cursor, or a timestamp in
+/// nanoseconds. Kept as Kraken's string so it round-trips exactly.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TradeCursor(pub String);
+
+impl TradeCursor {
+    /// Get the cursor as sent to Kraken
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for TradeCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for TradeCursor {
+    fn from(cursor: String) -> Self {
+        Self(cursor)
+    }
+}
+
+impl From<&str> for TradeCursor {
+    fn from(cursor: &str) -> Self {
+        Self(cursor.to_string())
+    }
+}
+
+impl From<u64> for TradeCursor {
+    fn from(cursor: u64) -> Self {
+        Self(cursor.to_string())
+    }
+}
+
 /// OHLC (Open, High, Low, Close) candle
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OHLC {
     /// Time
     pub time: i64,
@@ -106,8 +358,81 @@ pub struct OHLC {
     pub count: i64,
 }
 
+/// Candle interval accepted by the OHLC endpoint and WebSocket subscriptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interval {
+    /// 1 minute
+    Min1,
+    
+    /// 5 minutes
+    Min5,
+    
+    /// 15 minutes
+    Min15,
+    
+    /// 30 minutes
+    Min30,
+    
+    /// 1 hour
+    Hour1,
+    
+    /// 4 hours
+    Hour4,
+    
+    /// 1 day
+    Day1,
+    
+    /// 1 week
+    Week1,
+    
+    /// 15 days
+    Day15,
+}
+
+impl Interval {
+    /// Get the interval length in minutes
+    pub fn as_minutes(&self) -> u32 {
+        match self {
+            Interval::Min1 => 1,
+            Interval::Min5 => 5,
+            Interval::Min15 => 15,
+            Interval::Min30 => 30,
+            Interval::Hour1 => 60,
+            Interval::Hour4 => 240,
+            Interval::Day1 => 1440,
+            Interval::Week1 => 10080,
+            Interval::Day15 => 21600,
+        }
+    }
+}
+
+impl TryFrom<u32> for Interval {
+    type Error = Error;
+    
+    fn try_from(minutes: u32) -> std::result::Result<Self, Self::Error> {
+        match minutes {
+            1 => Ok(Interval::Min1),
+            5 => Ok(Interval::Min5),
+            15 => Ok(Interval::Min15),
+            30 => Ok(Interval::Min30),
+            60 => Ok(Interval::Hour1),
+            240 => Ok(Interval::Hour4),
+            1440 => Ok(Interval::Day1),
+            10080 => Ok(Interval::Week1),
+            21600 => Ok(Interval::Day15),
+            _ => Err(Error::Other(format!("Invalid interval: {} minutes", minutes))),
+        }
+    }
+}
+
+impl From<Interval> for u32 {
+    fn from(interval: Interval) -> Self {
+        interval.as_minutes()
+    }
+}
+
 /// Asset information
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssetInfo {
     /// Alternate name
     pub altname: String,
@@ -122,60 +447,194 @@ pub struct AssetInfo {
     pub display_decimals: i64,
 }
 
+/// Level of detail requested from the asset pairs endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetPairInfo {
+    /// All info
+    Info,
+    
+    /// Leverage info
+    Leverage,
+    
+    /// Fees schedule
+    Fees,
+    
+    /// Margin info
+    Margin,
+}
+
+impl fmt::Display for AssetPairInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            AssetPairInfo::Info => "info",
+            AssetPairInfo::Leverage => "leverage",
+            AssetPairInfo::Fees => "fees",
+            AssetPairInfo::Margin => "margin",
+        };
+        f.write_str(s)
+    }
+}
+
 /// Asset pair information
-#[derive(Debug, Clone, Deserialize)]
+///
+/// Fields are optional because the asset pairs endpoint only returns the subset
+/// of fields matching the requested [`AssetPairInfo`] level.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssetPair {
     /// Alternate pair name
-    pub altname: String,
+    pub altname: Option<String>,
     
     /// WebSocket pair name (if available)
     pub wsname: Option<String>,
     
     /// Asset class of base component
-    pub aclass_base: String,
+    pub aclass_base: Option<String>,
     
     /// Asset id of base component
-    pub base: String,
+    pub base: Option<String>,
     
     /// Asset class of quote component
-    pub aclass_quote: String,
+    pub aclass_quote: Option<String>,
     
     /// Asset id of quote component
-    pub quote: String,
+    pub quote: Option<String>,
     
     /// Lot multiplier
-    pub lot: String,
+    pub lot: Option<String>,
     
     /// Scaling decimal places for pair
-    pub pair_decimals: i64,
+    pub pair_decimals: Option<i64>,
     
     /// Scaling decimal places for volume
-    pub lot_decimals: i64,
+    pub lot_decimals: Option<i64>,
     
     /// Amount to multiply lot volume by to get currency volume
-    pub lot_multiplier: i64,
+    pub lot_multiplier: Option<i64>,
+    
+    /// Leverage amounts available when buying
+    pub leverage_buy: Option<Vec<i64>>,
     
-    /// Fee schedule array in [<volume>, <percent fee>] tuples
-    pub fees: Vec<Vec<f64>>,
+    /// Leverage amounts available when selling
+    pub leverage_sell: Option<Vec<i64>>,
     
-    /// Maker fee schedule array in [<volume>, <percent fee>] tuples (if on maker-taker)
-    pub fees_maker: Option<Vec<Vec<f64>>>,
+    /// Taker fee schedule
+    pub fees: Option<Vec<FeeTier>>,
+    
+    /// Maker fee schedule (if on maker-taker)
+    pub fees_maker: Option<Vec<FeeTier>>,
     
     /// Volume discount currency
-    pub fee_volume_currency: String,
+    pub fee_volume_currency: Option<String>,
     
     /// Margin call level
-    pub margin_call: i64,
+    pub margin_call: Option<i64>,
     
     /// Stop-out/liquidation margin level
-    pub margin_stop: i64,
+    pub margin_stop: Option<i64>,
     
     /// Minimum order size
     pub ordermin: Option<String>,
 }
 
+impl AssetPair {
+    /// Get the taker fee percentage for a 30-day trading volume
+    ///
+    /// Returns `None` if the pair has no fee schedule, e.g. because it wasn't requested
+    /// with [`AssetPairInfo::Fees`] or [`AssetPairInfo::Info`].
+    pub fn taker_fee(&self, volume_30d: f64) -> Option<f64> {
+        self.fees.as_deref().and_then(|fees| fee_for_volume(fees, volume_30d))
+    }
+    
+    /// Get the maker fee percentage for a 30-day trading volume
+    ///
+    /// Returns `None` if the pair has no maker fee schedule.
+    pub fn maker_fee(&self, volume_30d: f64) -> Option<f64> {
+        self.fees_maker.as_deref().and_then(|fees| fee_for_volume(fees, volume_30d))
+    }
+    
+    /// Format a price with the pair's `pair_decimals`, truncating extra digits
+    ///
+    /// Values are formatted unchanged if the pair has no `pair_decimals`. Fails for
+    /// NaN, infinite or out-of-range values.
+    pub fn format_price(&self, price: f64) -> Result<String> {
+        truncate_decimals(price, self.pair_decimals)
+    }
+    
+    /// Format a volume with the pair's `lot_decimals`, truncating extra digits
+    ///
+    /// Values are formatted unchanged if the pair has no `lot_decimals`. Fails for
+    /// NaN, infinite or out-of-range values.
+    pub fn format_volume(&self, volume: f64) -> Result<String> {
+        truncate_decimals(volume, self.lot_decimals)
+    }
+    
+    /// Check whether a volume is at least the pair's minimum order size
+    ///
+    /// Returns `true` if the pair has no known `ordermin`.
+    pub fn meets_ordermin(&self, volume: f64) -> bool {
+        match self.ordermin.as_deref().and_then(|min| min.parse::<f64>().ok()) {
+            Some(min) => volume >= min,
+            None => true,
+        }
+    }
+}
+
+/// Format a value with at most `decimals` decimal places, truncating toward zero
+///
+/// The value is converted to the shortest decimal that round-trips, so binary float
+/// noise (e.g. `0.3` stored as `0.29999...`) doesn't truncate a digit away.
+fn truncate_decimals(value: f64, decimals: Option<i64>) -> Result<String> {
+    let amount = Decimal::from_f64(value)
+        .ok_or_else(|| Error::Other(format!("Invalid amount {}: not a finite decimal", value)))?;
+    
+    let decimals = match decimals {
+        Some(decimals) => decimals.clamp(0, 28) as u32,
+        None => return Ok(value.to_string()),
+    };
+    
+    Ok(format!("{:.*}", decimals as usize, amount.trunc_with_scale(decimals)))
+}
+
+/// Fee schedule tier, sent by Kraken as a `[<volume>, <percent fee>]` array
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "(f64, f64)", into = "(f64, f64)")]
+pub struct FeeTier {
+    /// 30-day volume from which the tier applies
+    pub volume: f64,
+    
+    /// Fee percentage
+    pub percent: f64,
+}
+
+impl From<(f64, f64)> for FeeTier {
+    fn from((volume, percent): (f64, f64)) -> Self {
+        Self { volume, percent }
+    }
+}
+
+impl From<FeeTier> for (f64, f64) {
+    fn from(tier: FeeTier) -> Self {
+        (tier.volume, tier.percent)
+    }
+}
+
+/// Find the fee of the highest volume tier not exceeding `volume`
+///
+/// Volumes below the first tier fall back to the first tier's fee.
+fn fee_for_volume(schedule: &[FeeTier], volume: f64) -> Option<f64> {
+    let first = schedule.first()?;
+    
+    let tier = schedule.iter()
+        .filter(|tier| tier.volume <= volume)
+        .max_by(|a, b| a.volume.total_cmp(&b.volume))
+        .unwrap_or(first);
+    
+    Some(tier.percent)
+}
+
 /// Server time
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerTime {
     /// Unix timestamp
     pub unixtime: i64,
@@ -183,3 +642,245 @@ pub struct ServerTime {
     /// RFC 1123 time format
     pub rfc1123: String,
 }
+
+/// Exchange system status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemStatusType {
+    /// Kraken is operating normally
+    Online,
+    
+    /// Kraken is offline for maintenance
+    Maintenance,
+    
+    /// Only order cancellations are accepted
+    CancelOnly,
+    
+    /// Only post-only limit orders are accepted
+    PostOnly,
+}
+
+/// System status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemStatus {
+    /// Current system status
+    pub status: SystemStatusType,
+    
+    /// Current timestamp (RFC3339)
+    pub timestamp: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_ticker_accessors() {
+        let ticker: Ticker = serde_json::from_value(serde_json::json!({
+            "a": ["30300.10000", "1", "1.000"],
+            "b": ["30300.00000", "1", "1.000"],
+            "c": ["30303.20000", "0.00067643"],
+            "v": ["4083.67001100"],
+            "p": ["30706.77771", "30689.13205"],
+            "t": [34619, 38907],
+            "l": ["29868.30000", "29868.30000"],
+            "h": ["31631.00000", "31631.00000"],
+            "o": "30502.80000"
+        })).unwrap();
+        
+        assert_eq!(ticker.last_price().unwrap(), Decimal::new(303032, 1));
+        assert_eq!(ticker.last_price().unwrap().to_string(), "30303.20000");
+        assert_eq!(ticker.best_ask().unwrap(), Decimal::new(303001, 1));
+        assert_eq!(ticker.best_bid().unwrap(), Decimal::from(30300));
+        assert_eq!(ticker.volume_24h().unwrap_err().to_string(), "Other error: Ticker has no 24 hour volume");
+        
+        let invalid = Ticker { c: vec!["n/a".to_string()], ..ticker.clone() };
+        assert_eq!(invalid.last_price().unwrap_err().to_string(), "Other error: Invalid last trade price: \"n/a\"");
+        
+        for non_finite in ["NaN", "inf"] {
+            let invalid = Ticker { a: vec![non_finite.to_string()], ..ticker.clone() };
+            assert!(invalid.best_ask().is_err(), "{:?} should be rejected", non_finite);
+        }
+    }
+    
+    fn entry(price: &str) -> OrderbookEntry {
+        OrderbookEntry {
+            price: price.to_string(),
+            volume: "1.0".to_string(),
+            timestamp: None,
+        }
+    }
+    
+    #[test]
+    fn test_orderbook_sorting_and_spread() {
+        let orderbook = Orderbook::new(
+            vec![entry("30010.0"), entry("30001.5"), entry("30100.0")],
+            vec![entry("29990.0"), entry("30000.0"), entry("9999.0")],
+        );
+        
+        assert_eq!(orderbook.best_ask().unwrap().price, "30001.5");
+        assert_eq!(orderbook.best_bid().unwrap().price, "30000.0");
+        assert_eq!(orderbook.bids.last().unwrap().price, "9999.0");
+        assert_eq!(orderbook.spread(), Some(1.5));
+        
+        let empty = Orderbook::new(Vec::new(), vec![entry("30000.0")]);
+        assert!(empty.best_ask().is_none());
+        assert!(empty.spread().is_none());
+        
+        // Unparseable prices go last on both sides
+        let orderbook = Orderbook::new(
+            vec![entry("abc"), entry("30010.0"), entry("NaN"), entry("30001.5")],
+            vec![entry(""), entry("29990.0"), entry("30000.0")],
+        );
+        assert_eq!(orderbook.best_ask().unwrap().price, "30001.5");
+        assert_eq!(orderbook.asks[1].price, "30010.0");
+        assert_eq!(orderbook.best_bid().unwrap().price, "30000.0");
+        assert_eq!(orderbook.bids.last().unwrap().price, "");
+    }
+    
+    #[test]
+    fn test_orderbook_entry_from_rest_level() {
+        let level = serde_json::json!(["30000.10000", "1.500", 1688671200]);
+        let entry = OrderbookEntry::from_level(&level).unwrap();
+        
+        assert_eq!(entry.price, "30000.10000");
+        assert_eq!(entry.volume, "1.500");
+        assert_eq!(entry.timestamp.as_deref(), Some("1688671200"));
+        assert_eq!(entry.timestamp_value(), Some(1688671200.0));
+        
+        let bare = OrderbookEntry::from_level(&serde_json::json!(["30000.1", "1.5"])).unwrap();
+        assert!(bare.timestamp.is_none());
+        assert!(OrderbookEntry::from_level(&serde_json::json!(["30000.1"])).is_none());
+    }
+    
+    #[test]
+    fn test_orderbook_entry_from_websocket_level() {
+        let level = serde_json::json!(["5541.30000", "2.50700000", "1534614248.123678"]);
+        let entry = OrderbookEntry::from_level(&level).unwrap();
+        
+        assert_eq!(entry.timestamp.as_deref(), Some("1534614248.123678"));
+        assert_eq!(entry.timestamp_value(), Some(1534614248.123678));
+        
+        let json = serde_json::to_string(&entry).unwrap();
+        let round_trip: OrderbookEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_trip.timestamp.as_deref(), Some("1534614248.123678"));
+    }
+    
+    fn asset_pair(fees: Option<Vec<FeeTier>>, fees_maker: Option<Vec<FeeTier>>) -> AssetPair {
+        let mut pair: AssetPair = serde_json::from_value(serde_json::json!({})).unwrap();
+        pair.fees = fees;
+        pair.fees_maker = fees_maker;
+        pair
+    }
+    
+    #[test]
+    fn test_fee_tiers() {
+        let pair: AssetPair = serde_json::from_value(serde_json::json!({
+            "fees": [[0, 0.26], [50000, 0.24], [100000, 0.22]],
+            "fees_maker": [[0, 0.16], [50000, 0.14], [100000, 0.12]],
+        })).unwrap();
+        
+        assert_eq!(pair.fees.as_ref().unwrap()[1], FeeTier { volume: 50000.0, percent: 0.24 });
+        assert_eq!(serde_json::to_value(pair.fees.as_ref().unwrap()).unwrap()[2], serde_json::json!([100000.0, 0.22]));
+        
+        assert_eq!(pair.taker_fee(0.0), Some(0.26));
+        assert_eq!(pair.taker_fee(49999.0), Some(0.26));
+        assert_eq!(pair.taker_fee(50000.0), Some(0.24));
+        assert_eq!(pair.taker_fee(1_000_000.0), Some(0.22));
+        assert_eq!(pair.maker_fee(75000.0), Some(0.14));
+    }
+    
+    #[test]
+    fn test_precision_formatting() {
+        let mut pair = asset_pair(None, None);
+        pair.pair_decimals = Some(1);
+        pair.lot_decimals = Some(8);
+        pair.ordermin = Some("0.0001".to_string());
+        
+        assert_eq!(pair.format_price(30000.19).unwrap(), "30000.1");
+        assert_eq!(pair.format_price(30000.0).unwrap(), "30000.0");
+        assert_eq!(pair.format_volume(0.3).unwrap(), "0.30000000");
+        assert_eq!(pair.format_volume(1.234567899).unwrap(), "1.23456789");
+        assert_eq!(pair.format_volume(-1.234567899).unwrap(), "-1.23456789");
+        
+        // Digits past the precision are dropped, never rounded up
+        pair.pair_decimals = Some(2);
+        assert_eq!(pair.format_price(0.1999999999).unwrap(), "0.19");
+        
+        pair.pair_decimals = Some(0);
+        assert_eq!(pair.format_price(30000.99).unwrap(), "30000");
+        
+        // Non-finite values are rejected instead of panicking
+        assert!(pair.format_price(f64::NAN).is_err());
+        assert!(pair.format_price(f64::INFINITY).is_err());
+        assert!(pair.format_volume(f64::NEG_INFINITY).is_err());
+        
+        pair.pair_decimals = None;
+        assert_eq!(pair.format_price(30000.19).unwrap(), "30000.19");
+        assert!(pair.format_price(f64::NAN).is_err());
+        
+        assert!(pair.meets_ordermin(0.0001));
+        assert!(!pair.meets_ordermin(0.00009));
+        
+        pair.ordermin = None;
+        assert!(pair.meets_ordermin(0.0));
+    }
+    
+    fn spread(time: i64, bid: &str, ask: &str) -> Spread {
+        Spread { time, bid: bid.to_string(), ask: ask.to_string() }
+    }
+    
+    #[test]
+    fn test_spread_stats() {
+        let page = Paginated {
+            data: vec![
+                spread(100, "29999.0", "30001.0"),
+                spread(130, "29999.0", "30005.0"),
+                spread(140, "29998.0", "30002.0"),
+            ],
+            cursor: 140,
+        };
+        let spreads = &page.data;
+        
+        assert_eq!(spreads.current_spread(), Some(4.0));
+        assert!((spreads.current_spread_percent().unwrap() - 4.0 / 30000.0 * 100.0).abs() < 1e-9);
+        
+        // 2.0 for 30s and 6.0 for 10s
+        assert_eq!(spreads.time_weighted_average_spread(), Some(3.0));
+        
+        assert!(spreads[..1].time_weighted_average_spread().is_none());
+        assert!(Vec::<Spread>::new().current_spread().is_none());
+    }
+    
+    #[test]
+    fn test_fee_edge_cases() {
+        let single = asset_pair(Some(vec![FeeTier { volume: 0.0, percent: 0.26 }]), None);
+        assert_eq!(single.taker_fee(1_000_000.0), Some(0.26));
+        assert_eq!(single.maker_fee(1_000_000.0), None);
+        
+        let empty = asset_pair(Some(Vec::new()), Some(Vec::new()));
+        assert_eq!(empty.taker_fee(100.0), None);
+        assert_eq!(empty.maker_fee(100.0), None);
+        
+        let missing = asset_pair(None, None);
+        assert_eq!(missing.taker_fee(100.0), None);
+    }
+    
+    #[test]
+    fn test_ticker_round_trip() {
+        let json = serde_json::json!({
+            "a": ["30000.0", "1", "1.000"],
+            "b": ["29999.0", "1", "1.000"],
+            "c": ["30000.0", "0.1"],
+            "v": ["100.0", "200.0"],
+            "p": ["29900.0", "29800.0"],
+            "t": [1000, 2000],
+            "l": ["29000.0", "28000.0"],
+            "h": ["31000.0", "32000.0"],
+            "o": "29500.0"
+        });
+        
+        let ticker: Ticker = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&ticker).unwrap(), json);
+    }
+}
@@ -6,7 +6,7 @@ pub mod trading;
 pub mod websocket;
 
 // Re-export commonly used types
-pub use market::{Ticker, Orderbook, Trade, OHLC};
-pub use account::{Balance, TradeBalance, OpenOrders, ClosedOrders};
-pub use trading::{OrderType, OrderSide, OrderStatus, Order, OrderInfo, TradeInfo};
+pub use market::{Ticker, Orderbook, Trade, TradeCursor, Spread, SpreadStats, OHLC, Interval, Paginated};
+pub use account::{AccountSnapshot, Balance, BalanceEx, BalanceExEntry, TradeBalance, OpenOrders, OpenOrderWithTrades, OpenOrdersWithTrades, ClosedOrders, Page, PagedResult, PAGE_SIZE};
+pub use trading::{OrderType, OrderSide, OrderStatus, TriggerType, Price, NonNegative, Order, OrderBuilder, BatchOrder, CancelTarget, CancelResult, OrderInfo, TradeInfo};
 pub use websocket::{WebSocketMessage, WebSocketSubscription};
@@ -1,11 +1,27 @@
 //! Trading models for the Kraken API
 
+use chrono::{DateTime, SecondsFormat, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use uuid::Uuid;
+use crate::error::{Error, Result};
 use crate::models::account::OrderDescription;
 
+/// Serialize a user reference ID in the string form Kraken expects
+fn serialize_userref<S: serde::Serializer>(userref: &Option<i32>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match userref {
+        Some(userref) => serializer.serialize_str(&userref.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
 /// Order types
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+///
+/// Order types Kraken adds later deserialize as [`OrderType::Other`] instead of failing.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum OrderType {
     /// Market order
     Market,
@@ -27,22 +43,67 @@ pub enum OrderType {
     
     /// Settle-position order
     SettlePosition,
+    
+    /// Trailing-stop order
+    TrailingStop,
+    
+    /// Trailing-stop-limit order
+    TrailingStopLimit,
+    
+    /// Order type not known to this crate
+    Other(String),
 }
 
-impl ToString for OrderType {
-    fn to_string(&self) -> String {
-        match self {
-            OrderType::Market => "market".to_string(),
-            OrderType::Limit => "limit".to_string(),
-            OrderType::StopLoss => "stop-loss".to_string(),
-            OrderType::TakeProfit => "take-profit".to_string(),
-            OrderType::StopLossLimit => "stop-loss-limit".to_string(),
-            OrderType::TakeProfitLimit => "take-profit-limit".to_string(),
-            OrderType::SettlePosition => "settle-position".to_string(),
+impl OrderType {
+    /// Parse an order type name, keeping unknown names as [`OrderType::Other`]
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "market" => OrderType::Market,
+            "limit" => OrderType::Limit,
+            // The unhyphenated forms are what earlier versions of this crate serialized
+            "stop-loss" | "stoploss" => OrderType::StopLoss,
+            "take-profit" | "takeprofit" => OrderType::TakeProfit,
+            "stop-loss-limit" | "stoplosslimit" => OrderType::StopLossLimit,
+            "take-profit-limit" | "takeprofitlimit" => OrderType::TakeProfitLimit,
+            "settle-position" | "settleposition" => OrderType::SettlePosition,
+            "trailing-stop" => OrderType::TrailingStop,
+            "trailing-stop-limit" => OrderType::TrailingStopLimit,
+            other => OrderType::Other(other.to_string()),
         }
     }
 }
 
+impl Serialize for OrderType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for OrderType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(OrderType::from_name(&name))
+    }
+}
+
+impl fmt::Display for OrderType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            OrderType::Market => "market",
+            OrderType::Limit => "limit",
+            OrderType::StopLoss => "stop-loss",
+            OrderType::TakeProfit => "take-profit",
+            OrderType::StopLossLimit => "stop-loss-limit",
+            OrderType::TakeProfitLimit => "take-profit-limit",
+            OrderType::SettlePosition => "settle-position",
+            OrderType::TrailingStop => "trailing-stop",
+            OrderType::TrailingStopLimit => "trailing-stop-limit",
+            OrderType::Other(name) => name,
+        };
+        f.write_str(s)
+    }
+}
+
 /// Order sides
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -54,12 +115,13 @@ pub enum OrderSide {
     Sell,
 }
 
-impl ToString for OrderSide {
-    fn to_string(&self) -> String {
-        match self {
-            OrderSide::Buy => "buy".to_string(),
-            OrderSide::Sell => "sell".to_string(),
-        }
+impl fmt::Display for OrderSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            OrderSide::Buy => "buy",
+            OrderSide::Sell => "sell",
+        };
+        f.write_str(s)
     }
 }
 
@@ -81,6 +143,10 @@ pub enum OrderStatus {
     
     /// Expired order
     Expired,
+    
+    /// Status not known to this client
+    #[serde(other)]
+    Unknown,
 }
 
 /// Order flags
@@ -102,18 +168,236 @@ pub enum OrderFlag {
     Viqc,
 }
 
-impl ToString for OrderFlag {
-    fn to_string(&self) -> String {
+impl fmt::Display for OrderFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            OrderFlag::Post => "post",
+            OrderFlag::Fcib => "fcib",
+            OrderFlag::Fciq => "fciq",
+            OrderFlag::Nompp => "nompp",
+            OrderFlag::Viqc => "viqc",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for OrderFlag {
+    type Err = Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "post" => Ok(OrderFlag::Post),
+            "fcib" => Ok(OrderFlag::Fcib),
+            "fciq" => Ok(OrderFlag::Fciq),
+            "nompp" => Ok(OrderFlag::Nompp),
+            "viqc" => Ok(OrderFlag::Viqc),
+            _ => Err(Error::Other(format!("Unknown order flag: {}", s))),
+        }
+    }
+}
+
+/// Parse a comma delimited list of order flags
+///
+/// Unknown flags are skipped, so flags added by Kraken later don't cause an error.
+pub fn parse_order_flags(oflags: &str) -> Vec<OrderFlag> {
+    oflags
+        .split(',')
+        .map(str::trim)
+        .filter(|flag| !flag.is_empty())
+        .filter_map(|flag| match flag.parse() {
+            Ok(flag) => Some(flag),
+            Err(_) => {
+                log_debug!("Skipping unknown order flag: {}", flag);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Reference price that triggers stop-loss and take-profit orders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerType {
+    /// Last traded price
+    Last,
+    
+    /// Index price
+    Index,
+}
+
+impl fmt::Display for TriggerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            TriggerType::Last => "last",
+            TriggerType::Index => "index",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Order price, absolute or relative to the last traded price
+///
+/// Converts to the prefixed string Kraken expects, so it can be passed to
+/// [`Order::with_price`] and [`Order::with_price2`]. Amounts are [`NonNegative`], so
+/// the direction of an offset is always given by the variant; build prices with the
+/// checked constructors, such as [`Price::absolute`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Price {
+    /// Absolute price
+    Absolute(NonNegative),
+    
+    /// Amount added to the last traded price (`+`)
+    RelativePlus(NonNegative),
+    
+    /// Amount subtracted from the last traded price (`-`)
+    RelativeMinus(NonNegative),
+    
+    /// Percentage offset from the last traded price, negative to subtract (`+5%`, `-5%`)
+    Percent(Decimal),
+    
+    /// Amount added or subtracted depending on the order side (`#`), e.g. a trailing offset
+    Trailing(NonNegative),
+}
+
+/// Price amount that is zero or positive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NonNegative(Decimal);
+
+impl NonNegative {
+    /// Wrap an amount, rejecting negative values
+    pub fn new(amount: Decimal) -> Result<Self> {
+        non_negative("amount", amount)
+    }
+    
+    /// Get the amount
+    pub fn get(self) -> Decimal {
+        self.0
+    }
+}
+
+impl fmt::Display for NonNegative {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.normalize())
+    }
+}
+
+/// Check that a price amount is not negative
+fn non_negative(name: &str, amount: Decimal) -> Result<NonNegative> {
+    if amount.is_sign_negative() && !amount.is_zero() {
+        return Err(Error::Other(format!("Invalid {} {}: must not be negative", name, amount)));
+    }
+    
+    Ok(NonNegative(amount))
+}
+
+impl Price {
+    /// Create an absolute price, rejecting negative amounts
+    pub fn absolute(price: Decimal) -> Result<Self> {
+        non_negative("price", price).map(Price::Absolute)
+    }
+    
+    /// Create an offset added to the last traded price, rejecting negative amounts
+    pub fn relative_plus(offset: Decimal) -> Result<Self> {
+        non_negative("price offset", offset).map(Price::RelativePlus)
+    }
+    
+    /// Create an offset subtracted from the last traded price, rejecting negative amounts
+    pub fn relative_minus(offset: Decimal) -> Result<Self> {
+        non_negative("price offset", offset).map(Price::RelativeMinus)
+    }
+    
+    /// Create a percentage offset from the last traded price, negative to subtract
+    pub fn percent(percent: Decimal) -> Self {
+        Price::Percent(percent)
+    }
+    
+    /// Create an offset whose direction depends on the order side, rejecting negative amounts
+    pub fn trailing(offset: Decimal) -> Result<Self> {
+        non_negative("trailing offset", offset).map(Price::Trailing)
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            OrderFlag::Post => "post".to_string(),
-            OrderFlag::Fcib => "fcib".to_string(),
-            OrderFlag::Fciq => "fciq".to_string(),
-            OrderFlag::Nompp => "nompp".to_string(),
-            OrderFlag::Viqc => "viqc".to_string(),
+            Price::Absolute(price) => write!(f, "{}", price),
+            Price::RelativePlus(offset) => write!(f, "+{}", offset),
+            Price::RelativeMinus(offset) => write!(f, "-{}", offset),
+            Price::Percent(percent) if percent.is_sign_negative() => write!(f, "-{}%", percent.abs().normalize()),
+            Price::Percent(percent) => write!(f, "+{}%", percent.normalize()),
+            Price::Trailing(offset) => write!(f, "#{}", offset),
         }
     }
 }
 
+impl From<Price> for String {
+    fn from(price: Price) -> Self {
+        price.to_string()
+    }
+}
+
+/// Check that a price string is a number with an optional `+`, `-` or `#` prefix,
+/// and an optional `%` suffix on relative prices
+fn validate_price(name: &str, price: &str) -> Result<()> {
+    let relative = price.strip_prefix(['+', '-', '#']);
+    let amount = relative.unwrap_or(price);
+    let amount = match amount.strip_suffix('%') {
+        Some(amount) if relative.is_some() => amount,
+        Some(_) => return Err(Error::Other(format!("Invalid {} {:?}: a percentage needs a +, - or # prefix", name, price))),
+        None => amount,
+    };
+    
+    let is_number = !amount.is_empty()
+        && amount.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && amount.parse::<f64>().is_ok();
+    
+    if !is_number {
+        return Err(Error::Other(format!("Invalid {} {:?}", name, price)));
+    }
+    
+    Ok(())
+}
+
+/// Check that an order type has the prices it needs, and that they are well formed
+fn validate_prices(ordertype: &OrderType, price: Option<&str>, price2: Option<&str>) -> Result<()> {
+    let requires_price = matches!(
+        ordertype,
+        OrderType::Limit
+            | OrderType::StopLoss
+            | OrderType::TakeProfit
+            | OrderType::StopLossLimit
+            | OrderType::TakeProfitLimit
+            | OrderType::TrailingStop
+            | OrderType::TrailingStopLimit
+    );
+    let requires_price2 = matches!(
+        ordertype,
+        OrderType::StopLossLimit | OrderType::TakeProfitLimit | OrderType::TrailingStopLimit
+    );
+    
+    if requires_price && price.is_none() {
+        return Err(Error::Other(format!("{} order requires a price", ordertype)));
+    }
+    
+    if requires_price2 && price2.is_none() {
+        return Err(Error::Other(format!("{} order requires a secondary price", ordertype)));
+    }
+    
+    if *ordertype == OrderType::Market && price.is_some() {
+        return Err(Error::Other("market order must not have a price".to_string()));
+    }
+    
+    if let Some(price) = price {
+        validate_price("price", price)?;
+    }
+    
+    if let Some(price2) = price2 {
+        validate_price("secondary price", price2)?;
+    }
+    
+    Ok(())
+}
+
 /// Order request
 #[derive(Debug, Clone, Serialize)]
 pub struct Order {
@@ -153,9 +437,25 @@ pub struct Order {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expiretm: Option<String>,
     
+    /// RFC3339 timestamp after which the matching engine rejects the order (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<String>,
+    
+    /// Only reduce an existing margin position, never open or increase one (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reduce_only: Option<bool>,
+    
+    /// Reference price for triggered order types (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<TriggerType>,
+    
     /// User reference ID (optional)
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "serialize_userref")]
+    pub userref: Option<i32>,
+    
+    /// Client order ID, used by Kraken to reject duplicate submissions (optional)
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub userref: Option<String>,
+    pub cl_ord_id: Option<String>,
     
     /// Validate inputs only, do not submit order (optional)
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -175,6 +475,11 @@ pub struct Order {
 }
 
 impl Order {
+    /// Start building an order whose required prices are checked at compile time
+    pub fn builder(pair: impl Into<String>, side: OrderSide, volume: impl Into<String>) -> OrderBuilder<Untyped> {
+        OrderBuilder::new(pair, side, volume)
+    }
+    
     /// Create a new order
     pub fn new(pair: impl Into<String>, side: OrderSide, order_type: OrderType, volume: impl Into<String>) -> Self {
         Self {
@@ -188,7 +493,11 @@ impl Order {
             oflags: None,
             starttm: None,
             expiretm: None,
+            deadline: None,
+            reduce_only: None,
+            trigger: None,
             userref: None,
+            cl_ord_id: None,
             validate: None,
             close_ordertype: None,
             close_price: None,
@@ -196,7 +505,7 @@ impl Order {
         }
     }
     
-    /// Set the price
+    /// Set the price, either as a string or as a [`Price`]
     pub fn with_price(mut self, price: impl Into<String>) -> Self {
         self.price = Some(price.into());
         self
@@ -226,6 +535,11 @@ impl Order {
         self
     }
     
+    /// Parse the order flags set on this order
+    pub fn oflags_parsed(&self) -> Vec<OrderFlag> {
+        self.oflags.as_deref().map(parse_order_flags).unwrap_or_default()
+    }
+    
     /// Set the start time
     pub fn with_start_time(mut self, start_time: impl Into<String>) -> Self {
         self.starttm = Some(start_time.into());
@@ -238,12 +552,54 @@ impl Order {
         self
     }
     
+    /// Set the deadline as an RFC3339 timestamp
+    pub fn with_deadline(mut self, deadline: impl Into<String>) -> Self {
+        self.deadline = Some(deadline.into());
+        self
+    }
+    
+    /// Set the deadline
+    pub fn with_deadline_at(self, deadline: DateTime<Utc>) -> Self {
+        self.with_deadline(deadline.to_rfc3339_opts(SecondsFormat::Millis, true))
+    }
+    
+    /// Set the reduce-only flag
+    pub fn with_reduce_only(mut self, reduce_only: bool) -> Self {
+        self.reduce_only = Some(reduce_only);
+        self
+    }
+    
+    /// Set the trigger reference price
+    pub fn with_trigger(mut self, trigger: TriggerType) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+    
     /// Set the user reference ID
-    pub fn with_user_ref(mut self, user_ref: impl Into<String>) -> Self {
-        self.userref = Some(user_ref.into());
+    pub fn with_user_ref(mut self, user_ref: i32) -> Self {
+        self.userref = Some(user_ref);
+        self
+    }
+    
+    /// Set the client order ID
+    pub fn with_cl_ord_id(mut self, cl_ord_id: impl Into<String>) -> Self {
+        self.cl_ord_id = Some(cl_ord_id.into());
         self
     }
     
+    /// Set a randomly generated client order ID, unless one is already set
+    ///
+    /// Kraken rejects a second order with the same client order ID, so an order sent
+    /// again after a network failure can't be executed twice. Called automatically by
+    /// `add_order` when [`crate::config::Config::with_order_retries`] is set.
+    pub fn with_generated_cl_ord_id(self) -> Self {
+        if self.cl_ord_id.is_some() {
+            return self;
+        }
+        
+        self.with_cl_ord_id(Uuid::new_v4().to_string())
+    }
+    
     /// Set the validate flag
     pub fn with_validate(mut self, validate: bool) -> Self {
         self.validate = Some(validate);
@@ -267,116 +623,970 @@ impl Order {
         self.close_price2 = Some(close_price2.into());
         self
     }
-}
-
-/// Order response
-#[derive(Debug, Clone, Deserialize)]
-pub struct OrderResponse {
-    /// Order description info
-    pub descr: OrderResponseDescription,
     
-    /// Transaction IDs
-    pub txid: Vec<String>,
+    /// Check the order type and price combination before sending it to Kraken
+    pub fn validate_local(&self) -> Result<()> {
+        validate_prices(&self.ordertype, self.price.as_deref(), self.price2.as_deref())
+    }
 }
 
-/// Order response description
-#[derive(Debug, Clone, Deserialize)]
-pub struct OrderResponseDescription {
-    /// Order description
-    pub order: String,
-    
-    /// Conditional close order description (if conditional close set)
-    pub close: Option<String>,
+/// [`OrderBuilder`] state before the order type is chosen
+#[derive(Debug, Clone, Copy)]
+pub struct Untyped;
+
+/// [`OrderBuilder`] state once the order type and its required prices are set
+#[derive(Debug, Clone, Copy)]
+pub struct Typed;
+
+/// Builder for an [`Order`] that can only be built once its type is chosen
+///
+/// Each order type method takes the prices that type requires, so for example a
+/// limit order can't be built without a limit price and a market order can't be
+/// given one. Optional fields are set with the `with_*` methods once the type is
+/// chosen, and [`OrderBuilder::build`] checks the prices before returning the order.
+#[derive(Debug, Clone)]
+pub struct OrderBuilder<S> {
+    /// Order being built
+    order: Order,
+    
+    /// Builder state
+    state: PhantomData<S>,
 }
 
-/// Order info
-#[derive(Debug, Clone, Deserialize)]
-pub struct OrderInfo {
-    /// Transaction ID
-    pub txid: String,
-    
-    /// User reference ID
-    pub userref: Option<i64>,
+impl OrderBuilder<Untyped> {
+    /// Start building an order
+    pub fn new(pair: impl Into<String>, side: OrderSide, volume: impl Into<String>) -> Self {
+        Self {
+            order: Order::new(pair, side, OrderType::Market, volume),
+            state: PhantomData,
+        }
+    }
     
-    /// Status of order
-    pub status: String,
+    /// Set the order type and prices
+    fn typed(mut self, order_type: OrderType, price: Option<String>, price2: Option<String>) -> OrderBuilder<Typed> {
+        self.order.ordertype = order_type;
+        self.order.price = price;
+        self.order.price2 = price2;
+        
+        OrderBuilder { order: self.order, state: PhantomData }
+    }
     
-    /// Unix timestamp of when order was placed
-    pub opentm: f64,
+    /// Build a market order
+    pub fn market(self) -> OrderBuilder<Typed> {
+        self.typed(OrderType::Market, None, None)
+    }
     
-    /// Unix timestamp of order start time (or 0 if not set)
-    pub starttm: f64,
+    /// Build a limit order
+    pub fn limit(self, price: impl Into<String>) -> OrderBuilder<Typed> {
+        self.typed(OrderType::Limit, Some(price.into()), None)
+    }
     
-    /// Unix timestamp of order end time (or 0 if not set)
-    pub expiretm: f64,
+    /// Build a stop-loss order
+    pub fn stop_loss(self, trigger_price: impl Into<String>) -> OrderBuilder<Typed> {
+        self.typed(OrderType::StopLoss, Some(trigger_price.into()), None)
+    }
     
-    /// Order description info
-    pub descr: OrderDescription,
+    /// Build a take-profit order
+    pub fn take_profit(self, trigger_price: impl Into<String>) -> OrderBuilder<Typed> {
+        self.typed(OrderType::TakeProfit, Some(trigger_price.into()), None)
+    }
     
-    /// Volume of order (base currency)
-    pub vol: String,
+    /// Build a stop-loss-limit order
+    pub fn stop_loss_limit(self, trigger_price: impl Into<String>, limit_price: impl Into<String>) -> OrderBuilder<Typed> {
+        self.typed(OrderType::StopLossLimit, Some(trigger_price.into()), Some(limit_price.into()))
+    }
     
-    /// Volume executed (base currency)
-    pub vol_exec: String,
+    /// Build a take-profit-limit order
+    pub fn take_profit_limit(self, trigger_price: impl Into<String>, limit_price: impl Into<String>) -> OrderBuilder<Typed> {
+        self.typed(OrderType::TakeProfitLimit, Some(trigger_price.into()), Some(limit_price.into()))
+    }
     
-    /// Total cost (quote currency unless viqc set in oflags)
-    pub cost: String,
+    /// Build a settle-position order
+    pub fn settle_position(self) -> OrderBuilder<Typed> {
+        self.typed(OrderType::SettlePosition, None, None)
+    }
     
-    /// Total fee (quote currency)
-    pub fee: String,
+    /// Build a trailing-stop order, with a trigger offset such as `+50`
+    pub fn trailing_stop(self, trigger_offset: impl Into<String>) -> OrderBuilder<Typed> {
+        self.typed(OrderType::TrailingStop, Some(trigger_offset.into()), None)
+    }
     
-    /// Average price (quote currency unless viqc set in oflags)
-    pub price: String,
+    /// Build a trailing-stop-limit order, with trigger and limit offsets
+    pub fn trailing_stop_limit(self, trigger_offset: impl Into<String>, limit_offset: impl Into<String>) -> OrderBuilder<Typed> {
+        self.typed(OrderType::TrailingStopLimit, Some(trigger_offset.into()), Some(limit_offset.into()))
+    }
+}
+
+impl OrderBuilder<Typed> {
+    /// Set the leverage
+    pub fn with_leverage(mut self, leverage: impl Into<String>) -> Self {
+        self.order = self.order.with_leverage(leverage);
+        self
+    }
     
-    /// Stop price (quote currency, for trailing stops)
-    pub stopprice: Option<String>,
+    /// Add order flags
+    pub fn with_flags(mut self, flags: &[OrderFlag]) -> Self {
+        self.order = self.order.with_flags(flags);
+        self
+    }
     
-    /// Triggered limit price (quote currency, when limit based order type triggered)
-    pub limitprice: Option<String>,
+    /// Set the start time
+    pub fn with_start_time(mut self, start_time: impl Into<String>) -> Self {
+        self.order = self.order.with_start_time(start_time);
+        self
+    }
     
-    /// Comma delimited list of miscellaneous info
-    pub misc: String,
+    /// Set the expiration time
+    pub fn with_expiration_time(mut self, expiration_time: impl Into<String>) -> Self {
+        self.order = self.order.with_expiration_time(expiration_time);
+        self
+    }
     
-    /// Comma delimited list of order flags
-    pub oflags: String,
-}
-
-/// Trade info
-#[derive(Debug, Clone, Deserialize)]
-pub struct TradeInfo {
-    /// Order ID
-    pub ordertxid: String,
+    /// Set the deadline as an RFC3339 timestamp
+    pub fn with_deadline(mut self, deadline: impl Into<String>) -> Self {
+        self.order = self.order.with_deadline(deadline);
+        self
+    }
     
-    /// Position ID
-    pub postxid: String,
+    /// Set the deadline
+    pub fn with_deadline_at(mut self, deadline: DateTime<Utc>) -> Self {
+        self.order = self.order.with_deadline_at(deadline);
+        self
+    }
     
-    /// Asset pair
-    pub pair: String,
+    /// Set the reduce-only flag
+    pub fn with_reduce_only(mut self, reduce_only: bool) -> Self {
+        self.order = self.order.with_reduce_only(reduce_only);
+        self
+    }
     
-    /// Unix timestamp of trade
-    pub time: f64,
+    /// Set the trigger reference price
+    pub fn with_trigger(mut self, trigger: TriggerType) -> Self {
+        self.order = self.order.with_trigger(trigger);
+        self
+    }
     
-    /// Type of order (buy/sell)
-    pub type_: String,
+    /// Set the user reference ID
+    pub fn with_user_ref(mut self, user_ref: i32) -> Self {
+        self.order = self.order.with_user_ref(user_ref);
+        self
+    }
     
-    /// Order type
-    pub ordertype: String,
+    /// Set the client order ID
+    pub fn with_cl_ord_id(mut self, cl_ord_id: impl Into<String>) -> Self {
+        self.order = self.order.with_cl_ord_id(cl_ord_id);
+        self
+    }
     
-    /// Average price order was executed at (quote currency)
-    pub price: String,
+    /// Set a randomly generated client order ID, unless one is already set
+    pub fn with_generated_cl_ord_id(mut self) -> Self {
+        self.order = self.order.with_generated_cl_ord_id();
+        self
+    }
     
-    /// Total cost of order (quote currency)
-    pub cost: String,
+    /// Set the validate flag
+    pub fn with_validate(mut self, validate: bool) -> Self {
+        self.order = self.order.with_validate(validate);
+        self
+    }
     
-    /// Total fee (quote currency)
-    pub fee: String,
+    /// Set the close order type
+    pub fn with_close_order_type(mut self, close_order_type: OrderType) -> Self {
+        self.order = self.order.with_close_order_type(close_order_type);
+        self
+    }
     
-    /// Volume (base currency)
-    pub vol: String,
+    /// Set the close order price
+    pub fn with_close_price(mut self, close_price: impl Into<String>) -> Self {
+        self.order = self.order.with_close_price(close_price);
+        self
+    }
     
-    /// Initial margin (quote currency)
-    pub margin: String,
+    /// Set the close order secondary price
+    pub fn with_close_price2(mut self, close_price2: impl Into<String>) -> Self {
+        self.order = self.order.with_close_price2(close_price2);
+        self
+    }
     
-    /// Comma delimited list of miscellaneous info
+    /// Build the order, checking its prices with [`Order::validate_local`]
+    pub fn build(self) -> Result<Order> {
+        self.order.validate_local()?;
+        Ok(self.order)
+    }
+}
+
+/// Order submitted as part of a batch
+///
+/// Mirrors [`Order`] without the per-order `pair`, which is shared by the whole batch.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchOrder {
+    /// Type of order (buy/sell)
+    pub type_: OrderSide,
+    
+    /// Order type
+    pub ordertype: OrderType,
+    
+    /// Order volume in base currency
+    pub volume: String,
+    
+    /// Price (optional, dependent on ordertype)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<String>,
+    
+    /// Secondary price (optional, dependent on ordertype)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price2: Option<String>,
+    
+    /// Amount of leverage desired (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leverage: Option<String>,
+    
+    /// Comma delimited list of order flags (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oflags: Option<String>,
+    
+    /// Scheduled start time (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starttm: Option<String>,
+    
+    /// Expiration time (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiretm: Option<String>,
+    
+    /// Only reduce an existing margin position, never open or increase one (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reduce_only: Option<bool>,
+    
+    /// Reference price for triggered order types (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<TriggerType>,
+    
+    /// User reference ID (optional)
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "serialize_userref")]
+    pub userref: Option<i32>,
+    
+    /// Client order ID, used by Kraken to reject duplicate submissions (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cl_ord_id: Option<String>,
+    
+    /// Close order type (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close_ordertype: Option<OrderType>,
+    
+    /// Close order price (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close_price: Option<String>,
+    
+    /// Close order secondary price (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close_price2: Option<String>,
+}
+
+impl BatchOrder {
+    /// Create a new batch order
+    pub fn new(side: OrderSide, order_type: OrderType, volume: impl Into<String>) -> Self {
+        Self {
+            type_: side,
+            ordertype: order_type,
+            volume: volume.into(),
+            price: None,
+            price2: None,
+            leverage: None,
+            oflags: None,
+            starttm: None,
+            expiretm: None,
+            reduce_only: None,
+            trigger: None,
+            userref: None,
+            cl_ord_id: None,
+            close_ordertype: None,
+            close_price: None,
+            close_price2: None,
+        }
+    }
+    
+    /// Set the price, either as a string or as a [`Price`]
+    pub fn with_price(mut self, price: impl Into<String>) -> Self {
+        self.price = Some(price.into());
+        self
+    }
+    
+    /// Set the secondary price
+    pub fn with_price2(mut self, price2: impl Into<String>) -> Self {
+        self.price2 = Some(price2.into());
+        self
+    }
+    
+    /// Set the leverage
+    pub fn with_leverage(mut self, leverage: impl Into<String>) -> Self {
+        self.leverage = Some(leverage.into());
+        self
+    }
+    
+    /// Add order flags
+    pub fn with_flags(mut self, flags: &[OrderFlag]) -> Self {
+        let flags_str = flags
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        
+        self.oflags = Some(flags_str);
+        self
+    }
+    
+    /// Parse the order flags set on this order
+    pub fn oflags_parsed(&self) -> Vec<OrderFlag> {
+        self.oflags.as_deref().map(parse_order_flags).unwrap_or_default()
+    }
+    
+    /// Set the start time
+    pub fn with_start_time(mut self, start_time: impl Into<String>) -> Self {
+        self.starttm = Some(start_time.into());
+        self
+    }
+    
+    /// Set the expiration time
+    pub fn with_expiration_time(mut self, expiration_time: impl Into<String>) -> Self {
+        self.expiretm = Some(expiration_time.into());
+        self
+    }
+    
+    /// Set the user reference ID
+    pub fn with_user_ref(mut self, user_ref: i32) -> Self {
+        self.userref = Some(user_ref);
+        self
+    }
+    
+    /// Set the client order ID
+    pub fn with_cl_ord_id(mut self, cl_ord_id: impl Into<String>) -> Self {
+        self.cl_ord_id = Some(cl_ord_id.into());
+        self
+    }
+    
+    /// Set the reduce-only flag
+    pub fn with_reduce_only(mut self, reduce_only: bool) -> Self {
+        self.reduce_only = Some(reduce_only);
+        self
+    }
+    
+    /// Set the trigger reference price
+    pub fn with_trigger(mut self, trigger: TriggerType) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+    
+    /// Set the close order type
+    pub fn with_close_order_type(mut self, close_order_type: OrderType) -> Self {
+        self.close_ordertype = Some(close_order_type);
+        self
+    }
+    
+    /// Set the close order price
+    pub fn with_close_price(mut self, close_price: impl Into<String>) -> Self {
+        self.close_price = Some(close_price.into());
+        self
+    }
+    
+    /// Set the close order secondary price
+    pub fn with_close_price2(mut self, close_price2: impl Into<String>) -> Self {
+        self.close_price2 = Some(close_price2.into());
+        self
+    }
+    
+    /// Check the order type and price combination before sending it to Kraken
+    pub fn validate_local(&self) -> Result<()> {
+        validate_prices(&self.ordertype, self.price.as_deref(), self.price2.as_deref())
+    }
+}
+
+impl From<Order> for BatchOrder {
+    fn from(order: Order) -> Self {
+        Self {
+            type_: order.type_,
+            ordertype: order.ordertype,
+            volume: order.volume,
+            price: order.price,
+            price2: order.price2,
+            leverage: order.leverage,
+            oflags: order.oflags,
+            starttm: order.starttm,
+            expiretm: order.expiretm,
+            reduce_only: order.reduce_only,
+            trigger: order.trigger,
+            userref: order.userref,
+            cl_ord_id: order.cl_ord_id,
+            close_ordertype: order.close_ordertype,
+            close_price: order.close_price,
+            close_price2: order.close_price2,
+        }
+    }
+}
+
+/// Changes to apply to an open order with `AmendOrder`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrderAmend {
+    /// New order quantity in base currency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_qty: Option<String>,
+    
+    /// New limit price
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_price: Option<String>,
+    
+    /// New visible quantity of an iceberg order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_qty: Option<String>,
+    
+    /// Reject the amend if the new limit price would take liquidity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_only: Option<bool>,
+}
+
+impl OrderAmend {
+    /// Create an empty amend
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Set the new order quantity
+    pub fn with_order_qty(mut self, order_qty: impl Into<String>) -> Self {
+        self.order_qty = Some(order_qty.into());
+        self
+    }
+    
+    /// Set the new limit price
+    pub fn with_limit_price(mut self, limit_price: impl Into<String>) -> Self {
+        self.limit_price = Some(limit_price.into());
+        self
+    }
+    
+    /// Set the new visible quantity of an iceberg order
+    pub fn with_display_qty(mut self, display_qty: impl Into<String>) -> Self {
+        self.display_qty = Some(display_qty.into());
+        self
+    }
+    
+    /// Set whether the amend is rejected if it would take liquidity
+    pub fn with_post_only(mut self, post_only: bool) -> Self {
+        self.post_only = Some(post_only);
+        self
+    }
+    
+    /// Whether the amend leaves the quantities and price unchanged
+    pub fn is_empty(&self) -> bool {
+        self.order_qty.is_none() && self.limit_price.is_none() && self.display_qty.is_none()
+    }
+}
+
+/// Amend order response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmendOrderResponse {
+    /// ID of the amend, identifying it in the order's amend history
+    pub amend_id: String,
+}
+
+/// Order response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderResponse {
+    /// Order description info
+    pub descr: OrderResponseDescription,
+    
+    /// Transaction IDs (empty for validate-only orders)
+    #[serde(default)]
+    pub txid: Vec<String>,
+}
+
+/// Orders to cancel with `CancelOrder`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CancelTarget {
+    /// A single order, by transaction ID
+    TxId(String),
+    
+    /// A single order, by the client order ID it was placed with
+    ClOrdId(String),
+    
+    /// All orders placed with this user reference ID
+    UserRef(i32),
+}
+
+impl fmt::Display for CancelTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CancelTarget::TxId(txid) => f.write_str(txid),
+            CancelTarget::ClOrdId(cl_ord_id) => f.write_str(cl_ord_id),
+            CancelTarget::UserRef(userref) => write!(f, "{}", userref),
+        }
+    }
+}
+
+impl From<&str> for CancelTarget {
+    fn from(txid: &str) -> Self {
+        CancelTarget::TxId(txid.to_string())
+    }
+}
+
+impl From<String> for CancelTarget {
+    fn from(txid: String) -> Self {
+        CancelTarget::TxId(txid)
+    }
+}
+
+impl From<i32> for CancelTarget {
+    fn from(userref: i32) -> Self {
+        CancelTarget::UserRef(userref)
+    }
+}
+
+/// Cancel order response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelResult {
+    /// Number of orders cancelled
+    pub count: u64,
+    
+    /// Whether cancellation is pending
+    pub pending: Option<bool>,
+}
+
+/// Single entry of a batch order response
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct BatchOrderResponseEntry {
+    /// Order description info
+    pub descr: Option<OrderResponseDescription>,
+    
+    /// Transaction ID
+    pub txid: Option<String>,
+    
+    /// Error message if this order was rejected
+    pub error: Option<String>,
+}
+
+/// Order response description
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderResponseDescription {
+    /// Order description
+    pub order: String,
+    
+    /// Conditional close order description (if conditional close set)
+    pub close: Option<String>,
+}
+
+impl OrderResponseDescription {
+    /// Parse the structured values out of the order description text
+    pub fn parsed(&self) -> Result<ParsedOrderDescr> {
+        self.order.parse()
+    }
+}
+
+/// Structured values of an order description such as `"buy 1.0 XBTUSD @ limit 30000"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedOrderDescr {
+    /// Order side
+    pub side: OrderSide,
+    
+    /// Order volume
+    pub volume: String,
+    
+    /// Asset pair
+    pub pair: String,
+    
+    /// Order type
+    pub ordertype: OrderType,
+    
+    /// Primary price (absent for market orders)
+    pub price: Option<String>,
+    
+    /// Secondary price (limit price of stop-loss-limit and take-profit-limit orders)
+    pub price2: Option<String>,
+    
+    /// Leverage, e.g. `5:1`
+    pub leverage: Option<String>,
+}
+
+impl FromStr for ParsedOrderDescr {
+    type Err = Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        let unrecognized = || Error::Other(format!("Unrecognized order description: {}", s));
+        
+        let (head, rest) = s.split_once(" @ ").ok_or_else(unrecognized)?;
+        
+        let mut head = head.split_whitespace();
+        let side = match head.next() {
+            Some("buy") => OrderSide::Buy,
+            Some("sell") => OrderSide::Sell,
+            _ => return Err(unrecognized()),
+        };
+        let volume = head.next().ok_or_else(unrecognized)?.to_string();
+        let pair = head.next().ok_or_else(unrecognized)?.to_string();
+        if head.next().is_some() {
+            return Err(unrecognized());
+        }
+        
+        let (rest, leverage) = match rest.split_once(" with ") {
+            Some((rest, leverage)) => {
+                let leverage = leverage.strip_suffix(" leverage").ok_or_else(unrecognized)?;
+                (rest, Some(leverage.to_string()))
+            }
+            None => (rest, None),
+        };
+        
+        let (rest, price2) = match rest.split_once(" -> limit ") {
+            Some((rest, price2)) => (rest, Some(price2.to_string())),
+            None => (rest, None),
+        };
+        
+        let (ordertype, price) = match rest.rsplit_once(' ') {
+            None if rest == "market" => (OrderType::Market, None),
+            Some((kind, price)) => {
+                let ordertype = match (kind, price2.is_some()) {
+                    ("limit", false) => OrderType::Limit,
+                    ("stop loss", false) => OrderType::StopLoss,
+                    ("take profit", false) => OrderType::TakeProfit,
+                    ("stop loss", true) => OrderType::StopLossLimit,
+                    ("take profit", true) => OrderType::TakeProfitLimit,
+                    _ => return Err(unrecognized()),
+                };
+                (ordertype, Some(price.to_string()))
+            }
+            None => return Err(unrecognized()),
+        };
+        
+        Ok(Self { side, volume, pair, ordertype, price, price2, leverage })
+    }
+}
+
+impl fmt::Display for ParsedOrderDescr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} @ ", self.side, self.volume, self.pair)?;
+        
+        match &self.ordertype {
+            OrderType::StopLoss | OrderType::StopLossLimit => f.write_str("stop loss")?,
+            OrderType::TakeProfit | OrderType::TakeProfitLimit => f.write_str("take profit")?,
+            ordertype => write!(f, "{}", ordertype)?,
+        }
+        
+        if let Some(ref price) = self.price {
+            write!(f, " {}", price)?;
+        }
+        
+        if let Some(ref price2) = self.price2 {
+            write!(f, " -> limit {}", price2)?;
+        }
+        
+        if let Some(ref leverage) = self.leverage {
+            write!(f, " with {} leverage", leverage)?;
+        }
+        
+        Ok(())
+    }
+}
+
+/// Order info
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderInfo {
+    /// Transaction ID (filled in from the response key)
+    #[serde(default)]
+    pub txid: String,
+    
+    /// User reference ID
+    pub userref: Option<i32>,
+    
+    /// Status of order
+    pub status: OrderStatus,
+    
+    /// Unix timestamp of when order was placed
+    pub opentm: f64,
+    
+    /// Unix timestamp of order start time (or 0 if not set)
+    pub starttm: f64,
+    
+    /// Unix timestamp of order end time (or 0 if not set)
+    pub expiretm: f64,
+    
+    /// Order description info
+    pub descr: OrderDescription,
+    
+    /// Volume of order (base currency)
+    pub vol: String,
+    
+    /// Volume executed (base currency)
+    pub vol_exec: String,
+    
+    /// Total cost (quote currency unless viqc set in oflags)
+    pub cost: String,
+    
+    /// Total fee (quote currency)
+    pub fee: String,
+    
+    /// Average price (quote currency unless viqc set in oflags)
+    pub price: String,
+    
+    /// Stop price (quote currency, for trailing stops)
+    pub stopprice: Option<String>,
+    
+    /// Triggered limit price (quote currency, when limit based order type triggered)
+    pub limitprice: Option<String>,
+    
+    /// Comma delimited list of miscellaneous info
+    pub misc: String,
+    
+    /// Comma delimited list of order flags
+    pub oflags: String,
+    
+    /// Array of trade IDs related to order (if trades info requested and data available)
+    pub trades: Option<Vec<String>>,
+}
+
+impl OrderInfo {
+    /// Parse the order flags, skipping any this client doesn't know
+    pub fn oflags_parsed(&self) -> Vec<OrderFlag> {
+        parse_order_flags(&self.oflags)
+    }
+}
+
+/// Trade info
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeInfo {
+    /// Order ID
+    pub ordertxid: String,
+    
+    /// Position ID
+    pub postxid: String,
+    
+    /// Asset pair
+    pub pair: String,
+    
+    /// Unix timestamp of trade
+    pub time: f64,
+    
+    /// Type of order (buy/sell)
+    #[serde(rename = "type")]
+    pub type_: String,
+    
+    /// Order type
+    pub ordertype: String,
+    
+    /// Average price order was executed at (quote currency)
+    pub price: String,
+    
+    /// Total cost of order (quote currency)
+    pub cost: String,
+    
+    /// Total fee (quote currency)
+    pub fee: String,
+    
+    /// Volume (base currency)
+    pub vol: String,
+    
+    /// Initial margin (quote currency)
+    pub margin: String,
+    
+    /// Comma delimited list of miscellaneous info
     pub misc: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse_order_flags() {
+        assert_eq!(parse_order_flags("fcib,post"), vec![OrderFlag::Fcib, OrderFlag::Post]);
+        assert_eq!(parse_order_flags("post, nomo,viqc"), vec![OrderFlag::Post, OrderFlag::Viqc]);
+        assert!(parse_order_flags("").is_empty());
+        assert!("nomo".parse::<OrderFlag>().is_err());
+        
+        let order = Order::new("XBTUSD", OrderSide::Buy, OrderType::Limit, "1.0").with_flags(&[OrderFlag::Post, OrderFlag::Fciq]);
+        assert!(order.oflags_parsed().contains(&OrderFlag::Post));
+        assert!(Order::new("XBTUSD", OrderSide::Buy, OrderType::Market, "1.0").oflags_parsed().is_empty());
+    }
+    
+    #[test]
+    fn test_display_matches_api_names() {
+        assert_eq!(OrderType::StopLossLimit.to_string(), "stop-loss-limit");
+        assert_eq!(OrderSide::Sell.to_string(), "sell");
+        assert_eq!(format!("{},{}", OrderFlag::Post, OrderFlag::Nompp), "post,nompp");
+        
+        for flag in [OrderFlag::Post, OrderFlag::Fcib, OrderFlag::Fciq, OrderFlag::Nompp, OrderFlag::Viqc] {
+            assert_eq!(flag.to_string().parse::<OrderFlag>().unwrap(), flag);
+        }
+    }
+    
+    #[test]
+    fn test_validate_local() {
+        let market = Order::new("XBTUSD", OrderSide::Buy, OrderType::Market, "0.01");
+        assert!(market.validate_local().is_ok());
+        assert!(market.with_price("30000").validate_local().is_err());
+        
+        let limit = Order::new("XBTUSD", OrderSide::Buy, OrderType::Limit, "0.01");
+        assert!(limit.clone().validate_local().is_err());
+        assert!(limit.with_price("30000").validate_local().is_ok());
+        
+        let stop_limit = Order::new("XBTUSD", OrderSide::Sell, OrderType::StopLossLimit, "0.01")
+            .with_price("29000");
+        assert!(stop_limit.clone().validate_local().is_err());
+        assert!(stop_limit.with_price2("28900").validate_local().is_ok());
+    }
+    
+    #[test]
+    fn test_order_status_deserialization() {
+        let status: OrderStatus = serde_json::from_str("\"canceled\"").unwrap();
+        assert_eq!(status, OrderStatus::Canceled);
+        
+        let status: OrderStatus = serde_json::from_str("\"suspended\"").unwrap();
+        assert_eq!(status, OrderStatus::Unknown);
+    }
+    
+    #[test]
+    fn test_parse_order_description() {
+        let parsed: ParsedOrderDescr = "buy 1.00000000 XBTUSD @ limit 30000.0".parse().unwrap();
+        assert_eq!(parsed.side, OrderSide::Buy);
+        assert_eq!(parsed.volume, "1.00000000");
+        assert_eq!(parsed.pair, "XBTUSD");
+        assert_eq!(parsed.ordertype, OrderType::Limit);
+        assert_eq!(parsed.price.as_deref(), Some("30000.0"));
+        assert_eq!(parsed.price2, None);
+        
+        let parsed: ParsedOrderDescr = "sell 0.5 ETHEUR @ market".parse().unwrap();
+        assert_eq!(parsed.ordertype, OrderType::Market);
+        assert_eq!(parsed.price, None);
+        
+        let text = "sell 0.01 XBTUSD @ stop loss 25000.0 -> limit 24900.0 with 5:1 leverage";
+        let parsed: ParsedOrderDescr = text.parse().unwrap();
+        assert_eq!(parsed.ordertype, OrderType::StopLossLimit);
+        assert_eq!(parsed.price.as_deref(), Some("25000.0"));
+        assert_eq!(parsed.price2.as_deref(), Some("24900.0"));
+        assert_eq!(parsed.leverage.as_deref(), Some("5:1"));
+        assert_eq!(parsed.to_string(), text);
+    }
+    
+    #[test]
+    fn test_parse_order_description_rejects_unknown_format() {
+        assert!("buy 1.0 XBTUSD".parse::<ParsedOrderDescr>().is_err());
+        assert!("hold 1.0 XBTUSD @ limit 30000".parse::<ParsedOrderDescr>().is_err());
+        assert!("buy 1.0 XBTUSD @ trailing stop +50".parse::<ParsedOrderDescr>().is_err());
+    }
+    
+    #[test]
+    fn test_with_deadline_at() {
+        let deadline = DateTime::parse_from_rfc3339("2023-07-06T18:50:48Z").unwrap().with_timezone(&Utc);
+        let order = Order::new("XBTUSD", OrderSide::Buy, OrderType::Market, "0.01").with_deadline_at(deadline);
+        
+        assert_eq!(order.deadline.as_deref(), Some("2023-07-06T18:50:48.000Z"));
+    }
+    
+    #[test]
+    fn test_with_generated_cl_ord_id() {
+        let order = Order::new("XBTUSD", OrderSide::Buy, OrderType::Market, "0.01").with_generated_cl_ord_id();
+        let cl_ord_id = order.cl_ord_id.clone().unwrap();
+        assert!(Uuid::parse_str(&cl_ord_id).is_ok());
+        
+        // Regenerating keeps the existing ID so a retried order is recognized as a duplicate
+        assert_eq!(order.with_generated_cl_ord_id().cl_ord_id, Some(cl_ord_id));
+    }
+    
+    #[test]
+    fn test_price() {
+        assert_eq!(Price::absolute(Decimal::new(300005, 1)).unwrap().to_string(), "30000.5");
+        assert_eq!(Price::relative_plus(Decimal::new(10000, 2)).unwrap().to_string(), "+100");
+        assert_eq!(Price::relative_minus(Decimal::from(100)).unwrap().to_string(), "-100");
+        assert_eq!(Price::percent(Decimal::from(-5)).to_string(), "-5%");
+        assert_eq!(Price::percent(Decimal::new(25, 1)).to_string(), "+2.5%");
+        assert_eq!(Price::trailing(Decimal::from(50)).unwrap().to_string(), "#50");
+        
+        // Decimal amounts render exactly, without float noise
+        assert_eq!(Price::absolute(Decimal::new(1, 1) + Decimal::new(2, 1)).unwrap().to_string(), "0.3");
+        
+        let amount = NonNegative::new(Decimal::from(30000)).unwrap();
+        assert_eq!(Price::absolute(Decimal::from(30000)).unwrap(), Price::Absolute(amount));
+        assert_eq!(amount.get(), Decimal::from(30000));
+        
+        // A negative absolute price can't be built, so it can't be mistaken for a relative one
+        assert!(NonNegative::new(Decimal::from(-5)).is_err());
+        assert!(Price::absolute(Decimal::from(-5)).is_err());
+        assert!(Price::relative_plus(Decimal::from(-5)).is_err());
+        assert!(Price::relative_minus(Decimal::from(-5)).is_err());
+        assert!(Price::trailing(Decimal::from(-5)).is_err());
+        assert!(Price::relative_minus(Decimal::ZERO).is_ok());
+        
+        let order = Order::new("XBTUSD", OrderSide::Sell, OrderType::StopLossLimit, "0.01")
+            .with_price(Price::percent(Decimal::from(-5)))
+            .with_price2(Price::relative_minus(Decimal::from(10)).unwrap());
+        assert_eq!(order.price.as_deref(), Some("-5%"));
+        assert!(order.validate_local().is_ok());
+        
+        for invalid in ["", "abc", "5%", "+-5", "1e5", "#"] {
+            let order = Order::new("XBTUSD", OrderSide::Buy, OrderType::Limit, "0.01").with_price(invalid);
+            assert!(order.validate_local().is_err(), "{:?} should be rejected", invalid);
+        }
+    }
+    
+    #[test]
+    fn test_order_builder() {
+        let limit = Order::builder("XBTUSD", OrderSide::Buy, "0.01").limit("30000.0").build().unwrap();
+        assert_eq!(limit.ordertype, OrderType::Limit);
+        assert_eq!(limit.price.as_deref(), Some("30000.0"));
+        
+        let market = Order::builder("XBTUSD", OrderSide::Sell, "0.01").market().with_user_ref(7).with_reduce_only(true).build().unwrap();
+        assert!(market.price.is_none());
+        assert_eq!(market.userref, Some(7));
+        assert_eq!(market.reduce_only, Some(true));
+        
+        let stop_limit = OrderBuilder::new("XBTUSD", OrderSide::Sell, "0.01").stop_loss_limit("29000.0", "28900.0").build().unwrap();
+        assert_eq!(stop_limit.price.as_deref(), Some("29000.0"));
+        assert_eq!(stop_limit.price2.as_deref(), Some("28900.0"));
+        
+        // Prices given to the order type method are checked when building
+        let error = Order::builder("XBTUSD", OrderSide::Buy, "0.01").limit("abc").build().unwrap_err();
+        assert!(matches!(error, Error::Other(_)));
+        assert!(Order::builder("XBTUSD", OrderSide::Buy, "0.01").limit("+5%").build().is_ok());
+    }
+    
+    #[test]
+    fn test_userref_serializes_as_string() {
+        let order = Order::new("XBTUSD", OrderSide::Buy, OrderType::Market, "0.01").with_user_ref(-42);
+        let value = serde_json::to_value(&order).unwrap();
+        assert_eq!(value["userref"], "-42");
+        
+        let batch = BatchOrder::from(Order::new("XBTUSD", OrderSide::Buy, OrderType::Market, "0.01"));
+        assert!(serde_json::to_value(&batch).unwrap().get("userref").is_none());
+    }
+    
+    #[test]
+    fn test_batch_order_keeps_cl_ord_id() {
+        let order = Order::new("XBTUSD", OrderSide::Buy, OrderType::Market, "0.01").with_generated_cl_ord_id();
+        let cl_ord_id = order.cl_ord_id.clone();
+        
+        assert_eq!(BatchOrder::from(order).cl_ord_id, cl_ord_id);
+    }
+    
+    #[test]
+    fn test_batch_order_keeps_reduce_only_and_trigger() {
+        let order = Order::new("XBTUSD", OrderSide::Sell, OrderType::StopLoss, "0.01")
+            .with_price("29000.0")
+            .with_reduce_only(true)
+            .with_trigger(TriggerType::Index);
+        
+        let batch = BatchOrder::from(order);
+        assert_eq!(batch.reduce_only, Some(true));
+        assert_eq!(batch.trigger, Some(TriggerType::Index));
+    }
+    
+    #[test]
+    fn test_order_type_serde() {
+        assert_eq!(serde_json::to_value(OrderType::StopLossLimit).unwrap(), "stop-loss-limit");
+        assert_eq!(serde_json::to_value(OrderType::TrailingStop).unwrap(), "trailing-stop");
+        
+        let parsed: OrderType = serde_json::from_str("\"trailing-stop-limit\"").unwrap();
+        assert_eq!(parsed, OrderType::TrailingStopLimit);
+        
+        let unknown: OrderType = serde_json::from_str("\"iceberg\"").unwrap();
+        assert_eq!(unknown, OrderType::Other("iceberg".to_string()));
+        assert_eq!(unknown.to_string(), "iceberg");
+        assert_eq!(serde_json::to_value(&unknown).unwrap(), "iceberg");
+        
+        assert!(OrderBuilder::new("XBTUSD", OrderSide::Sell, "0.01").trailing_stop("+50").build().is_ok());
+        assert!(Order::new("XBTUSD", OrderSide::Sell, OrderType::TrailingStopLimit, "0.01").with_price("+50").validate_local().is_err());
+    }
+}
@@ -1,7 +1,11 @@
 //! WebSocket models for the Kraken API
 
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::models::market::Interval;
 
 /// WebSocket message types
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -53,6 +57,21 @@ pub enum WebSocketSubscriptionType {
     /// All tickers
     #[serde(rename = "*")]
     All,
+    
+    /// Own trades (private)
+    #[serde(rename = "ownTrades")]
+    OwnTrades,
+    
+    /// Open orders (private)
+    #[serde(rename = "openOrders")]
+    OpenOrders,
+}
+
+impl WebSocketSubscriptionType {
+    /// Whether the channel requires an authenticated connection and token
+    pub fn is_private(&self) -> bool {
+        matches!(self, WebSocketSubscriptionType::OwnTrades | WebSocketSubscriptionType::OpenOrders)
+    }
 }
 
 /// WebSocket subscription
@@ -68,6 +87,10 @@ pub struct WebSocketSubscription {
     /// Depth for book
     #[serde(skip_serializing_if = "Option::is_none")]
     pub depth: Option<u32>,
+    
+    /// Authentication token for private channels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
 }
 
 /// WebSocket subscription request
@@ -93,6 +116,7 @@ impl WebSocketSubscriptionRequest {
                 name: WebSocketSubscriptionType::Ticker,
                 interval: None,
                 depth: None,
+                token: None,
             },
             pair: None,
         }
@@ -106,6 +130,7 @@ impl WebSocketSubscriptionRequest {
                 name: subscription_type,
                 interval: None,
                 depth: None,
+                token: None,
             },
             pair: None,
         }
@@ -133,12 +158,24 @@ impl WebSocketSubscriptionRequest {
         self
     }
     
+    /// Set the interval for OHLC from a typed interval
+    pub fn with_ohlc_interval(mut self, interval: Interval) -> Self {
+        self.subscription.interval = Some(interval.as_minutes());
+        self
+    }
+    
     /// Set the depth for book
     pub fn with_depth(mut self, depth: u32) -> Self {
         self.subscription.depth = Some(depth);
         self
     }
     
+    /// Set the authentication token for private channels
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.subscription.token = Some(token.into());
+        self
+    }
+    
     /// Set the subscription type
     pub fn add_subscription<S: AsRef<str>>(mut self, name: S) -> Self {
         let name_str = name.as_ref();
@@ -148,12 +185,20 @@ impl WebSocketSubscriptionRequest {
             "trade" => WebSocketSubscriptionType::Trade,
             "spread" => WebSocketSubscriptionType::Spread,
             "book" => WebSocketSubscriptionType::Book,
+            "ownTrades" => WebSocketSubscriptionType::OwnTrades,
+            "openOrders" => WebSocketSubscriptionType::OpenOrders,
             _ => WebSocketSubscriptionType::Ticker, // Default to ticker
         };
         self
     }
 }
 
+impl Default for WebSocketSubscriptionRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// WebSocket unsubscription request
 #[derive(Debug, Clone, Serialize)]
 pub struct WebSocketUnsubscriptionRequest {
@@ -177,6 +222,7 @@ impl WebSocketUnsubscriptionRequest {
                 name: subscription_type,
                 interval: None,
                 depth: None,
+                token: None,
             },
             pair: None,
         }
@@ -188,12 +234,24 @@ impl WebSocketUnsubscriptionRequest {
         self
     }
     
+    /// Set the authentication token for private channels
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.subscription.token = Some(token.into());
+        self
+    }
+    
     /// Set the interval for OHLC
     pub fn with_interval(mut self, interval: u32) -> Self {
         self.subscription.interval = Some(interval);
         self
     }
     
+    /// Set the interval for OHLC from a typed interval
+    pub fn with_ohlc_interval(mut self, interval: Interval) -> Self {
+        self.subscription.interval = Some(interval.as_minutes());
+        self
+    }
+    
     /// Set the depth for book
     pub fn with_depth(mut self, depth: u32) -> Self {
         self.subscription.depth = Some(depth);
@@ -201,6 +259,86 @@ impl WebSocketUnsubscriptionRequest {
     }
 }
 
+/// Channel Kraken confirmed in a `subscriptionStatus` message
+#[derive(Debug, Clone)]
+pub struct WebSocketChannel {
+    /// Channel name, e.g. `book-10`
+    pub channel_name: Option<String>,
+    
+    /// Pair
+    pub pair: Option<String>,
+    
+    /// Subscription
+    pub subscription: WebSocketSubscription,
+}
+
+impl WebSocketChannel {
+    /// Build the request that unsubscribes from this channel
+    pub fn unsubscription_request(&self) -> WebSocketUnsubscriptionRequest {
+        WebSocketUnsubscriptionRequest {
+            event: WebSocketMessageType::Unsubscribe,
+            subscription: self.subscription.clone(),
+            pair: self.pair.clone().map(|pair| vec![pair]),
+        }
+    }
+}
+
+/// WebSocket connection state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ConnectionState {
+    /// Connected, waiting for the initial system status
+    Connecting,
+    
+    /// Kraken reported the system as online
+    Online,
+    
+    /// Kraken reported maintenance or a restricted trading mode
+    Maintenance,
+    
+    /// Not connected
+    #[default]
+    Disconnected,
+}
+
+impl ConnectionState {
+    /// Get the state for a `systemStatus` status string
+    pub fn from_system_status(status: &str) -> Self {
+        match status {
+            "online" => ConnectionState::Online,
+            _ => ConnectionState::Maintenance,
+        }
+    }
+}
+
+/// Deserialize an event type, failing unless it is `expected`
+///
+/// [`WebSocketMessage`] is untagged, so without this check a `pong` event would
+/// be taken for the first variant with the same shape.
+fn expect_event<'de, D: Deserializer<'de>>(deserializer: D, expected: WebSocketMessageType) -> Result<WebSocketMessageType, D::Error> {
+    let event_type = WebSocketMessageType::deserialize(deserializer)?;
+    
+    if event_type != expected {
+        return Err(D::Error::custom(format!("expected {:?} event, got {:?}", expected, event_type)));
+    }
+    
+    Ok(event_type)
+}
+
+/// Deserialize the event type of [`WebSocketMessage::Heartbeat`]
+fn heartbeat_event<'de, D: Deserializer<'de>>(deserializer: D) -> Result<WebSocketMessageType, D::Error> {
+    expect_event(deserializer, WebSocketMessageType::Heartbeat)
+}
+
+/// Deserialize the event type of [`WebSocketMessage::Ping`]
+fn ping_event<'de, D: Deserializer<'de>>(deserializer: D) -> Result<WebSocketMessageType, D::Error> {
+    expect_event(deserializer, WebSocketMessageType::Ping)
+}
+
+/// Deserialize the event type of [`WebSocketMessage::Pong`]
+fn pong_event<'de, D: Deserializer<'de>>(deserializer: D) -> Result<WebSocketMessageType, D::Error> {
+    expect_event(deserializer, WebSocketMessageType::Pong)
+}
+
 /// WebSocket message
 #[derive(Debug, Clone, Deserialize)]
 #[serde(untagged)]
@@ -242,19 +380,23 @@ pub enum WebSocketMessage {
         
         /// Subscription
         subscription: WebSocketSubscription,
+        
+        /// Error message if the status is `error`
+        #[serde(rename = "errorMessage", default, skip_serializing_if = "Option::is_none")]
+        error_message: Option<String>,
     },
     
     /// Heartbeat
     Heartbeat {
         /// Event type
-        #[serde(rename = "event")]
+        #[serde(rename = "event", deserialize_with = "heartbeat_event")]
         event_type: WebSocketMessageType,
     },
     
     /// Ping
     Ping {
         /// Event type
-        #[serde(rename = "event")]
+        #[serde(rename = "event", deserialize_with = "ping_event")]
         event_type: WebSocketMessageType,
         
         /// Request ID
@@ -265,7 +407,7 @@ pub enum WebSocketMessage {
     /// Pong
     Pong {
         /// Event type
-        #[serde(rename = "event")]
+        #[serde(rename = "event", deserialize_with = "pong_event")]
         event_type: WebSocketMessageType,
         
         /// Request ID
@@ -298,3 +440,386 @@ pub enum WebSocketMessage {
     /// Generic message
     Generic(Value),
 }
+
+impl WebSocketMessage {
+    /// Parse an `ownTrades` channel message
+    ///
+    /// Returns `None` if this is not an `ownTrades` message or it can't be parsed.
+    pub fn own_trades(&self) -> Option<OwnTradesMessage> {
+        let (payload, sequence) = self.private_channel_payload("ownTrades")?;
+        
+        Some(OwnTradesMessage {
+            trades: serde_json::from_value(payload.clone()).ok()?,
+            sequence,
+        })
+    }
+    
+    /// Parse an `openOrders` channel message
+    ///
+    /// Returns `None` if this is not an `openOrders` message or it can't be parsed.
+    pub fn open_orders(&self) -> Option<OpenOrdersMessage> {
+        let (payload, sequence) = self.private_channel_payload("openOrders")?;
+        
+        Some(OpenOrdersMessage {
+            orders: serde_json::from_value(payload.clone()).ok()?,
+            sequence,
+        })
+    }
+    
+    /// Split a public channel data array into its parts
+    ///
+    /// Returns `None` if this is not a public channel data message.
+    pub fn channel_data(&self) -> Option<ChannelData> {
+        match self {
+            WebSocketMessage::DataArray(array) => ChannelData::from_array(array),
+            _ => None,
+        }
+    }
+    
+    /// Split a private channel data array of the form `[payload, channel_name, {"sequence": n}]`
+    fn private_channel_payload(&self, channel_name: &str) -> Option<(&Value, Option<u64>)> {
+        let array = match self {
+            WebSocketMessage::DataArray(array) => array,
+            _ => return None,
+        };
+        
+        if array.len() < 2 || array[1].as_str() != Some(channel_name) {
+            return None;
+        }
+        
+        let sequence = array.get(2).and_then(|v| v.get("sequence")).and_then(|v| v.as_u64());
+        
+        Some((&array[0], sequence))
+    }
+}
+
+/// Public channel data message, split into its parts
+///
+/// Public channels send arrays of the form `[channel_id, payload, channel_name, pair]`.
+/// Book updates touching both sides carry two payload objects, `{"a": ..}` and
+/// `{"b": .., "c": ..}`, which are merged into a single object.
+#[derive(Debug, Clone)]
+pub struct ChannelData {
+    /// Channel ID
+    pub channel_id: u64,
+    
+    /// Payload
+    pub payload: Value,
+    
+    /// Channel name, e.g. `ticker` or `book-10`
+    pub channel_name: String,
+    
+    /// Pair
+    pub pair: String,
+}
+
+impl ChannelData {
+    /// Split a public channel data array
+    ///
+    /// Returns `None` if the array doesn't have the public channel layout.
+    pub fn from_array(array: &[Value]) -> Option<Self> {
+        if array.len() < 4 {
+            return None;
+        }
+        
+        let channel_id = array[0].as_u64()?;
+        let channel_name = array[array.len() - 2].as_str()?.to_string();
+        let pair = array[array.len() - 1].as_str()?.to_string();
+        
+        let payload = match &array[1..array.len() - 2] {
+            [payload] => payload.clone(),
+            payloads => {
+                let mut merged = Map::new();
+                for payload in payloads {
+                    merged.extend(payload.as_object()?.clone());
+                }
+                Value::Object(merged)
+            }
+        };
+        
+        Some(Self { channel_id, payload, channel_name, pair })
+    }
+    
+    /// Whether this is a book snapshot rather than an incremental update
+    pub fn is_book_snapshot(&self) -> bool {
+        self.channel_name.starts_with("book") && (self.payload.get("as").is_some() || self.payload.get("bs").is_some())
+    }
+}
+
+/// Response to a WebSocket `addOrder` request
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebSocketAddOrderStatus {
+    /// Event type (`addOrderStatus`)
+    pub event: String,
+    
+    /// Status (`ok` or `error`)
+    pub status: String,
+    
+    /// Transaction ID of the placed order
+    pub txid: Option<String>,
+    
+    /// Order description
+    pub descr: Option<String>,
+    
+    /// Request ID
+    pub reqid: Option<u64>,
+    
+    /// Error message
+    #[serde(rename = "errorMessage")]
+    pub error_message: Option<String>,
+}
+
+/// Response to a WebSocket `cancelOrder` request
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebSocketCancelOrderStatus {
+    /// Event type (`cancelOrderStatus`)
+    pub event: String,
+    
+    /// Status (`ok` or `error`)
+    pub status: String,
+    
+    /// Request ID
+    pub reqid: Option<u64>,
+    
+    /// Error message
+    #[serde(rename = "errorMessage")]
+    pub error_message: Option<String>,
+}
+
+/// WebSocket authentication token
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebSocketToken {
+    /// Token to pass when subscribing to private channels
+    pub token: String,
+    
+    /// Time in seconds until the token expires if unused
+    pub expires: u64,
+}
+
+/// Trade from the `ownTrades` channel
+#[derive(Debug, Clone, Deserialize)]
+pub struct OwnTrade {
+    /// Order ID
+    pub ordertxid: String,
+    
+    /// Position ID
+    pub postxid: Option<String>,
+    
+    /// Asset pair
+    pub pair: String,
+    
+    /// Unix timestamp of trade
+    pub time: String,
+    
+    /// Type of order (buy/sell)
+    #[serde(rename = "type")]
+    pub type_: String,
+    
+    /// Order type
+    pub ordertype: String,
+    
+    /// Average price order was executed at (quote currency)
+    pub price: String,
+    
+    /// Total cost of order (quote currency)
+    pub cost: String,
+    
+    /// Total fee (quote currency)
+    pub fee: String,
+    
+    /// Volume (base currency)
+    pub vol: String,
+    
+    /// Initial margin (quote currency)
+    pub margin: Option<String>,
+    
+    /// User reference ID
+    pub userref: Option<i32>,
+}
+
+/// Message from the `ownTrades` channel
+#[derive(Debug, Clone)]
+pub struct OwnTradesMessage {
+    /// Trades keyed by trade ID
+    pub trades: Vec<HashMap<String, OwnTrade>>,
+    
+    /// Sequence number
+    pub sequence: Option<u64>,
+}
+
+/// Order description from the `openOrders` channel
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenOrderUpdateDescription {
+    /// Asset pair
+    pub pair: Option<String>,
+    
+    /// Type of order (buy/sell)
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    
+    /// Order type
+    pub ordertype: Option<String>,
+    
+    /// Primary price
+    pub price: Option<String>,
+    
+    /// Secondary price
+    pub price2: Option<String>,
+    
+    /// Amount of leverage
+    pub leverage: Option<String>,
+    
+    /// Order description
+    pub order: Option<String>,
+    
+    /// Conditional close order description
+    pub close: Option<String>,
+}
+
+/// Order update from the `openOrders` channel
+///
+/// The first message for an order carries all fields, while later updates only
+/// carry the fields that changed (e.g. `status` or `vol_exec`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenOrderUpdate {
+    /// Referral order transaction ID that created this order
+    pub refid: Option<String>,
+    
+    /// User reference ID
+    pub userref: Option<i32>,
+    
+    /// Status of order
+    pub status: Option<String>,
+    
+    /// Unix timestamp of when order was placed
+    pub opentm: Option<String>,
+    
+    /// Unix timestamp of order start time
+    pub starttm: Option<String>,
+    
+    /// Unix timestamp of order end time
+    pub expiretm: Option<String>,
+    
+    /// Order description info
+    pub descr: Option<OpenOrderUpdateDescription>,
+    
+    /// Volume of order (base currency)
+    pub vol: Option<String>,
+    
+    /// Volume executed (base currency)
+    pub vol_exec: Option<String>,
+    
+    /// Total cost (quote currency)
+    pub cost: Option<String>,
+    
+    /// Total fee (quote currency)
+    pub fee: Option<String>,
+    
+    /// Average price (quote currency)
+    pub avg_price: Option<String>,
+    
+    /// Stop price (quote currency)
+    pub stopprice: Option<String>,
+    
+    /// Triggered limit price (quote currency)
+    pub limitprice: Option<String>,
+    
+    /// Comma delimited list of miscellaneous info
+    pub misc: Option<String>,
+    
+    /// Comma delimited list of order flags
+    pub oflags: Option<String>,
+}
+
+/// Message from the `openOrders` channel
+#[derive(Debug, Clone)]
+pub struct OpenOrdersMessage {
+    /// Order updates keyed by order ID
+    pub orders: Vec<HashMap<String, OpenOrderUpdate>>,
+    
+    /// Sequence number
+    pub sequence: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_event_variants() {
+        let message = |value: Value| serde_json::from_value::<WebSocketMessage>(value).unwrap();
+        
+        assert!(matches!(message(json!({"event": "heartbeat"})), WebSocketMessage::Heartbeat { .. }));
+        assert!(matches!(message(json!({"event": "ping", "reqid": 3})), WebSocketMessage::Ping { req_id: Some(3), .. }));
+        assert!(matches!(message(json!({"event": "pong", "reqid": 7})), WebSocketMessage::Pong { req_id: Some(7), .. }));
+        assert!(matches!(message(json!({"event": "pong"})), WebSocketMessage::Pong { req_id: None, .. }));
+    }
+    
+    #[test]
+    fn test_channel_data() {
+        let ticker = WebSocketMessage::DataArray(vec![json!(340), json!({ "c": ["5525.40000", "0.00398963"] }), json!("ticker"), json!("XBT/USD")]);
+        let data = ticker.channel_data().unwrap();
+        assert_eq!(data.channel_id, 340);
+        assert_eq!(data.channel_name, "ticker");
+        assert_eq!(data.pair, "XBT/USD");
+        assert_eq!(data.payload["c"][0], "5525.40000");
+        assert!(!data.is_book_snapshot());
+        
+        let snapshot = WebSocketMessage::DataArray(vec![
+            json!(0),
+            json!({ "as": [["5541.30000", "2.50700000", "1534614248.123678"]], "bs": [["5541.20000", "1.52900000", "1534614248.765567"]] }),
+            json!("book-10"),
+            json!("XBT/USD"),
+        ]);
+        assert!(snapshot.channel_data().unwrap().is_book_snapshot());
+        
+        let update = WebSocketMessage::DataArray(vec![
+            json!(1234),
+            json!({ "a": [["5541.30000", "2.50700000", "1534614248.456738"]] }),
+            json!({ "b": [["5541.30000", "0.00000000", "1534614335.345903"]], "c": "974942666" }),
+            json!("book-10"),
+            json!("XBT/USD"),
+        ]);
+        let data = update.channel_data().unwrap();
+        assert!(!data.is_book_snapshot());
+        assert!(data.payload.get("a").is_some());
+        assert!(data.payload.get("b").is_some());
+        assert_eq!(data.payload["c"], "974942666");
+        assert_eq!(data.pair, "XBT/USD");
+        
+        // Private channels have no channel ID
+        let own_trades = WebSocketMessage::DataArray(vec![json!([]), json!("ownTrades"), json!({ "sequence": 1 })]);
+        assert!(own_trades.channel_data().is_none());
+    }
+    
+    #[test]
+    fn test_private_subscription_serialization() {
+        let request = WebSocketSubscriptionRequest::new_with_type(WebSocketSubscriptionType::OwnTrades)
+            .with_token("abc");
+        
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value, json!({
+            "event": "subscribe",
+            "subscription": { "name": "ownTrades", "token": "abc" }
+        }));
+    }
+    
+    #[test]
+    fn test_parse_own_trades() {
+        let text = r#"[[{"TDLH43-DVQXD-2KHVYY":{"cost":"1000000.00000","fee":"1600.00000","margin":"0.00000","ordertxid":"TDLH43-DVQXD-2KHVYY","ordertype":"limit","pair":"XBT/EUR","postxid":"OGTT3Y-C6I3P-XRI6HX","price":"100000.00000","time":"1560516023.070651","type":"sell","vol":"1000000000.00000000"}}],"ownTrades",{"sequence":2948}]"#;
+        let message: WebSocketMessage = serde_json::from_str(text).unwrap();
+        
+        let own_trades = message.own_trades().unwrap();
+        assert_eq!(own_trades.sequence, Some(2948));
+        assert_eq!(own_trades.trades[0]["TDLH43-DVQXD-2KHVYY"].type_, "sell");
+        assert!(message.open_orders().is_none());
+    }
+    
+    #[test]
+    fn test_connection_state_from_system_status() {
+        assert_eq!(ConnectionState::from_system_status("online"), ConnectionState::Online);
+        assert_eq!(ConnectionState::from_system_status("maintenance"), ConnectionState::Maintenance);
+        assert_eq!(ConnectionState::from_system_status("cancel_only"), ConnectionState::Maintenance);
+    }
+}
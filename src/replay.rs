@@ -0,0 +1,188 @@
+//! Record and replay API responses
+//!
+//! [`RecordTransport`] wraps another transport and saves every response body to a
+//! directory. [`ReplayTransport`] serves those saved responses without touching the
+//! network, so code built on the client can be tested against real captured data:
+//!
+//! ```no_run
+//! use na_kraken_client::replay::{RecordTransport, ReplayTransport};
+//! use na_kraken_client::{Config, KrakenClient};
+//!
+//! # fn main() -> na_kraken_client::error::Result<()> {
+//! // Capture responses from the live API once
+//! let transport = RecordTransport::new(reqwest::Client::new(), "tests/recordings");
+//! let client = KrakenClient::with_transport(Config::default(), transport)?;
+//!
+//! // Then serve them in tests
+//! let client = KrakenClient::with_transport(Config::default(), ReplayTransport::new("tests/recordings"))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Recordings are keyed by method, endpoint and parameters. The `nonce` and `otp`
+//! parameters change on every private call, so they are left out of the key.
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::error::{Error, Result};
+use crate::transport::{Method, Transport, TransportRequest, TransportResponse};
+
+/// Parameters that differ between otherwise identical private calls
+const VOLATILE_PARAMS: &[&str] = &["nonce", "otp"];
+
+/// Get the file name a request's response is recorded under
+fn recording_name(request: &TransportRequest) -> Result<String> {
+    let url = Url::parse(&request.url)?;
+    
+    let query = match request.method {
+        Method::Get => url.query().unwrap_or_default(),
+        Method::Post => request.body.as_deref().unwrap_or_default(),
+    };
+    
+    let mut params: Vec<&str> = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .filter(|param| !VOLATILE_PARAMS.iter().any(|name| param.split('=').next() == Some(name)))
+        .collect();
+    params.sort_unstable();
+    
+    let method = match request.method {
+        Method::Get => "GET",
+        Method::Post => "POST",
+    };
+    
+    let digest = Sha256::digest(format!("{} {}?{}", method, url.path(), params.join("&")));
+    let hash: String = digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+    
+    let endpoint = url.path().trim_matches('/').replace('/', "_");
+    
+    Ok(format!("{}-{}.json", endpoint, hash))
+}
+
+/// Transport that saves every response it receives to a directory
+#[derive(Debug)]
+pub struct RecordTransport<T> {
+    /// Transport that sends the requests
+    inner: T,
+    
+    /// Directory the responses are written to
+    dir: PathBuf,
+}
+
+impl<T: Transport> RecordTransport<T> {
+    /// Record the responses from `inner` into `dir`, which is created if needed
+    pub fn new(inner: T, dir: impl Into<PathBuf>) -> Self {
+        Self { inner, dir: dir.into() }
+    }
+    
+    /// Get the directory the responses are written to
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for RecordTransport<T> {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let name = recording_name(&request)?;
+        let response = self.inner.send(request).await?;
+        
+        // Error pages from proxies aren't API responses, so only successful ones are kept
+        if (200..300).contains(&response.status) {
+            let path = self.dir.join(name);
+            
+            let written = async {
+                tokio::fs::create_dir_all(&self.dir).await?;
+                tokio::fs::write(&path, &response.body).await
+            };
+            written.await.map_err(|e| Error::Other(format!("Failed to write recording {}: {}", path.display(), e)))?;
+            
+            log_debug!("Recorded response to {}", path.display());
+        }
+        
+        Ok(response)
+    }
+}
+
+/// Transport that serves responses saved by a [`RecordTransport`]
+#[derive(Debug, Clone)]
+pub struct ReplayTransport {
+    /// Directory the responses are read from
+    dir: PathBuf,
+}
+
+impl ReplayTransport {
+    /// Serve the responses recorded in `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+    
+    /// Get the directory the responses are read from
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let path = self.dir.join(recording_name(&request)?);
+        
+        let body = tokio::fs::read(&path).await
+            .map_err(|e| Error::Other(format!("No recorded response for {} at {}: {}", request.url, path.display(), e)))?;
+        
+        Ok(TransportResponse::json(body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::KrakenClient;
+    use crate::config::Config;
+    use crate::transport::mock::MockTransport;
+    
+    /// Answer the balance and the server time
+    fn mock_transport() -> MockTransport {
+        MockTransport::new(|request| {
+            if request.url.ends_with("/0/private/Balance") {
+                return Ok(TransportResponse::json(r#"{"error":[],"result":{"ZUSD":"171288.6158"}}"#));
+            }
+            
+            Ok(TransportResponse::json(r#"{"error":[],"result":{"unixtime":1688669448,"rfc1123":"Thu,  6 Jul 23 18:50:48 +0000"}}"#))
+        })
+    }
+    
+    #[test]
+    fn test_recording_name_ignores_nonce_and_param_order() {
+        let first = TransportRequest::post("https://api.kraken.com/0/private/Balance", "nonce=1&asset=XBT&otp=123");
+        let second = TransportRequest::post("https://api.kraken.com/0/private/Balance", "asset=XBT&nonce=2");
+        let other = TransportRequest::post("https://api.kraken.com/0/private/Balance", "asset=ETH&nonce=3");
+        
+        assert_eq!(recording_name(&first).unwrap(), recording_name(&second).unwrap());
+        assert_ne!(recording_name(&first).unwrap(), recording_name(&other).unwrap());
+        assert!(recording_name(&first).unwrap().starts_with("0_private_Balance-"));
+    }
+    
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = std::env::temp_dir().join(format!("kraken-replay-{}", uuid::Uuid::new_v4()));
+        let config = Config::new().with_api_key("key").with_api_secret("c2VjcmV0");
+        
+        let client = KrakenClient::with_transport(config.clone(), RecordTransport::new(mock_transport(), &dir)).unwrap();
+        client.public().get_server_time().await.unwrap();
+        client.private().get_balance().await.unwrap();
+        
+        let client = KrakenClient::with_transport(config, ReplayTransport::new(&dir)).unwrap();
+        assert_eq!(client.public().get_server_time().await.unwrap().unixtime, 1688669448);
+        assert_eq!(client.private().get_balance().await.unwrap()["ZUSD"], "171288.6158");
+        
+        let error = client.public().get_ticker(vec!["XBTUSD"]).await.unwrap_err();
+        assert!(matches!(error, Error::Other(ref msg) if msg.starts_with("No recorded response")));
+        
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
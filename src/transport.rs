@@ -0,0 +1,229 @@
+//! HTTP transport used by the REST API endpoints
+//!
+//! Requests are sent through the [`Transport`] trait so that a mock transport
+//! returning canned responses can be injected in place of the network.
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+/// HTTP methods used by the Kraken REST API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    /// GET request
+    Get,
+    
+    /// POST request
+    Post,
+}
+
+/// HTTP request sent through a transport
+#[derive(Debug, Clone)]
+pub struct TransportRequest {
+    /// Request method
+    pub method: Method,
+    
+    /// Full request URL, including any query string
+    pub url: String,
+    
+    /// Request headers
+    pub headers: Vec<(String, String)>,
+    
+    /// Request body
+    pub body: Option<String>,
+    
+    /// Request timeout
+    pub timeout: Option<Duration>,
+    
+    /// Maximum size of the response body in bytes
+    pub max_response_bytes: Option<usize>,
+}
+
+impl TransportRequest {
+    /// Create a GET request
+    pub fn get(url: impl Into<String>) -> Self {
+        Self {
+            method: Method::Get,
+            url: url.into(),
+            headers: Vec::new(),
+            body: None,
+            timeout: None,
+            max_response_bytes: None,
+        }
+    }
+    
+    /// Create a POST request with the given body
+    pub fn post(url: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            method: Method::Post,
+            url: url.into(),
+            headers: Vec::new(),
+            body: Some(body.into()),
+            timeout: None,
+            max_response_bytes: None,
+        }
+    }
+    
+    /// Add a header
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+    
+    /// Set the timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    
+    /// Set the maximum size of the response body
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_response_bytes);
+        self
+    }
+}
+
+/// Error for a response body larger than `max_response_bytes`
+pub(crate) fn response_too_large(max_response_bytes: usize) -> Error {
+    Error::Api(format!("Response body exceeds the limit of {} bytes", max_response_bytes).into())
+}
+
+/// HTTP response received from a transport
+#[derive(Debug, Clone)]
+pub struct TransportResponse {
+    /// Status code
+    pub status: u16,
+    
+    /// Response headers, keyed by lowercase name
+    pub headers: HashMap<String, String>,
+    
+    /// Response body
+    pub body: Vec<u8>,
+}
+
+impl TransportResponse {
+    /// Create a `200 OK` response with a JSON body
+    pub fn json(body: impl Into<Vec<u8>>) -> Self {
+        let mut headers = HashMap::new();
+        headers.insert("content-type".to_string(), "application/json".to_string());
+        
+        Self { status: 200, headers, body: body.into() }
+    }
+    
+    /// Get the content type
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers.get("content-type").map(|v| v.as_str())
+    }
+    
+    /// Turn a non-2xx response into [`Error::HttpStatus`]
+    pub fn error_for_status(self) -> Result<Self> {
+        if (200..300).contains(&self.status) {
+            return Ok(self);
+        }
+        
+        Err(Error::HttpStatus {
+            code: self.status,
+            body: String::from_utf8_lossy(&self.body).into_owned().into(),
+        })
+    }
+}
+
+/// Sends HTTP requests on behalf of the API endpoints
+#[async_trait]
+pub trait Transport: fmt::Debug + Send + Sync {
+    /// Send a request and return the response
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse>;
+}
+
+#[async_trait]
+impl Transport for reqwest::Client {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let mut builder = match request.method {
+            Method::Get => self.get(&request.url),
+            Method::Post => self.post(&request.url),
+        };
+        
+        for (name, value) in request.headers {
+            builder = builder.header(name, value);
+        }
+        
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        
+        let mut response = builder.send().await?;
+        
+        let status = response.status().as_u16();
+        let headers = response.headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str().to_lowercase(), value.to_str().ok()?.to_string())))
+            .collect();
+        
+        let body = match request.max_response_bytes {
+            Some(max_response_bytes) => {
+                if response.content_length().is_some_and(|length| length > max_response_bytes as u64) {
+                    return Err(response_too_large(max_response_bytes));
+                }
+                
+                // Read chunk by chunk so an oversized body is never held in full
+                let mut body = Vec::new();
+                while let Some(chunk) = response.chunk().await? {
+                    if body.len() + chunk.len() > max_response_bytes {
+                        return Err(response_too_large(max_response_bytes));
+                    }
+                    body.extend_from_slice(&chunk);
+                }
+                body
+            }
+            None => response.bytes().await?.to_vec(),
+        };
+        
+        Ok(TransportResponse { status, headers, body })
+    }
+}
+
+/// Test double for [`Transport`]
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    
+    /// Transport answering every request with a closure
+    pub(crate) struct MockTransport(Box<dyn Fn(TransportRequest) -> Result<TransportResponse> + Send + Sync>);
+    
+    impl MockTransport {
+        /// Answer each request with the response returned by `respond`
+        pub(crate) fn new(respond: impl Fn(TransportRequest) -> Result<TransportResponse> + Send + Sync + 'static) -> Self {
+            Self(Box::new(respond))
+        }
+        
+        /// Answer every request with the same JSON body
+        pub(crate) fn json(body: &'static str) -> Self {
+            Self::new(move |_| Ok(TransportResponse::json(body)))
+        }
+        
+        /// Fail the test on any request
+        pub(crate) fn unreachable() -> Self {
+            Self::new(|request| panic!("Unexpected request to {}", request.url))
+        }
+    }
+    
+    impl fmt::Debug for MockTransport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("MockTransport")
+        }
+    }
+    
+    #[async_trait]
+    impl Transport for MockTransport {
+        async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+            (self.0)(request)
+        }
+    }
+}
@@ -1,7 +1,9 @@
 //! Utility functions for the Kraken API client
 
 use std::collections::HashMap;
-use url::Url;
+use url::{form_urlencoded, Url};
+
+use crate::models::market::AssetPair;
 
 /// Build a URL with query parameters
 pub fn build_url(base_url: &str, path: &str, params: Option<&HashMap<String, String>>) -> String {
@@ -23,14 +25,191 @@ pub fn build_url(base_url: &str, path: &str, params: Option<&HashMap<String, Str
 }
 
 /// Convert a HashMap to a URL encoded string
+///
+/// Parameters are sorted by key so the encoded body, and therefore the request
+/// signature, is stable across runs.
 pub fn hashmap_to_url_encoded(params: &HashMap<String, String>) -> String {
-    params
-        .iter()
-        .map(|(key, value)| format!("{}={}", key, value))
+    let mut serializer = form_urlencoded::Serializer::new(String::new());
+    
+    for (key, value) in sorted_params(params) {
+        serializer.append_pair(key, value);
+    }
+    
+    serializer.finish()
+}
+
+/// Sort parameters by key
+fn sorted_params(params: &HashMap<String, String>) -> Vec<(&String, &String)> {
+    let mut sorted: Vec<(&String, &String)> = params.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+    sorted
+}
+
+/// Parameters whose values must never appear in logs
+const SENSITIVE_PARAMS: &[&str] = &["otp"];
+
+/// Format parameters for logging
+///
+/// Values of sensitive parameters such as one-time passwords are redacted.
+pub fn sanitize_params(params: &HashMap<String, String>) -> String {
+    sorted_params(params)
+        .into_iter()
+        .map(|(key, value)| {
+            if SENSITIVE_PARAMS.contains(&key.as_str()) {
+                format!("{}=<redacted>", key)
+            } else {
+                format!("{}={}", key, value)
+            }
+        })
         .collect::<Vec<String>>()
         .join("&")
 }
 
+/// Legacy asset names Kraken reports with an `X` (crypto) or `Z` (fiat) class prefix
+const PREFIXED_ASSETS: &[&str] = &[
+    "XXBT", "XETH", "XETC", "XLTC", "XMLN", "XREP", "XXDG", "XXLM", "XXMR", "XXRP", "XZEC",
+    "ZAUD", "ZCAD", "ZEUR", "ZGBP", "ZJPY", "ZUSD",
+];
+
+/// Common asset names that Kraken knows under a different code
+const ASSET_ALIASES: &[(&str, &str)] = &[("BTC", "XBT"), ("DOGE", "XDG")];
+
+/// Normalize an asset name to Kraken's short form
+///
+/// Aliases such as `BTC` are mapped to Kraken's code (`XBT`) and the legacy
+/// `X`/`Z` class prefix is stripped, so `btc`, `XBT` and `XXBT` all become `XBT`.
+pub fn normalize_asset(asset: &str) -> String {
+    let asset = asset.to_uppercase();
+    
+    if PREFIXED_ASSETS.contains(&asset.as_str()) {
+        return asset[1..].to_string();
+    }
+    
+    ASSET_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == asset)
+        .map(|(_, code)| code.to_string())
+        .unwrap_or(asset)
+}
+
+/// Normalize a pair name to Kraken's short form
+///
+/// `XXBTZUSD`, `XBTUSD`, `BTCUSD` and `BTC/USD` all become `XBTUSD`.
+pub fn normalize_pair(pair: &str) -> String {
+    let pair = pair.to_uppercase();
+    
+    if let Some((base, quote)) = pair.split_once('/') {
+        return format!("{}{}", normalize_asset(base), normalize_asset(quote));
+    }
+    
+    if pair.len() == 8 {
+        let (base, quote) = pair.split_at(4);
+        if PREFIXED_ASSETS.contains(&base) && PREFIXED_ASSETS.contains(&quote) {
+            return format!("{}{}", &base[1..], &quote[1..]);
+        }
+    }
+    
+    for (alias, code) in ASSET_ALIASES {
+        if let Some(quote) = pair.strip_prefix(alias) {
+            return format!("{}{}", code, quote);
+        }
+        
+        if let Some(base) = pair.strip_suffix(alias) {
+            return format!("{}{}", base, code);
+        }
+    }
+    
+    pair
+}
+
+/// Look up pair-keyed results by the pair name the caller requested
+///
+/// Kraken keys results by its own pair name (e.g. `XXBTZUSD` for a request for
+/// `XBTUSD`), so lookups fall back to comparing normalized names.
+pub trait PairLookup<V> {
+    /// Get the entry for a pair, regardless of which naming form was used
+    fn get_pair(&self, pair: &str) -> Option<&V>;
+}
+
+impl<V> PairLookup<V> for HashMap<String, V> {
+    fn get_pair(&self, pair: &str) -> Option<&V> {
+        if let Some(value) = self.get(pair) {
+            return Some(value);
+        }
+        
+        let pair = normalize_pair(pair);
+        self.iter()
+            .find(|(key, _)| normalize_pair(key) == pair)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Index between the names Kraken uses for each pair
+///
+/// Maps the canonical REST key (e.g. `XXBTZUSD`), the alternate name (`XBTUSD`)
+/// and the WebSocket name (`XBT/USD`) onto each other.
+#[derive(Debug, Clone, Default)]
+pub struct PairIndex {
+    /// Canonical key by WebSocket name
+    by_wsname: HashMap<String, String>,
+    
+    /// Canonical key by alternate name
+    by_altname: HashMap<String, String>,
+    
+    /// Alternate and WebSocket names by canonical key
+    names: HashMap<String, (Option<String>, Option<String>)>,
+}
+
+impl PairIndex {
+    /// Build an index from the result of [`crate::api::public::PublicApi::get_asset_pairs`]
+    pub fn new(pairs: &HashMap<String, AssetPair>) -> Self {
+        let mut index = Self::default();
+        
+        for (key, pair) in pairs {
+            if let Some(ref wsname) = pair.wsname {
+                index.by_wsname.insert(wsname.clone(), key.clone());
+            }
+            
+            if let Some(ref altname) = pair.altname {
+                index.by_altname.insert(altname.clone(), key.clone());
+            }
+            
+            index.names.insert(key.clone(), (pair.altname.clone(), pair.wsname.clone()));
+        }
+        
+        index
+    }
+    
+    /// Get the canonical key of a pair by its WebSocket name, e.g. `XBT/USD`
+    pub fn by_wsname(&self, wsname: &str) -> Option<&str> {
+        self.by_wsname.get(wsname).map(|key| key.as_str())
+    }
+    
+    /// Get the canonical key of a pair by its alternate name, e.g. `XBTUSD`
+    pub fn by_altname(&self, altname: &str) -> Option<&str> {
+        self.by_altname.get(altname).map(|key| key.as_str())
+    }
+    
+    /// Get the canonical key of a pair given any of its names
+    pub fn canonical(&self, pair: &str) -> Option<&str> {
+        if let Some((key, _)) = self.names.get_key_value(pair) {
+            return Some(key.as_str());
+        }
+        
+        self.by_wsname(pair).or_else(|| self.by_altname(pair))
+    }
+    
+    /// Get the WebSocket name of a pair given any of its names
+    pub fn wsname(&self, pair: &str) -> Option<&str> {
+        self.names.get(self.canonical(pair)?)?.1.as_deref()
+    }
+    
+    /// Get the alternate name of a pair given any of its names
+    pub fn altname(&self, pair: &str) -> Option<&str> {
+        self.names.get(self.canonical(pair)?)?.0.as_deref()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,4 +243,94 @@ mod tests {
         assert!(encoded.contains("pair=XBTUSD"));
         assert_eq!(encoded.split('&').count(), 2);
     }
+    
+    #[test]
+    fn test_hashmap_to_url_encoded_is_sorted() {
+        let mut params = HashMap::new();
+        params.insert("pair".to_string(), "XBTUSD".to_string());
+        params.insert("type".to_string(), "buy".to_string());
+        params.insert("nonce".to_string(), "1614232229325".to_string());
+        params.insert("ordertype".to_string(), "limit".to_string());
+        
+        let encoded = hashmap_to_url_encoded(&params);
+        assert_eq!(encoded, "nonce=1614232229325&ordertype=limit&pair=XBTUSD&type=buy");
+    }
+    
+    #[test]
+    fn test_hashmap_to_url_encoded_escapes_values() {
+        let mut params = HashMap::new();
+        params.insert("from".to_string(), "Spot Wallet".to_string());
+        
+        assert_eq!(hashmap_to_url_encoded(&params), "from=Spot+Wallet");
+    }
+    
+    #[test]
+    fn test_sanitize_params() {
+        let mut params = HashMap::new();
+        params.insert("otp".to_string(), "123456".to_string());
+        params.insert("pair".to_string(), "XBTUSD".to_string());
+        
+        assert_eq!(sanitize_params(&params), "otp=<redacted>&pair=XBTUSD");
+    }
+    
+    #[test]
+    fn test_normalize_asset() {
+        assert_eq!(normalize_asset("XXBT"), "XBT");
+        assert_eq!(normalize_asset("btc"), "XBT");
+        assert_eq!(normalize_asset("ZUSD"), "USD");
+        assert_eq!(normalize_asset("XDG"), "XDG");
+        assert_eq!(normalize_asset("DOGE"), "XDG");
+        assert_eq!(normalize_asset("XTZ"), "XTZ");
+        assert_eq!(normalize_asset("DOT"), "DOT");
+    }
+    
+    #[test]
+    fn test_normalize_pair() {
+        assert_eq!(normalize_pair("XXBTZUSD"), "XBTUSD");
+        assert_eq!(normalize_pair("XBTUSD"), "XBTUSD");
+        assert_eq!(normalize_pair("BTCUSD"), "XBTUSD");
+        assert_eq!(normalize_pair("btc/usd"), "XBTUSD");
+        assert_eq!(normalize_pair("ETHBTC"), "ETHXBT");
+        assert_eq!(normalize_pair("XETHZEUR"), "ETHEUR");
+        assert_eq!(normalize_pair("DOTUSD"), "DOTUSD");
+    }
+    
+    #[test]
+    fn test_get_pair() {
+        let mut results = HashMap::new();
+        results.insert("XXBTZUSD".to_string(), 1);
+        results.insert("DOTUSD".to_string(), 2);
+        
+        assert_eq!(results.get_pair("XXBTZUSD"), Some(&1));
+        assert_eq!(results.get_pair("XBTUSD"), Some(&1));
+        assert_eq!(results.get_pair("BTC/USD"), Some(&1));
+        assert_eq!(results.get_pair("DOTUSD"), Some(&2));
+        assert_eq!(results.get_pair("ETHUSD"), None);
+    }
+    
+    #[test]
+    fn test_pair_index() {
+        let pair = |altname: &str, wsname: Option<&str>| {
+            let mut pair: AssetPair = serde_json::from_value(serde_json::json!({})).unwrap();
+            pair.altname = Some(altname.to_string());
+            pair.wsname = wsname.map(str::to_string);
+            pair
+        };
+        
+        let mut pairs = HashMap::new();
+        pairs.insert("XXBTZUSD".to_string(), pair("XBTUSD", Some("XBT/USD")));
+        pairs.insert("DOTUSD".to_string(), pair("DOTUSD", None));
+        
+        let index = PairIndex::new(&pairs);
+        
+        assert_eq!(index.by_wsname("XBT/USD"), Some("XXBTZUSD"));
+        assert_eq!(index.by_altname("XBTUSD"), Some("XXBTZUSD"));
+        assert_eq!(index.canonical("XXBTZUSD"), Some("XXBTZUSD"));
+        assert_eq!(index.canonical("XBT/USD"), Some("XXBTZUSD"));
+        assert_eq!(index.canonical("DOTUSD"), Some("DOTUSD"));
+        assert_eq!(index.wsname("XBTUSD"), Some("XBT/USD"));
+        assert_eq!(index.altname("XBT/USD"), Some("XBTUSD"));
+        assert_eq!(index.wsname("DOTUSD"), None);
+        assert_eq!(index.canonical("ETH/USD"), None);
+    }
 }